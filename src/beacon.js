@@ -0,0 +1,23 @@
+// Sends events for emitters in `emit_web` when the page is closed.
+
+export function emit_web_on_unload(f) {
+    // Emitters may be used outside of a page, like in NodeJS or a worker
+    if (typeof addEventListener !== "function" || typeof document === "undefined") {
+        return;
+    }
+
+    addEventListener("pagehide", () => f());
+    document.addEventListener("visibilitychange", () => {
+        if (document.visibilityState === "hidden") {
+            f();
+        }
+    });
+}
+
+export function emit_web_send_beacon(url, body, type) {
+    try {
+        return navigator.sendBeacon(url, new Blob([body], { type }));
+    } catch {
+        return false;
+    }
+}
@@ -0,0 +1,276 @@
+/*!
+Send queued events when the page is closed using the Beacon API.
+*/
+
+use alloc::{collections::VecDeque, string::String};
+
+use wasm_bindgen::prelude::*;
+
+use crate::FetchFormat;
+
+// Browsers limit the total size of beacons that can be queued
+const BEACON_LIMIT: usize = 64 * 1024;
+
+/**
+A [`Beacon`] that sends events to `url` using the [Beacon API](https://developer.mozilla.org/en-US/docs/Web/API/Beacon_API).
+*/
+pub fn beacon(url: impl Into<String>) -> Beacon {
+    Beacon::new(url)
+}
+
+/**
+Send queued events to an HTTP endpoint using the [Beacon API](https://developer.mozilla.org/en-US/docs/Web/API/Beacon_API) when the page is closed or hidden.
+
+Emitters that queue events, like [`crate::FetchEmitter`], can't wait for them to be sent before the page closes.
+A beacon is handed to the browser to send, even after the page is gone.
+Queued events are sent when the page fires a [`pagehide`](https://developer.mozilla.org/en-US/docs/Web/API/Window/pagehide_event) event, or a [`visibilitychange`](https://developer.mozilla.org/en-US/docs/Web/API/Document/visibilitychange_event) event to hidden.
+
+Events are split across multiple beacons to keep each under the 64KiB limit browsers enforce.
+An event that's too large to fit in a beacon by itself is dropped.
+
+Beacons can't carry custom headers, so the endpoint can't require them.
+Browsers may also refuse to send beacons with a `Content-Type` of `application/json` to another origin unless it allows them through CORS.
+*/
+#[derive(Debug, Clone)]
+pub struct Beacon {
+    url: String,
+    format: FetchFormat,
+}
+
+impl Beacon {
+    /**
+    Create a new beacon that sends events to `url`.
+    */
+    pub fn new(url: impl Into<String>) -> Self {
+        Beacon {
+            url: url.into(),
+            format: FetchFormat::Json,
+        }
+    }
+
+    /**
+    Set the format of beacons.
+
+    The default format is [`FetchFormat::Json`].
+    */
+    pub fn format(mut self, format: FetchFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /**
+    Send the records in `queue`, splitting them across as many beacons as needed.
+
+    Records are only removed from the queue once the browser accepts the beacon carrying them.
+    */
+    pub(crate) fn send(&self, queue: &mut VecDeque<impl AsRef<str>>) {
+        while !queue.is_empty() {
+            let mut records = 0;
+            let mut len = 0;
+
+            for record in queue.iter() {
+                let record = record.as_ref();
+
+                if self.format.body_len(records + 1, len + record.len()) > BEACON_LIMIT {
+                    break;
+                }
+
+                records += 1;
+                len += record.len();
+            }
+
+            // The first record is too big to ever be sent
+            if records == 0 {
+                queue.pop_front();
+                continue;
+            }
+
+            let body = self.format.body(queue.iter().take(records));
+
+            if !shim::emit_web_send_beacon(&self.url, &body, self.format.content_type()) {
+                return;
+            }
+
+            queue.drain(..records);
+        }
+    }
+}
+
+/**
+Call `f` whenever the page is closed or hidden.
+
+This does nothing outside of a page.
+*/
+pub(crate) fn on_unload(f: impl FnMut() + 'static) {
+    shim::emit_web_on_unload(&Closure::<dyn FnMut()>::new(f).into_js_value());
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/beacon.js")]
+    extern "C" {
+        pub fn emit_web_on_unload(f: &JsValue);
+        pub fn emit_web_send_beacon(url: &str, body: &str, content_type: &str) -> bool;
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use core::time::Duration;
+
+    use emit::Emitter as _;
+    use js_sys::{Array, Reflect, JSON};
+
+    use crate::{
+        fetch,
+        test_util::{get, Globals},
+    };
+
+    #[wasm_bindgen(inline_js = "
+    export function stub_page(accept) {
+        const listeners = [];
+        const beacons = [];
+
+        globalThis.addEventListener = (_, f) => listeners.push(f);
+        globalThis.document = { addEventListener: () => {} };
+
+        Object.defineProperty(globalThis, 'navigator', {
+            configurable: true,
+            value: {
+                sendBeacon: (url, blob) => {
+                    if (beacons.length >= page.accept) {
+                        return false;
+                    }
+
+                    beacons.push({ url, blob });
+                    return true;
+                },
+            },
+        });
+
+        const page = { accept, beacons, unload: () => listeners.forEach((f) => f()) };
+        return page;
+    }
+    ")]
+    extern "C" {
+        #[wasm_bindgen(js_name = stub_page)]
+        fn stub_page_js(accept: usize) -> JsValue;
+    }
+
+    fn stub_page(accept: usize) -> (JsValue, Globals) {
+        let globals = Globals::save(&["addEventListener", "document", "navigator"]);
+
+        (stub_page_js(accept), globals)
+    }
+
+    fn unload(page: &JsValue) {
+        get(page, "unload")
+            .dyn_into::<js_sys::Function>()
+            .unwrap()
+            .call0(&JsValue::NULL)
+            .unwrap();
+    }
+
+    async fn beacons(page: &JsValue) -> Vec<Array> {
+        let mut bodies = Vec::new();
+
+        for beacon in Array::from(&get(page, "beacons")) {
+            let text = Reflect::get(&get(&beacon, "blob"), &"text".into())
+                .unwrap()
+                .dyn_into::<js_sys::Function>()
+                .unwrap()
+                .call0(&get(&beacon, "blob"))
+                .unwrap();
+
+            let text = wasm_bindgen_futures::JsFuture::from(js_sys::Promise::from(text))
+                .await
+                .unwrap();
+
+            bodies.push(Array::from(
+                &JSON::parse(&text.as_string().unwrap()).unwrap(),
+            ));
+        }
+
+        bodies
+    }
+
+    fn emitter() -> crate::FetchEmitter {
+        fetch("http://localhost/ingest")
+            .flush_interval(Duration::from_secs(60))
+            .beacon_on_unload(beacon("http://localhost/beacon"))
+            .build()
+    }
+
+    #[wasm_bindgen_test]
+    async fn send_queued_on_unload() {
+        let (page, _globals) = stub_page(usize::MAX);
+        let emitter = emitter();
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+
+        unload(&page);
+
+        let beacons = beacons(&page).await;
+
+        assert_eq!(1, beacons.len());
+        assert_eq!(2, beacons[0].length());
+        assert_eq!(JsValue::from("event 1"), get(&beacons[0].get(0), "msg"));
+
+        // Events are only sent once
+        unload(&page);
+
+        assert_eq!(1, Array::from(&get(&page, "beacons")).length());
+    }
+
+    #[wasm_bindgen_test]
+    async fn split_large_payloads() {
+        let (page, _globals) = stub_page(usize::MAX);
+        let emitter = emitter();
+
+        let data = "a".repeat(40 * 1024);
+        let too_big = "a".repeat(64 * 1024);
+
+        emitter.emit(emit::evt!("event {n}", n: 1, data));
+        emitter.emit(emit::evt!("event {n}", n: 2, data: too_big));
+        emitter.emit(emit::evt!("event {n}", n: 3, data));
+
+        unload(&page);
+
+        let beacons = beacons(&page).await;
+
+        assert_eq!(2, beacons.len());
+        assert_eq!(1, beacons[0].length());
+        assert_eq!(
+            JsValue::from(1),
+            get(&get(&beacons[0].get(0), "props"), "n")
+        );
+        assert_eq!(1, beacons[1].length());
+        assert_eq!(
+            JsValue::from(3),
+            get(&get(&beacons[1].get(0), "props"), "n")
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn keep_events_when_beacon_is_refused() {
+        let (page, _globals) = stub_page(0);
+        let emitter = emitter();
+
+        emitter.emit(emit::evt!("event"));
+
+        unload(&page);
+
+        assert_eq!(0, Array::from(&get(&page, "beacons")).length());
+
+        Reflect::set(&page, &"accept".into(), &1.into()).unwrap();
+
+        unload(&page);
+
+        assert_eq!(1, Array::from(&get(&page, "beacons")).length());
+    }
+}
@@ -0,0 +1,60 @@
+// Posts events between tabs and workers for the `BroadcastChannelEmitter`
+// and `BroadcastChannelReceiver` in `emit_web`.
+//
+// Messages are objects with the `source` of the event and its `record`
+// as a JSON string. Events re-emitted by a receiver aren't posted again,
+// so a context that sends and receives on the same channel doesn't bounce
+// events back and forth with other contexts.
+
+let receiving = false;
+
+export function emit_web_broadcast_open(name) {
+    try {
+        return new BroadcastChannel(name);
+    } catch {
+        return null;
+    }
+}
+
+export function emit_web_broadcast_source() {
+    // Workers and named windows have a `name`
+    return globalThis.name || globalThis.location?.href || "";
+}
+
+export function emit_web_broadcast_post(channel, source, record) {
+    if (channel === null || receiving) {
+        return;
+    }
+
+    try {
+        channel.postMessage({ source, record });
+    } catch {
+        // The channel has been closed
+    }
+}
+
+export function emit_web_broadcast_receive(channel, f) {
+    if (channel === null) {
+        return;
+    }
+
+    channel.onmessage = (e) => {
+        const { source, record } = e.data ?? {};
+
+        if (typeof source !== "string" || typeof record !== "string") {
+            return;
+        }
+
+        receiving = true;
+
+        try {
+            f(source, record);
+        } finally {
+            receiving = false;
+        }
+    };
+}
+
+export function emit_web_broadcast_close(channel) {
+    channel?.close();
+}
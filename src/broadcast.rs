@@ -0,0 +1,257 @@
+/*!
+Share events between tabs and workers using the Broadcast Channel API.
+*/
+
+use alloc::string::String;
+use core::time::Duration;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{record::Received, ser, Local};
+
+/**
+A [`BroadcastChannelEmitter`] that posts events to the channel called `name` using the [Broadcast Channel API](https://developer.mozilla.org/en-US/docs/Web/API/Broadcast_Channel_API).
+
+See [`BroadcastChannelEmitterBuilder`] for configuration.
+*/
+pub fn broadcast_channel(name: impl Into<String>) -> BroadcastChannelEmitterBuilder {
+    BroadcastChannelEmitterBuilder::new(name)
+}
+
+/**
+A [`BroadcastChannelReceiver`] that emits events received on the channel called `name` through `emitter`.
+*/
+pub fn broadcast_channel_receiver(
+    name: &str,
+    emitter: impl emit::Emitter + 'static,
+) -> BroadcastChannelReceiver {
+    BroadcastChannelReceiver::new(name, emitter)
+}
+
+/**
+A builder for a [`BroadcastChannelEmitter`].
+*/
+pub struct BroadcastChannelEmitterBuilder {
+    name: String,
+    source: Option<String>,
+}
+
+impl BroadcastChannelEmitterBuilder {
+    /**
+    Create a new builder for an emitter that posts events to the channel called `name`.
+    */
+    pub fn new(name: impl Into<String>) -> Self {
+        BroadcastChannelEmitterBuilder {
+            name: name.into(),
+            source: None,
+        }
+    }
+
+    /**
+    Set the `source` that receivers attach to events from this emitter.
+
+    The default source is the `name` of the worker or window, if it has one, or the URL of the page.
+    */
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /**
+    Build a [`BroadcastChannelEmitter`] from this configuration.
+    */
+    pub fn build(self) -> BroadcastChannelEmitter {
+        BroadcastChannelEmitter {
+            channel: Local(shim::emit_web_broadcast_open(&self.name)),
+            source: self.source.unwrap_or_else(shim::emit_web_broadcast_source),
+        }
+    }
+}
+
+/**
+An emitter that posts events to a named channel using the [Broadcast Channel API](https://developer.mozilla.org/en-US/docs/Web/API/Broadcast_Channel_API).
+
+Events are posted as records in the schema described by [`crate::FetchFormat`], along with the `source` they came from.
+Other tabs and workers on the same origin can use a [`BroadcastChannelReceiver`] to emit them locally, so events from all of them can be seen in one place.
+
+Events emitted by a [`BroadcastChannelReceiver`] in the same tab or worker aren't posted, so they don't get sent back and forth between contexts.
+If the Broadcast Channel API isn't available then events are discarded.
+The channel is closed when the emitter is dropped.
+
+Use [`broadcast_channel`] to configure a new emitter.
+*/
+pub struct BroadcastChannelEmitter {
+    channel: Local<JsValue>,
+    source: String,
+}
+
+impl emit::Emitter for BroadcastChannelEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let mut buf = ser::Buf::new(false);
+        buf.record(&evt, None);
+
+        shim::emit_web_broadcast_post(&self.channel, &self.source, &buf.json());
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        true
+    }
+}
+
+impl Drop for BroadcastChannelEmitter {
+    fn drop(&mut self) {
+        shim::emit_web_broadcast_close(&self.channel);
+    }
+}
+
+/**
+Receive events posted by [`BroadcastChannelEmitter`]s in other tabs and workers, and emit them locally.
+
+Received events keep their original module, template, extent, and props.
+They also get a `source` prop with the source of the [`BroadcastChannelEmitter`] that posted them, replacing any `source` prop they already had.
+
+Events are received until the receiver is dropped.
+*/
+pub struct BroadcastChannelReceiver {
+    channel: JsValue,
+}
+
+impl BroadcastChannelReceiver {
+    /**
+    Start receiving events on the channel called `name`, emitting them through `emitter`.
+    */
+    pub fn new(name: &str, emitter: impl emit::Emitter + 'static) -> Self {
+        let channel = shim::emit_web_broadcast_open(name);
+
+        shim::emit_web_broadcast_receive(
+            &channel,
+            &Closure::<dyn FnMut(String, String)>::new(move |source: String, record: String| {
+                if let Some(received) = Received::from_json(&record) {
+                    received.emit(&emitter, ("source", &*source));
+                }
+            })
+            .into_js_value(),
+        );
+
+        BroadcastChannelReceiver { channel }
+    }
+}
+
+impl Drop for BroadcastChannelReceiver {
+    fn drop(&mut self) {
+        shim::emit_web_broadcast_close(&self.channel);
+    }
+}
+
+mod shim {
+    use alloc::string::String;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/broadcast.js")]
+    extern "C" {
+        pub fn emit_web_broadcast_open(name: &str) -> JsValue;
+        pub fn emit_web_broadcast_source() -> String;
+        pub fn emit_web_broadcast_post(channel: &JsValue, source: &str, record: &str);
+        pub fn emit_web_broadcast_receive(channel: &JsValue, f: &JsValue);
+        pub fn emit_web_broadcast_close(channel: &JsValue);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use core::cell::RefCell;
+    use std::rc::Rc;
+
+    use emit::{Emitter as _, Props as _};
+
+    use crate::test_util::sleep;
+
+    type Collected = Rc<RefCell<Vec<(String, String, Option<emit::Extent>)>>>;
+
+    fn collect() -> (Collected, impl emit::Emitter + 'static) {
+        let received = Rc::new(RefCell::new(Vec::new()));
+
+        let emitter = emit::emitter::from_fn({
+            let received = received.clone();
+
+            move |evt| {
+                received.borrow_mut().push((
+                    evt.msg().to_string(),
+                    evt.props().pull::<String, _>("source").unwrap_or_default(),
+                    evt.extent().cloned(),
+                ))
+            }
+        });
+
+        (received, emitter)
+    }
+
+    #[wasm_bindgen_test]
+    async fn receive_events() {
+        let (received, emitter) = collect();
+        let _receiver = broadcast_channel_receiver("receive_events", emitter);
+
+        let emitter = broadcast_channel("receive_events").source("tab").build();
+
+        let extent = emit::Timestamp::from_unix(Duration::from_secs(1)).unwrap()
+            ..emit::Timestamp::from_unix(Duration::from_secs(2)).unwrap();
+
+        emitter.emit(emit::evt!(extent: extent.clone(), "event {n}", n: 1));
+
+        sleep(50.0).await;
+
+        let received = received.borrow();
+
+        assert_eq!(1, received.len());
+
+        let (msg, source, ts) = &received[0];
+
+        assert_eq!("event 1", msg);
+        assert_eq!("tab", source);
+        assert_eq!(
+            Some(&extent),
+            ts.as_ref().and_then(|extent| extent.as_range())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn dont_repost_received_events() {
+        // A receiver that re-emits events into an emitter for the same channel
+        let _relay = broadcast_channel_receiver(
+            "dont_repost_received_events",
+            broadcast_channel("dont_repost_received_events").build(),
+        );
+
+        let (received, emitter) = collect();
+        let _receiver = broadcast_channel_receiver("dont_repost_received_events", emitter);
+
+        let emitter = broadcast_channel("dont_repost_received_events").build();
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(50.0).await;
+
+        assert_eq!(1, received.borrow().len());
+    }
+
+    #[wasm_bindgen_test]
+    async fn stop_receiving_when_dropped() {
+        let (received, emitter) = collect();
+        let receiver = broadcast_channel_receiver("stop_receiving_when_dropped", emitter);
+
+        drop(receiver);
+
+        let emitter = broadcast_channel("stop_receiving_when_dropped").build();
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(50.0).await;
+
+        assert!(received.borrow().is_empty());
+    }
+}
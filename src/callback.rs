@@ -0,0 +1,241 @@
+/*!
+Deliver events to a JavaScript function.
+*/
+
+use core::time::Duration;
+
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::{level, ser, Local};
+
+/**
+A [`JsCallbackEmitter`] that calls `callback` with each event.
+*/
+pub fn js_callback(callback: Function) -> JsCallbackEmitter {
+    JsCallbackEmitter::new(callback)
+}
+
+/**
+An emitter that calls a JavaScript function with each event.
+
+This can be used to forward events into existing JavaScript tooling, like breadcrumbs in an error tracker, without writing an emitter for each.
+
+The function is called with a single object with the following fields:
+
+- `msg`: The event's rendered message.
+- `tpl`: The event's template.
+- `mdl`: The module the event was emitted from.
+- `lvl`: The event's level as a string, like `"info"`, or `null` if it doesn't have one.
+- `extent`: An object with `start` and `end` fields as a [`Date`](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Date), or `null` if the event doesn't have an extent. `start` is `null` unless the event's extent is a range, like for spans.
+- `props`: The event's properties, in the same form as [`crate::ConsoleEmitter`] writes them.
+- `traceId`: The event's trace id as a hex string, or `null` if it doesn't have one.
+- `spanId`: The event's span id as a hex string, or `null` if it doesn't have one.
+
+Exceptions thrown by the function are caught and ignored.
+*/
+pub struct JsCallbackEmitter {
+    callback: Local<Function>,
+    always_use_map: bool,
+}
+
+impl JsCallbackEmitter {
+    /**
+    Create a new emitter that calls `callback` with each event.
+    */
+    pub fn new(callback: Function) -> Self {
+        JsCallbackEmitter {
+            callback: Local(callback),
+            always_use_map: false,
+        }
+    }
+
+    /**
+    Whether to always serialize maps in props as a JavaScript [`Map`](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Map).
+
+    See [`crate::ConsoleEmitter::always_use_map`] for details.
+    */
+    pub const fn always_use_map(mut self, always_use_map: bool) -> Self {
+        self.always_use_map = always_use_map;
+        self
+    }
+}
+
+impl emit::Emitter for JsCallbackEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        use emit::{
+            well_known::{KEY_SPAN_ID, KEY_TRACE_ID},
+            Props as _,
+        };
+
+        let evt = evt.to_event();
+
+        let mut buf = ser::Buf::new(self.always_use_map);
+
+        buf.object_begin();
+
+        buf.interned("msg");
+        buf.msg(&evt);
+
+        buf.interned("tpl");
+        buf.tpl(&evt);
+
+        buf.interned("mdl");
+        buf.display(evt.mdl());
+
+        buf.interned("lvl");
+        match level(&evt) {
+            Some(lvl) => buf.interned(lvl),
+            None => buf.null(),
+        }
+
+        buf.interned("extent");
+        match evt.extent() {
+            Some(extent) => {
+                buf.object_begin();
+
+                buf.interned("start");
+                match extent.as_range() {
+                    Some(range) => buf.timestamp(range.start),
+                    None => buf.null(),
+                }
+
+                buf.interned("end");
+                buf.timestamp(*extent.as_point());
+
+                buf.end();
+            }
+            None => buf.null(),
+        }
+
+        buf.interned("props");
+        buf.props(evt.props());
+
+        buf.interned("traceId");
+        match evt.props().pull::<emit::TraceId, _>(KEY_TRACE_ID) {
+            Some(trace_id) => buf.display(trace_id),
+            None => buf.null(),
+        }
+
+        buf.interned("spanId");
+        match evt.props().pull::<emit::SpanId, _>(KEY_SPAN_ID) {
+            Some(span_id) => buf.display(span_id),
+            None => buf.null(),
+        }
+
+        buf.end();
+
+        // Exceptions can't unwind through `emit`, so they're discarded
+        let _ = self.callback.call1(&JsValue::UNDEFINED, &buf.decode());
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        true
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::Emitter as _;
+    use js_sys::{Array, Date};
+
+    use crate::test_util::get;
+
+    #[wasm_bindgen(inline_js = "
+    export function collect() {
+        const events = [];
+        const callback = (evt) => events.push(evt);
+
+        return { events, callback };
+    }
+
+    export function throw_error() {
+        return () => {
+            throw new Error('failed to handle event');
+        };
+    }
+    ")]
+    extern "C" {
+        fn collect() -> JsValue;
+        fn throw_error() -> Function;
+    }
+
+    fn millis(value: &JsValue) -> f64 {
+        value.dyn_ref::<Date>().unwrap().get_time()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn call_with_event() {
+        let collected = collect();
+        let emitter = js_callback(get(&collected, "callback").unchecked_into());
+
+        emitter.emit(emit::warn_evt!(
+            extent: emit::Timestamp::from_unix(Duration::from_secs(1)),
+            "event {n}",
+            n: 1,
+        ));
+
+        let events = Array::from(&get(&collected, "events"));
+        assert_eq!(1, events.length());
+
+        let evt = events.get(0);
+
+        assert_eq!(JsValue::from("event 1"), get(&evt, "msg"));
+        assert_eq!(JsValue::from("event {n}"), get(&evt, "tpl"));
+        assert_eq!(JsValue::from("emit_web::callback::tests"), get(&evt, "mdl"));
+        assert_eq!(JsValue::from("warn"), get(&evt, "lvl"));
+        assert_eq!(JsValue::from(1), get(&get(&evt, "props"), "n"));
+
+        let extent = get(&evt, "extent");
+        assert!(get(&extent, "start").is_null());
+        assert_eq!(1000.0, millis(&get(&extent, "end")));
+
+        assert!(get(&evt, "traceId").is_null());
+        assert!(get(&evt, "spanId").is_null());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn call_with_span() {
+        let collected = collect();
+        let emitter = js_callback(get(&collected, "callback").unchecked_into());
+
+        let trace_id = emit::TraceId::from_u128(1).unwrap();
+        let span_id = emit::SpanId::from_u64(2).unwrap();
+
+        emitter.emit(emit::evt!(
+            extent: emit::Timestamp::from_unix(Duration::from_secs(1)).unwrap()
+                ..emit::Timestamp::from_unix(Duration::from_millis(1500)).unwrap(),
+            "span",
+            evt_kind: "span",
+            trace_id,
+            span_id,
+        ));
+
+        let evt = Array::from(&get(&collected, "events")).get(0);
+
+        let extent = get(&evt, "extent");
+        assert_eq!(1000.0, millis(&get(&extent, "start")));
+        assert_eq!(1500.0, millis(&get(&extent, "end")));
+
+        assert_eq!(
+            JsValue::from("00000000000000000000000000000001"),
+            get(&evt, "traceId")
+        );
+        assert_eq!(JsValue::from("0000000000000002"), get(&evt, "spanId"));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn ignore_exceptions() {
+        let emitter = js_callback(throw_error());
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+
+        assert!(emitter.blocking_flush(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,57 @@
+// Keeps events captured by the `CaptureEmitter` in `emit_web`.
+
+const events = [];
+const waiters = new Set();
+
+export function emit_web_capture(evt) {
+    events.push(evt);
+
+    for (const waiter of waiters) {
+        waiter(evt);
+    }
+}
+
+export function emit_web_captured() {
+    return events.slice();
+}
+
+export function emit_web_clear() {
+    events.length = 0;
+}
+
+export function emit_web_wait_for(predicate, timeout) {
+    return new Promise((resolve, reject) => {
+        const found = events.find(predicate);
+
+        if (found !== undefined) {
+            resolve(found);
+            return;
+        }
+
+        const done = () => {
+            clearTimeout(timer);
+            waiters.delete(waiter);
+        };
+
+        // Errors thrown by the predicate mustn't escape into the emitter
+        const waiter = (evt) => {
+            try {
+                if (predicate(evt)) {
+                    done();
+                    resolve(evt);
+                }
+            } catch (e) {
+                done();
+                reject(e);
+            }
+        };
+
+        const timer = setTimeout(() => {
+            waiters.delete(waiter);
+
+            reject(new Error(`no matching event was captured within ${timeout}ms`));
+        }, timeout);
+
+        waiters.add(waiter);
+    });
+}
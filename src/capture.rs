@@ -0,0 +1,242 @@
+/*!
+Capture events as JavaScript objects so tests can make assertions about them.
+*/
+
+use core::time::Duration;
+
+use js_sys::{Function, Promise};
+use wasm_bindgen::prelude::*;
+
+use crate::{level, ser};
+
+/**
+An emitter that captures events as JavaScript objects for tests to make assertions about.
+*/
+pub const fn capture() -> CaptureEmitter {
+    CaptureEmitter::new()
+}
+
+/**
+An emitter that captures events as JavaScript objects for tests to make assertions about.
+
+Browser test suites, like ones using Playwright or Puppeteer, can use the exported functions `emitWebEvents()`, `emitWebClear()`, and `emitWebWaitFor(predicate, timeout)` to inspect captured events instead of scraping the console.
+See [`captured_events`], [`clear_captured_events`], and [`wait_for_event`] for details.
+
+Each event is captured as an object with the following fields:
+
+- `msg`: The event's rendered message.
+- `tpl`: The event's template.
+- `lvl`: The event's level as a string, like `"info"`, or `null` if it doesn't have one.
+- `extent`: The event's extent, in the same form as [`crate::ConsoleEmitter`] writes it.
+- `props`: The event's properties, in the same form as [`crate::ConsoleEmitter`] writes them.
+
+Events are kept until they're cleared, so this emitter isn't suitable for production use.
+*/
+pub struct CaptureEmitter {
+    always_use_map: bool,
+}
+
+impl CaptureEmitter {
+    /**
+    Create a new instance of the capture emitter.
+    */
+    pub const fn new() -> Self {
+        CaptureEmitter {
+            always_use_map: false,
+        }
+    }
+
+    /**
+    Whether to always serialize maps in props as a JavaScript [`Map`](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Map).
+
+    See [`crate::ConsoleEmitter::always_use_map`] for details.
+    */
+    pub const fn always_use_map(mut self, always_use_map: bool) -> Self {
+        self.always_use_map = always_use_map;
+        self
+    }
+}
+
+impl Default for CaptureEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl emit::Emitter for CaptureEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let mut buf = ser::Buf::new(self.always_use_map);
+
+        buf.object_begin();
+
+        buf.interned("msg");
+        buf.msg(&evt);
+
+        buf.interned("tpl");
+        buf.tpl(&evt);
+
+        buf.interned("lvl");
+        match level(&evt) {
+            Some(lvl) => buf.interned(lvl),
+            None => buf.null(),
+        }
+
+        buf.interned("extent");
+        buf.extent(evt.extent());
+
+        buf.interned("props");
+        buf.props(evt.props());
+
+        buf.end();
+
+        shim::emit_web_capture(&buf.decode());
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        true
+    }
+}
+
+/**
+Get a copy of all events captured by [`CaptureEmitter`]s, in the order they were emitted.
+
+This function is exported to JavaScript as `emitWebEvents`.
+*/
+#[wasm_bindgen(js_name = emitWebEvents)]
+pub fn captured_events() -> JsValue {
+    shim::emit_web_captured()
+}
+
+/**
+Clear all events captured by [`CaptureEmitter`]s.
+
+This function is exported to JavaScript as `emitWebClear`.
+*/
+#[wasm_bindgen(js_name = emitWebClear)]
+pub fn clear_captured_events() {
+    shim::emit_web_clear()
+}
+
+/**
+Wait for an event matching `predicate` to be captured by a [`CaptureEmitter`].
+
+The returned promise resolves with the first matching event, which may have already been captured.
+If no matching event is captured within `timeout` milliseconds then the promise is rejected.
+The default timeout is 5 seconds.
+
+This function is exported to JavaScript as `emitWebWaitFor`:
+
+```js
+const evt = await emitWebWaitFor((evt) => evt.tpl === "checkout completed", 1000);
+```
+*/
+#[wasm_bindgen(js_name = emitWebWaitFor)]
+pub fn wait_for_event(predicate: Function, timeout: Option<f64>) -> Promise {
+    shim::emit_web_wait_for(&predicate, timeout.unwrap_or(5_000.0))
+}
+
+mod shim {
+    use js_sys::{Function, Promise};
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/capture.js")]
+    extern "C" {
+        pub fn emit_web_capture(evt: &JsValue);
+        pub fn emit_web_captured() -> JsValue;
+        pub fn emit_web_clear();
+        pub fn emit_web_wait_for(predicate: &Function, timeout: f64) -> Promise;
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::Emitter as _;
+    use js_sys::Array;
+    use wasm_bindgen_futures::JsFuture;
+
+    use crate::test_util::get;
+
+    fn tpl_eq(tpl: &'static str) -> Function {
+        Closure::<dyn Fn(JsValue) -> bool>::new(move |evt: JsValue| {
+            get(&evt, "tpl").as_string().as_deref() == Some(tpl)
+        })
+        .into_js_value()
+        .unchecked_into()
+    }
+
+    #[wasm_bindgen_test]
+    fn capture_events() {
+        clear_captured_events();
+
+        let emitter = capture();
+
+        emitter.emit(emit::evt!(
+            extent: emit::Timestamp::from_unix(Duration::from_secs(1)),
+            "event {n}",
+            n: 1,
+            lvl: emit::Level::Warn,
+        ));
+        emitter.emit(emit::evt!("event"));
+
+        let events = Array::from(&captured_events());
+        assert_eq!(2, events.length());
+
+        let evt = events.get(0);
+        assert_eq!(JsValue::from("event 1"), get(&evt, "msg"));
+        assert_eq!(JsValue::from("event {n}"), get(&evt, "tpl"));
+        assert_eq!(JsValue::from("warn"), get(&evt, "lvl"));
+        assert_eq!(
+            1000.0,
+            get(&get(&evt, "extent"), "timestamp")
+                .dyn_into::<js_sys::Date>()
+                .unwrap()
+                .get_time()
+        );
+        assert_eq!(JsValue::from(1), get(&get(&evt, "props"), "n"));
+
+        let evt = events.get(1);
+        assert!(get(&evt, "lvl").is_null());
+        assert!(get(&evt, "extent").is_null());
+
+        clear_captured_events();
+
+        assert_eq!(0, Array::from(&captured_events()).length());
+    }
+
+    #[wasm_bindgen_test]
+    async fn wait_for_captured() {
+        clear_captured_events();
+
+        let emitter = capture();
+
+        emitter.emit(emit::evt!("first"));
+
+        let evt = JsFuture::from(wait_for_event(tpl_eq("first"), Some(0.0)))
+            .await
+            .unwrap();
+        assert_eq!(JsValue::from("first"), get(&evt, "msg"));
+
+        let wait = JsFuture::from(wait_for_event(tpl_eq("second"), Some(1_000.0)));
+
+        emitter.emit(emit::evt!("second"));
+
+        let evt = wait.await.unwrap();
+        assert_eq!(JsValue::from("second"), get(&evt, "msg"));
+    }
+
+    #[wasm_bindgen_test]
+    async fn wait_for_timeout() {
+        clear_captured_events();
+
+        assert!(
+            JsFuture::from(wait_for_event(tpl_eq("missing"), Some(10.0)))
+                .await
+                .is_err()
+        );
+    }
+}
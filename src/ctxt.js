@@ -0,0 +1,116 @@
+// Keeps the current context in JS async context for the `AsyncCtxt` in `emit_web`.
+//
+// The store holds a handle with the id of a frame that's kept in Rust.
+// Handles are released back to Rust once they've been garbage collected.
+// Without `FinalizationRegistry`, Rust releases frames itself instead.
+//
+// `AsyncLocalStorage` can change its store for the rest of the current
+// execution, so frames entered in Rust flow into any callbacks scheduled while
+// they're active. `AsyncContext.Variable` and Zone.js can only change their
+// store while running a function, so callbacks need to be bound to carry it.
+// Without any of them, bound callbacks enter their frame in Rust instead.
+
+const ZONE_KEY = "emitWebCtxt";
+
+let backend = null;
+let registry = null;
+let enterBound = null;
+let exitBound = null;
+
+// Stores replaced by `emit_web_ctxt_enter`, restored by `emit_web_ctxt_exit`
+const previous = [];
+
+function handle(id) {
+    const h = { id };
+    registry?.register(h, id);
+
+    return h;
+}
+
+function detect() {
+    if (typeof AsyncContext !== "undefined" && typeof AsyncContext.Variable === "function") {
+        const variable = new AsyncContext.Variable({ name: "emit_web" });
+
+        return {
+            kind: "async-context",
+            get: () => variable.get(),
+            run: (h, f) => variable.run(h, f),
+        };
+    }
+
+    const AsyncLocalStorage = globalThis.AsyncLocalStorage
+        ?? globalThis.process?.getBuiltinModule?.("node:async_hooks")?.AsyncLocalStorage;
+
+    if (typeof AsyncLocalStorage === "function") {
+        const storage = new AsyncLocalStorage();
+
+        return {
+            kind: "async-local-storage",
+            get: () => storage.getStore(),
+            run: (h, f) => storage.run(h, f),
+            enterWith: (h) => storage.enterWith(h),
+        };
+    }
+
+    const Zone = globalThis.Zone;
+
+    if (Zone?.current) {
+        return {
+            kind: "zone",
+            get: () => Zone.current.get(ZONE_KEY),
+            run: (h, f) => Zone.current.fork({ name: "emit_web", properties: { [ZONE_KEY]: h } }).run(f),
+        };
+    }
+
+    return {
+        kind: "thread-local",
+        get: () => undefined,
+        run: (h, f) => {
+            enterBound(h.id);
+
+            try {
+                return f();
+            } finally {
+                exitBound();
+            }
+        },
+    };
+}
+
+export function emit_web_ctxt_init(release, enter, exit) {
+    if (registry === null && typeof FinalizationRegistry === "function") {
+        registry = new FinalizationRegistry(release);
+    }
+
+    enterBound = enter;
+    exitBound = exit;
+
+    backend = detect();
+
+    return backend.kind;
+}
+
+export function emit_web_ctxt_finalizes() {
+    return registry !== null;
+}
+
+export function emit_web_ctxt_get() {
+    return backend.get()?.id;
+}
+
+export function emit_web_ctxt_enter(id) {
+    previous.push(backend.get());
+    backend.enterWith(handle(id));
+}
+
+export function emit_web_ctxt_exit() {
+    backend.enterWith(previous.pop());
+}
+
+export function emit_web_ctxt_bind(id, f) {
+    const h = handle(id);
+
+    return function (...args) {
+        return backend.run(h, () => f.apply(this, args));
+    };
+}
@@ -0,0 +1,582 @@
+/*!
+Store ambient context in JavaScript's async context, so it survives promises and callbacks.
+*/
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::cell::{Cell, RefCell};
+use std::sync::OnceLock;
+
+use emit::{
+    platform::thread_local_ctxt::{ThreadLocalCtxt, ThreadLocalCtxtFrame},
+    Ctxt as _,
+};
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+/**
+An [`AsyncCtxt`] that stores ambient context in JavaScript's async context.
+*/
+pub const fn async_ctxt() -> AsyncCtxt {
+    AsyncCtxt::new()
+}
+
+/**
+An [`emit::Ctxt`] that stores ambient context in JavaScript's async context.
+
+`emit`'s default context is thread-local, so the current span is lost when control passes through a JavaScript promise or callback and back into Rust.
+This context keeps it in the first of these that's available:
+
+1. The [`AsyncContext`](https://github.com/tc39/proposal-async-context) proposal.
+2. Node's [`AsyncLocalStorage`](https://nodejs.org/api/async_context.html#class-asynclocalstorage).
+3. [Zone.js](https://github.com/angular/angular/tree/main/packages/zone.js).
+
+With `AsyncLocalStorage`, context flows into any callbacks scheduled while it's active, like `setTimeout` or `.then`.
+With `AsyncContext` and Zone.js, callbacks need to be bound with [`AsyncCtxt::bind`] to carry it.
+When none of them are available, this falls back to thread-local storage, and bound callbacks enter their context when they're called.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[wasm_bindgen]
+pub fn setup() {
+    let _ = emit::setup()
+        .emit_to(emit_web::console())
+        .with_ctxt(emit_web::async_ctxt())
+        .try_init();
+}
+```
+
+All instances of this context share the same storage.
+Frames are kept until JavaScript no longer refers to them, which is detected with a [`FinalizationRegistry`](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/FinalizationRegistry).
+Where that isn't available, frames are released as soon as they're exited, and only the most recent 1024 frames for bound functions are kept.
+
+This requires the `std` feature.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncCtxt {}
+
+/**
+A [`emit::Ctxt::Frame`] on an [`AsyncCtxt`].
+*/
+pub struct AsyncCtxtFrame {
+    inner: ThreadLocalCtxtFrame,
+}
+
+// The most frames to keep for bound functions when they can't be released after garbage collection
+const MAX_UNFINALIZED_FRAMES: usize = 1024;
+
+std::thread_local! {
+    // Frames referenced by handles in JavaScript
+    static FRAMES: RefCell<BTreeMap<u64, ThreadLocalCtxtFrame>> = const { RefCell::new(BTreeMap::new()) };
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+
+    // The ids of frames entered into the store in JavaScript
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+
+    // Whether frames are released once their handles in JavaScript are garbage collected
+    static FINALIZES: Cell<bool> = const { Cell::new(false) };
+
+    // The number of frames entered in the current synchronous execution
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+
+    // Frames entered by bound callbacks when falling back to thread-local storage
+    static BOUND: RefCell<Vec<AsyncCtxtFrame>> = const { RefCell::new(Vec::new()) };
+
+    // Whether entering a frame needs to change the store in JavaScript
+    static ENTER_WITH: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+impl AsyncCtxt {
+    /**
+    Create a new instance of the async context.
+    */
+    pub const fn new() -> Self {
+        AsyncCtxt {}
+    }
+
+    /**
+    Bind a JavaScript function to the current context.
+
+    When the returned function is called, the current context is the one that was active when it was bound.
+    This is needed to carry context into callbacks when using `AsyncContext` or Zone.js, and is harmless with `AsyncLocalStorage`.
+    */
+    pub fn bind(&self, f: &Function) -> Function {
+        let id = self.with_current(|current| store(current.clone()));
+
+        shim::emit_web_ctxt_bind(id, f)
+    }
+}
+
+/**
+The thread-local storage for frames entered in the current synchronous execution.
+
+Frames are always exited before control returns to JavaScript, so this is empty whenever Rust is called.
+*/
+fn thread_local() -> ThreadLocalCtxt {
+    static CTXT: OnceLock<ThreadLocalCtxt> = OnceLock::new();
+
+    *CTXT.get_or_init(ThreadLocalCtxt::new)
+}
+
+fn init() -> bool {
+    let kind = shim::emit_web_ctxt_init(
+        &Closure::<dyn FnMut(u64)>::new(|id: u64| release(id)).into_js_value(),
+        &Closure::<dyn FnMut(u64)>::new(|id: u64| {
+            let mut frame = AsyncCtxtFrame {
+                inner: load(id).unwrap_or_else(|| thread_local().open_root(emit::Empty)),
+            };
+
+            AsyncCtxt::new().enter(&mut frame);
+            BOUND.with(|bound| bound.borrow_mut().push(frame));
+        })
+        .into_js_value(),
+        &Closure::<dyn FnMut()>::new(|| {
+            if let Some(mut frame) = BOUND.with(|bound| bound.borrow_mut().pop()) {
+                AsyncCtxt::new().exit(&mut frame);
+            }
+        })
+        .into_js_value(),
+    );
+
+    let enter_with = kind == "async-local-storage";
+    ENTER_WITH.with(|cell| cell.set(Some(enter_with)));
+    FINALIZES.with(|cell| cell.set(shim::emit_web_ctxt_finalizes()));
+
+    enter_with
+}
+
+fn enter_with() -> bool {
+    ENTER_WITH.with(|cell| cell.get()).unwrap_or_else(init)
+}
+
+fn finalizes() -> bool {
+    FINALIZES.with(|cell| cell.get())
+}
+
+fn store(frame: ThreadLocalCtxtFrame) -> u64 {
+    // Make sure the store in JavaScript has been created
+    enter_with();
+
+    // Ids are never reused, so a released id can't refer to a different frame
+    let id = NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+
+        id
+    });
+
+    FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+
+        // Ids are ordered, so the first frame is the oldest
+        while !finalizes() && frames.len() >= MAX_UNFINALIZED_FRAMES {
+            frames.pop_first();
+        }
+
+        frames.insert(id, frame);
+    });
+
+    id
+}
+
+fn load(id: u64) -> Option<ThreadLocalCtxtFrame> {
+    FRAMES.with(|frames| frames.borrow().get(&id).cloned())
+}
+
+fn release(id: u64) {
+    FRAMES.with(|frames| frames.borrow_mut().remove(&id));
+}
+
+impl emit::Ctxt for AsyncCtxt {
+    type Current = ThreadLocalCtxtFrame;
+    type Frame = AsyncCtxtFrame;
+
+    fn open_root<P: emit::Props>(&self, props: P) -> Self::Frame {
+        AsyncCtxtFrame {
+            inner: thread_local().open_root(props),
+        }
+    }
+
+    fn open_push<P: emit::Props>(&self, props: P) -> Self::Frame {
+        self.with_current(|current| self.open_root(props.and_props(current)))
+    }
+
+    fn enter(&self, frame: &mut Self::Frame) {
+        if enter_with() {
+            let id = store(frame.inner.clone());
+
+            ENTERED.with(|entered| entered.borrow_mut().push(id));
+            shim::emit_web_ctxt_enter(id);
+        }
+
+        thread_local().enter(&mut frame.inner);
+        DEPTH.with(|depth| depth.set(depth.get() + 1));
+    }
+
+    fn with_current<R, F: FnOnce(&Self::Current) -> R>(&self, with: F) -> R {
+        // Frames entered in Rust take precedence over the store in JavaScript
+        if DEPTH.with(|depth| depth.get()) == 0 {
+            enter_with();
+
+            if let Some(current) = shim::emit_web_ctxt_get().and_then(load) {
+                return with(&current);
+            }
+        }
+
+        thread_local().with_current(with)
+    }
+
+    fn exit(&self, frame: &mut Self::Frame) {
+        thread_local().exit(&mut frame.inner);
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+
+        if enter_with() {
+            shim::emit_web_ctxt_exit();
+
+            let id = ENTERED.with(|entered| entered.borrow_mut().pop());
+
+            // Without a way to tell when JavaScript is done with the frame, it's released now
+            if let (Some(id), false) = (id, finalizes()) {
+                release(id);
+            }
+        }
+    }
+
+    fn close(&self, frame: Self::Frame) {
+        thread_local().close(frame.inner);
+    }
+}
+
+mod shim {
+    use alloc::string::String;
+    use js_sys::Function;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/ctxt.js")]
+    extern "C" {
+        pub fn emit_web_ctxt_init(release: &JsValue, enter: &JsValue, exit: &JsValue) -> String;
+        pub fn emit_web_ctxt_finalizes() -> bool;
+        pub fn emit_web_ctxt_get() -> Option<u64>;
+        pub fn emit_web_ctxt_enter(id: u64);
+        pub fn emit_web_ctxt_exit();
+        pub fn emit_web_ctxt_bind(id: u64, f: &Function) -> Function;
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use alloc::rc::Rc;
+    use emit::Props as _;
+    use wasm_bindgen_futures::JsFuture;
+
+    use crate::test_util::{Globals, Root};
+
+    #[wasm_bindgen(inline_js = "
+    export function stub_async_context() {
+        // Enough of the proposal to run synchronous functions
+        globalThis.AsyncContext = {
+            Variable: class {
+                constructor() {
+                    this.value = undefined;
+                }
+
+                get() {
+                    return this.value;
+                }
+
+                run(value, f) {
+                    const previous = this.value;
+                    this.value = value;
+
+                    try {
+                        return f();
+                    } finally {
+                        this.value = previous;
+                    }
+                }
+            },
+        };
+    }
+
+    export function stub_zone() {
+        const global = {};
+
+        class Zone {
+            constructor(parent, properties) {
+                this.parent = parent;
+                this.properties = properties;
+            }
+
+            get(key) {
+                return key in this.properties ? this.properties[key] : this.parent?.get(key);
+            }
+
+            fork(spec) {
+                return new Zone(this, spec.properties ?? {});
+            }
+
+            run(f) {
+                const previous = global.current;
+                global.current = this;
+
+                try {
+                    return f();
+                } finally {
+                    global.current = previous;
+                }
+            }
+        }
+
+        global.current = new Zone(null, {});
+        globalThis.Zone = global;
+    }
+
+    export function hide_backends() {
+        globalThis.process = undefined;
+    }
+
+    export function later(f) {
+        return new Promise((resolve) => setTimeout(() => resolve(f()), 0));
+    }
+    ")]
+    extern "C" {
+        fn stub_async_context();
+        fn stub_zone();
+        fn hide_backends();
+        fn later(f: &Function) -> js_sys::Promise;
+    }
+
+    fn current_trace_id() -> Option<emit::TraceId> {
+        async_ctxt().with_current(|current| current.pull(emit::well_known::KEY_TRACE_ID))
+    }
+
+    fn trace_id() -> emit::TraceId {
+        emit::TraceId::from_u128(1).unwrap()
+    }
+
+    /**
+    The backend detected by [`detect`].
+
+    When dropped, the backend for the real environment is detected again.
+    */
+    struct Detected {
+        enter_with: bool,
+    }
+
+    impl Drop for Detected {
+        fn drop(&mut self) {
+            init();
+        }
+    }
+
+    /**
+    Detect the backend with only the globals set by `stub`.
+    */
+    fn detect(stub: impl FnOnce()) -> Detected {
+        let globals = Globals::save(&["process", "AsyncContext", "Zone"]);
+
+        hide_backends();
+        stub();
+
+        let enter_with = init();
+
+        drop(globals);
+
+        Detected { enter_with }
+    }
+
+    /**
+    Behave as though `FinalizationRegistry` isn't available until dropped.
+    */
+    struct Unfinalized;
+
+    impl Unfinalized {
+        fn new() -> Self {
+            enter_with();
+            FINALIZES.with(|cell| cell.set(false));
+
+            Unfinalized
+        }
+    }
+
+    impl Drop for Unfinalized {
+        fn drop(&mut self) {
+            FINALIZES.with(|cell| cell.set(shim::emit_web_ctxt_finalizes()));
+        }
+    }
+
+    fn frames() -> usize {
+        FRAMES.with(|frames| frames.borrow().len())
+    }
+
+    fn observe_trace_id() -> (Rc<Cell<Option<emit::TraceId>>>, Function) {
+        let observed = Rc::new(Cell::new(None));
+
+        let f = Closure::<dyn FnMut()>::new({
+            let observed = observed.clone();
+
+            move || observed.set(current_trace_id())
+        })
+        .into_js_value()
+        .unchecked_into();
+
+        (observed, f)
+    }
+
+    #[wasm_bindgen_test]
+    async fn flow_through_async_local_storage() {
+        // Node has `AsyncLocalStorage`
+        assert!(enter_with());
+
+        let (observed, f) = observe_trace_id();
+
+        let scheduled = emit::Frame::push(async_ctxt(), ("trace_id", trace_id())).call(|| {
+            assert_eq!(Some(trace_id()), current_trace_id());
+
+            later(&f)
+        });
+
+        assert_eq!(None, current_trace_id());
+
+        JsFuture::from(scheduled).await.unwrap();
+
+        assert_eq!(Some(trace_id()), observed.get());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn bind_with_async_context() {
+        let detected = detect(stub_async_context);
+        assert!(!detected.enter_with);
+
+        let (observed, f) = observe_trace_id();
+
+        let bound = emit::Frame::push(async_ctxt(), ("trace_id", trace_id()))
+            .call(|| async_ctxt().bind(&f));
+
+        f.call0(&JsValue::UNDEFINED).unwrap();
+        assert_eq!(None, observed.get());
+
+        bound.call0(&JsValue::UNDEFINED).unwrap();
+        assert_eq!(Some(trace_id()), observed.get());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn bind_after_page_load() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+
+        let detected = detect(stub_async_context);
+        assert!(!detected.enter_with);
+
+        let _ = SLOT.init(
+            emit::runtime::Runtime::new()
+                .with_ctxt(async_ctxt())
+                .with_rng(crate::crypto_rng()),
+        );
+        let rt = SLOT.get();
+
+        let _root = Root::save();
+        let page_load = crate::page_load(rt).start();
+
+        // The page-load span doesn't leave a frame entered
+        assert_eq!(None, current_trace_id());
+
+        let (observed, f) = observe_trace_id();
+
+        let bound = emit::Frame::push(async_ctxt(), ("trace_id", trace_id()))
+            .call(|| async_ctxt().bind(&f));
+
+        bound.call0(&JsValue::UNDEFINED).unwrap();
+        assert_eq!(Some(trace_id()), observed.get());
+
+        page_load.complete();
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn bind_with_zone() {
+        let detected = detect(stub_zone);
+        assert!(!detected.enter_with);
+
+        let (observed, f) = observe_trace_id();
+
+        let bound = emit::Frame::push(async_ctxt(), ("trace_id", trace_id()))
+            .call(|| async_ctxt().bind(&f));
+
+        bound.call0(&JsValue::UNDEFINED).unwrap();
+        assert_eq!(Some(trace_id()), observed.get());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn fall_back_to_thread_local() {
+        let detected = detect(|| ());
+        assert!(!detected.enter_with);
+
+        let (observed, f) = observe_trace_id();
+
+        let bound = emit::Frame::push(async_ctxt(), ("trace_id", trace_id())).call(|| {
+            // Frames can be pushed onto the current one
+            emit::Frame::push(
+                async_ctxt(),
+                ("span_id", emit::SpanId::from_u64(1).unwrap()),
+            )
+            .call(|| {
+                async_ctxt().with_current(|current| {
+                    assert_eq!(Some(trace_id()), current.pull("trace_id"));
+                    assert!(current.get("span_id").is_some());
+                })
+            });
+
+            async_ctxt().bind(&f)
+        });
+
+        assert_eq!(None, current_trace_id());
+
+        bound.call0(&JsValue::UNDEFINED).unwrap();
+        assert_eq!(Some(trace_id()), observed.get());
+
+        // The bound frame is exited after the call
+        assert_eq!(None, current_trace_id());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn release_on_exit_without_finalization() {
+        assert!(enter_with());
+
+        let _unfinalized = Unfinalized::new();
+        let before = frames();
+
+        emit::Frame::push(async_ctxt(), ("trace_id", trace_id())).call(|| {
+            assert_eq!(before + 1, frames());
+            assert_eq!(Some(trace_id()), current_trace_id());
+        });
+
+        assert_eq!(before, frames());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn limit_bound_frames_without_finalization() {
+        let _unfinalized = Unfinalized::new();
+
+        let (observed, f) = observe_trace_id();
+
+        let bound = emit::Frame::push(async_ctxt(), ("trace_id", trace_id()))
+            .call(|| async_ctxt().bind(&f));
+
+        for _ in 0..MAX_UNFINALIZED_FRAMES {
+            let _ = async_ctxt().bind(&f);
+        }
+
+        assert!(frames() <= MAX_UNFINALIZED_FRAMES);
+
+        // The oldest frame has been released
+        bound.call0(&JsValue::UNDEFINED).unwrap();
+        assert_eq!(None, observed.get());
+    }
+}
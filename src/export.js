@@ -0,0 +1,88 @@
+// Builds exports of events for `emit_web`.
+//
+// Events are records in the same schema sent by the `FetchEmitter`
+// and kept by the `StorageRingEmitter`.
+
+// Convert an RFC3339 timestamp into microseconds since the Unix epoch
+// `Date` only has millisecond precision, so the fractional seconds are read separately
+function micros(ts) {
+    const parts = /^(.*T\d\d:\d\d:\d\d)(?:\.(\d+))?Z$/.exec(ts);
+
+    if (parts === null) {
+        return Date.parse(ts) * 1000;
+    }
+
+    const fraction = (parts[2] ?? "").padEnd(6, "0").slice(0, 6);
+
+    return Date.parse(`${parts[1]}Z`) * 1000 + Number(fraction);
+}
+
+function ndjson(events) {
+    return events.map((evt) => `${JSON.stringify(evt)}\n`);
+}
+
+// See: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+function chromeTrace(events) {
+    // Spans on the same thread must be nested, so each trace gets its own thread
+    const threads = new Map();
+    const tid = (traceId) => {
+        if (traceId == null) {
+            return 0;
+        }
+
+        if (!threads.has(traceId)) {
+            threads.set(traceId, threads.size + 1);
+        }
+
+        return threads.get(traceId);
+    };
+
+    const traceEvents = [];
+
+    for (const evt of events) {
+        const props = evt.props ?? {};
+        const common = { cat: evt.mdl, pid: 1, tid: tid(props.trace_id), args: props };
+
+        if (props.evt_kind === "span" && evt.ts_start != null && evt.ts != null) {
+            const ts = micros(evt.ts_start);
+
+            traceEvents.push({ name: props.span_name ?? evt.msg, ph: "X", ts, dur: micros(evt.ts) - ts, ...common });
+        } else if (evt.ts != null) {
+            traceEvents.push({ name: evt.msg, ph: "i", s: "t", ts: micros(evt.ts), ...common });
+        }
+    }
+
+    return [JSON.stringify({ traceEvents, displayTimeUnit: "ms" })];
+}
+
+export function emit_web_export(events, chrome) {
+    events = Array.from(events ?? []);
+
+    return chrome
+        ? new Blob(chromeTrace(events), { type: "application/json" })
+        : new Blob(ndjson(events), { type: "application/x-ndjson" });
+}
+
+export function emit_web_object_url(blob) {
+    return URL.createObjectURL(blob);
+}
+
+export function emit_web_download(blob, fileName) {
+    if (typeof document === "undefined") {
+        return;
+    }
+
+    const url = URL.createObjectURL(blob);
+
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = fileName;
+    link.style.display = "none";
+
+    document.body.appendChild(link);
+    link.click();
+    link.remove();
+
+    // Give the browser a chance to start the download before the URL is revoked
+    setTimeout(() => URL.revokeObjectURL(url), 0);
+}
@@ -0,0 +1,219 @@
+/*!
+Export events as a file that can be downloaded or attached to bug reports.
+*/
+
+use alloc::string::String;
+
+use wasm_bindgen::prelude::*;
+
+/**
+Export `events` in the given `format`.
+
+The `events` are an array of objects in the schema described by [`crate::FetchFormat`], like the ones returned by [`crate::dump_logs`].
+*/
+pub fn export(events: &JsValue, format: ExportFormat) -> Export {
+    Export::new(events, format)
+}
+
+/**
+The format of an [`Export`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /**
+    Newline-delimited JSON with one event per line, in the schema described by [`crate::FetchFormat`].
+    */
+    Ndjson,
+    /**
+    The [Chrome Trace Event Format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU), which can be opened in [Perfetto](https://ui.perfetto.dev) or `chrome://tracing`.
+
+    Spans are written as complete events, with a `ph` of `X`, using their extent for their start and duration.
+    Other events are written as instant events, with a `ph` of `i`.
+    Each trace is given its own thread, so spans from different traces don't need to be nested.
+    Events without a timestamp are skipped.
+    */
+    ChromeTrace,
+}
+
+impl ExportFormat {
+    /**
+    The conventional file extension for the format, without a leading `.`.
+    */
+    pub const fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::ChromeTrace => "json",
+        }
+    }
+}
+
+/**
+A set of events exported into a [`Blob`](https://developer.mozilla.org/en-US/docs/Web/API/Blob).
+
+Use [`export`] to create a new export.
+*/
+pub struct Export {
+    blob: JsValue,
+    format: ExportFormat,
+}
+
+impl Export {
+    /**
+    Export `events` in the given `format`.
+
+    See [`export`] for details.
+    */
+    pub fn new(events: &JsValue, format: ExportFormat) -> Self {
+        Export {
+            blob: shim::emit_web_export(events, matches!(format, ExportFormat::ChromeTrace)),
+            format,
+        }
+    }
+
+    /**
+    Get the exported events as a `Blob`.
+    */
+    pub fn blob(&self) -> &JsValue {
+        &self.blob
+    }
+
+    /**
+    Get the format the events were exported in.
+    */
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    /**
+    Create an [object URL](https://developer.mozilla.org/en-US/docs/Web/API/URL/createObjectURL_static) for the exported events.
+
+    The URL should be revoked with `URL.revokeObjectURL` once it's no longer needed.
+    */
+    pub fn object_url(&self) -> String {
+        shim::emit_web_object_url(&self.blob)
+    }
+
+    /**
+    Prompt the browser to download the exported events as a file with the given name.
+
+    This does nothing outside of a page.
+    */
+    pub fn download(&self, file_name: &str) {
+        shim::emit_web_download(&self.blob, file_name)
+    }
+}
+
+mod shim {
+    use alloc::string::String;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/export.js")]
+    extern "C" {
+        pub fn emit_web_export(events: &JsValue, chrome: bool) -> JsValue;
+        pub fn emit_web_object_url(blob: &JsValue) -> String;
+        pub fn emit_web_download(blob: &JsValue, file_name: &str);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use core::time::Duration;
+
+    use js_sys::{Array, JSON};
+    use wasm_bindgen_futures::JsFuture;
+
+    use crate::{ser::Buf, test_util::get};
+
+    fn ts(micros: u64) -> emit::Timestamp {
+        emit::Timestamp::from_unix(Duration::from_micros(micros)).unwrap()
+    }
+
+    fn events() -> JsValue {
+        let events = Array::new();
+
+        let span = emit::evt!(
+            extent: ts(1_000_001)..ts(1_500_002),
+            "work",
+            evt_kind: "span",
+            span_name: "work",
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736",
+        );
+        let evt = emit::evt!(extent: ts(1_200_000), "event {n}", n: 1);
+
+        let mut buf = Buf::new(false);
+        buf.record(&span, None);
+        events.push(&JSON::parse(&buf.json()).unwrap());
+
+        let mut buf = Buf::new(false);
+        buf.record(&evt, None);
+        events.push(&JSON::parse(&buf.json()).unwrap());
+
+        events.into()
+    }
+
+    async fn text(export: &Export) -> String {
+        let text = get(export.blob(), "text")
+            .dyn_into::<js_sys::Function>()
+            .unwrap()
+            .call0(export.blob())
+            .unwrap();
+
+        JsFuture::from(js_sys::Promise::from(text))
+            .await
+            .unwrap()
+            .as_string()
+            .unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    async fn export_ndjson() {
+        let export = export(&events(), ExportFormat::Ndjson);
+
+        assert_eq!(
+            JsValue::from("application/x-ndjson"),
+            get(export.blob(), "type")
+        );
+
+        let text = text(&export).await;
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(2, lines.len());
+        assert_eq!(
+            JsValue::from("event 1"),
+            get(&JSON::parse(lines[1]).unwrap(), "msg")
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn export_chrome_trace() {
+        let export = export(&events(), ExportFormat::ChromeTrace);
+
+        let trace = JSON::parse(&text(&export).await).unwrap();
+        let trace_events = Array::from(&get(&trace, "traceEvents"));
+
+        assert_eq!(2, trace_events.length());
+
+        let span = trace_events.get(0);
+        assert_eq!(JsValue::from("work"), get(&span, "name"));
+        assert_eq!(JsValue::from("X"), get(&span, "ph"));
+        assert_eq!(JsValue::from(1_000_001), get(&span, "ts"));
+        assert_eq!(JsValue::from(500_001), get(&span, "dur"));
+        assert_eq!(JsValue::from(1), get(&span, "tid"));
+
+        let evt = trace_events.get(1);
+        assert_eq!(JsValue::from("event 1"), get(&evt, "name"));
+        assert_eq!(JsValue::from("i"), get(&evt, "ph"));
+        assert_eq!(JsValue::from(1_200_000), get(&evt, "ts"));
+        assert_eq!(JsValue::from(0), get(&evt, "tid"));
+    }
+
+    #[wasm_bindgen_test]
+    fn export_object_url() {
+        let export = export(&events(), ExportFormat::Ndjson);
+
+        assert!(export.object_url().starts_with("blob:"));
+    }
+}
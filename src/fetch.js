@@ -0,0 +1,20 @@
+// Sends batches of events for the `FetchEmitter` in `emit_web`.
+//
+// The outcome of a request is reported back to Rust as a status code.
+// Requests that fail to send at all, like when the network is unavailable,
+// report a status of `0`.
+//
+// If `fetch` has been wrapped by the `FetchTracer` then the original is used,
+// so sending events doesn't produce more events.
+
+export function emit_web_fetch(url, headers, body, keepalive, done) {
+    const send = globalThis[Symbol.for("emit_web.fetch")] ?? fetch;
+
+    // Start from a resolved promise so errors thrown by `fetch` itself are handled too
+    Promise.resolve()
+        .then(() => send(url, { method: "POST", headers, body, keepalive }))
+        .then(
+            (res) => done(res.status),
+            () => done(0),
+        );
+}
@@ -0,0 +1,703 @@
+/*!
+Send batches of events to an HTTP endpoint using the Fetch API.
+*/
+
+use alloc::{
+    boxed::Box,
+    collections::VecDeque,
+    rc::{Rc, Weak},
+    string::String,
+    vec::Vec,
+};
+use core::{
+    cell::{OnceCell, RefCell},
+    cmp,
+    time::Duration,
+};
+
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+
+use crate::{beacon, duration_millis_f64, ser, timers, Beacon, Local, OfflineTransport};
+
+// Browsers limit the total size of requests made with `keepalive`
+const KEEPALIVE_LIMIT: usize = 64 * 1024;
+
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/**
+An emitter that sends batches of events to `url` using the [Fetch API](https://developer.mozilla.org/en-US/docs/Web/API/Fetch_API).
+
+See [`FetchEmitterBuilder`] for configuration.
+*/
+pub fn fetch(url: impl Into<String>) -> FetchEmitterBuilder {
+    FetchEmitterBuilder::new(url)
+}
+
+/**
+The format of requests sent by a [`FetchEmitter`].
+
+Each event is written as a JSON object with the following fields:
+
+- `ts_start`: The start of the event's extent as an RFC3339 timestamp, if it's a span.
+- `ts`: The event's timestamp as an RFC3339 timestamp, if it has one.
+- `mdl`: The module the event was emitted from.
+- `tpl`: The event's template.
+- `msg`: The event's rendered message.
+- `props`: The event's properties as an object.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchFormat {
+    /**
+    A JSON array of events, sent as `application/json`.
+    */
+    Json,
+    /**
+    Newline-delimited JSON with one event per line, sent as `application/x-ndjson`.
+    */
+    Ndjson,
+}
+
+impl FetchFormat {
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            FetchFormat::Json => "application/json",
+            FetchFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub(crate) fn body(self, records: impl Iterator<Item = impl AsRef<str>>) -> String {
+        let mut body = String::new();
+
+        match self {
+            FetchFormat::Json => {
+                body.push('[');
+
+                for (i, record) in records.enumerate() {
+                    if i > 0 {
+                        body.push(',');
+                    }
+
+                    body.push_str(record.as_ref());
+                }
+
+                body.push(']');
+            }
+            FetchFormat::Ndjson => {
+                for record in records {
+                    body.push_str(record.as_ref());
+                    body.push('\n');
+                }
+            }
+        }
+
+        body
+    }
+
+    /**
+    The length in bytes of a body containing `records` events with a combined length of `len`.
+    */
+    pub(crate) fn body_len(self, records: usize, len: usize) -> usize {
+        match self {
+            FetchFormat::Json => len + records.saturating_sub(1) + 2,
+            FetchFormat::Ndjson => len + records,
+        }
+    }
+}
+
+/**
+A builder for a [`FetchEmitter`].
+*/
+pub struct FetchEmitterBuilder {
+    url: String,
+    headers: Vec<(String, String)>,
+    format: FetchFormat,
+    batch_size: usize,
+    max_queue_len: usize,
+    flush_interval: Duration,
+    max_retries: u32,
+    retry_backoff: Duration,
+    beacon: Option<Beacon>,
+}
+
+impl FetchEmitterBuilder {
+    /**
+    Create a new builder for an emitter that sends events to `url`.
+    */
+    pub fn new(url: impl Into<String>) -> Self {
+        FetchEmitterBuilder {
+            url: url.into(),
+            headers: Vec::new(),
+            format: FetchFormat::Json,
+            batch_size: 100,
+            max_queue_len: 1000,
+            flush_interval: Duration::from_secs(5),
+            max_retries: 5,
+            retry_backoff: Duration::from_millis(500),
+            beacon: None,
+        }
+    }
+
+    /**
+    Add a header to send with each request.
+
+    If no `Content-Type` header is given then one is set based on the [`FetchFormat`].
+    */
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /**
+    Set the format of requests.
+
+    The default format is [`FetchFormat::Json`].
+    */
+    pub fn format(mut self, format: FetchFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /**
+    Set the maximum number of events to send in a single request.
+
+    A request is sent as soon as this many events are queued, without waiting for the flush interval.
+    The default batch size is 100 events.
+    */
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = cmp::max(1, batch_size);
+        self
+    }
+
+    /**
+    Set the maximum number of events to keep while waiting to send them.
+
+    When the queue is full, the oldest events are dropped to make room for new ones.
+    The default maximum is 1000 events.
+    */
+    pub fn max_queue_len(mut self, max_queue_len: usize) -> Self {
+        self.max_queue_len = cmp::max(1, max_queue_len);
+        self
+    }
+
+    /**
+    Set the time to wait before sending events that don't fill a batch.
+
+    The default interval is 5 seconds.
+    */
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /**
+    Set the number of times to retry a request that fails.
+
+    Requests are retried if they can't be sent, or if the endpoint responds with a `408`, `429`, or `5xx` status.
+    Any other unsuccessful status drops the batch without retrying.
+    The default is 5 retries.
+    */
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /**
+    Set the time to wait before the first retry.
+
+    The time to wait doubles after each retry, up to a minute.
+    The default backoff is 500 milliseconds.
+    */
+    pub fn retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /**
+    Send any queued events with a [`Beacon`] when the page is closed or hidden.
+
+    Without a beacon, events that are still queued when the page is closed are lost.
+    */
+    pub fn beacon_on_unload(mut self, beacon: Beacon) -> Self {
+        self.beacon = Some(beacon);
+        self
+    }
+
+    /**
+    Build a [`FetchEmitter`] from this configuration.
+    */
+    pub fn build(self) -> FetchEmitter {
+        let headers = Array::new();
+
+        let mut has_content_type = false;
+        for (name, value) in &self.headers {
+            has_content_type |= name.eq_ignore_ascii_case("content-type");
+
+            headers.push(&Array::of2(&(&**name).into(), &(&**value).into()));
+        }
+
+        if !has_content_type {
+            headers.push(&Array::of2(
+                &"Content-Type".into(),
+                &self.format.content_type().into(),
+            ));
+        }
+
+        let inner = Rc::new(Inner {
+            url: self.url,
+            headers: headers.into(),
+            format: self.format,
+            batch_size: self.batch_size,
+            max_queue_len: self.max_queue_len,
+            flush_interval: self.flush_interval,
+            max_retries: self.max_retries,
+            retry_backoff: self.retry_backoff,
+            on_settled: OnceCell::new(),
+            state: RefCell::new(State {
+                queue: VecDeque::new(),
+                scheduled: false,
+                sending: false,
+            }),
+        });
+
+        if let Some(beacon) = self.beacon {
+            let inner = Rc::downgrade(&inner);
+            beacon::on_unload(move || {
+                if let Some(inner) = Weak::upgrade(&inner) {
+                    inner.unload(&beacon);
+                }
+            });
+        }
+
+        FetchEmitter {
+            inner: Local(inner),
+        }
+    }
+}
+
+/**
+An emitter that sends batches of events to an HTTP endpoint using the [Fetch API](https://developer.mozilla.org/en-US/docs/Web/API/Fetch_API).
+
+Events are queued in memory and sent in the background once there's enough to fill a batch, or the flush interval passes.
+Only one request is sent at a time.
+Requests are made with [`keepalive`](https://developer.mozilla.org/en-US/docs/Web/API/RequestInit#keepalive) when they're small enough, so they can complete even if the page is closed.
+
+Use [`fetch`] to configure a new emitter.
+*/
+pub struct FetchEmitter {
+    inner: Local<Rc<Inner>>,
+}
+
+struct Inner {
+    url: String,
+    headers: JsValue,
+    format: FetchFormat,
+    batch_size: usize,
+    max_queue_len: usize,
+    flush_interval: Duration,
+    max_retries: u32,
+    retry_backoff: Duration,
+    on_settled: OnceCell<OnSettled>,
+    state: RefCell<State>,
+}
+
+type OnSettled = Box<dyn Fn(Vec<Rc<str>>, bool)>;
+
+struct State {
+    queue: VecDeque<Record>,
+    // Whether a timer will send the queue
+    scheduled: bool,
+    // Whether a request is in progress
+    sending: bool,
+}
+
+/**
+An event waiting to be sent.
+*/
+pub(crate) struct Record {
+    /**
+    An id for records that are tracked outside of the emitter.
+    */
+    pub id: Option<Rc<str>>,
+    pub json: String,
+}
+
+impl AsRef<str> for Record {
+    fn as_ref(&self) -> &str {
+        &self.json
+    }
+}
+
+struct Batch {
+    body: String,
+    ids: Vec<Rc<str>>,
+}
+
+/**
+Records are kept to try again later when a request fails after exhausting its retries, or when they're dropped from a full queue.
+Records that are sent with a beacon are assumed to be kept, and aren't settled.
+*/
+impl OfflineTransport for FetchEmitter {
+    fn push(&self, id: Rc<str>, json: String) {
+        self.inner.push(Record { id: Some(id), json });
+    }
+
+    fn on_settled(&self, settled: Box<dyn Fn(Vec<Rc<str>>, bool)>) {
+        let _ = self.inner.on_settled.set(settled);
+    }
+}
+
+impl emit::Emitter for FetchEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let mut buf = ser::Buf::new(false);
+        buf.record(&evt, None);
+
+        self.inner.push(Record {
+            id: None,
+            json: buf.json(),
+        });
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        // Requests can't be waited on without blocking the only thread
+        // that can complete them, so this just sends what's queued
+        self.inner.send();
+
+        self.inner
+            .state
+            .try_borrow()
+            .map(|state| !state.sending && state.queue.is_empty())
+            .unwrap_or(false)
+    }
+}
+
+impl Inner {
+    fn push(self: &Rc<Self>, record: Record) {
+        let Ok(mut state) = self.state.try_borrow_mut() else {
+            return;
+        };
+
+        state.queue.push_back(record);
+
+        let mut dropped = Vec::new();
+        while state.queue.len() > self.max_queue_len {
+            dropped.extend(state.queue.pop_front().and_then(|record| record.id));
+        }
+
+        drop(state);
+
+        if !dropped.is_empty() {
+            self.settled(dropped, true);
+        }
+
+        self.schedule();
+    }
+
+    fn settled(&self, ids: Vec<Rc<str>>, keep: bool) {
+        if let Some(on_settled) = self.on_settled.get() {
+            on_settled(ids, keep);
+        }
+    }
+
+    /**
+    Send all queued events with a beacon.
+
+    A batch that's already being sent isn't included, since its request was made with `keepalive`.
+    */
+    fn unload(&self, beacon: &Beacon) {
+        let Ok(mut state) = self.state.try_borrow_mut() else {
+            return;
+        };
+
+        beacon.send(&mut state.queue);
+    }
+
+    /**
+    Send a batch if there's enough events queued, or set a timer to send them later.
+    */
+    fn schedule(self: &Rc<Self>) {
+        let Ok(mut state) = self.state.try_borrow_mut() else {
+            return;
+        };
+
+        if state.sending || state.queue.is_empty() {
+            return;
+        }
+
+        if state.queue.len() >= self.batch_size {
+            drop(state);
+            return self.send();
+        }
+
+        if !state.scheduled {
+            state.scheduled = true;
+
+            let inner = self.clone();
+            timers::set_timeout(
+                &Closure::once_into_js(move || {
+                    inner.state.borrow_mut().scheduled = false;
+                    inner.send();
+                }),
+                duration_millis_f64(self.flush_interval),
+            );
+        }
+    }
+
+    /**
+    Send a batch of queued events.
+    */
+    fn send(self: &Rc<Self>) {
+        let batch = {
+            let Ok(mut state) = self.state.try_borrow_mut() else {
+                return;
+            };
+
+            if state.sending || state.queue.is_empty() {
+                return;
+            }
+
+            state.sending = true;
+
+            let len = cmp::min(self.batch_size, state.queue.len());
+            let records = state.queue.drain(..len).collect::<Vec<_>>();
+
+            Batch {
+                body: self.format.body(records.iter()),
+                ids: records.into_iter().filter_map(|record| record.id).collect(),
+            }
+        };
+
+        self.attempt(Rc::new(batch), 0);
+    }
+
+    fn attempt(self: &Rc<Self>, batch: Rc<Batch>, attempt: u32) {
+        let inner = self.clone();
+        let done = {
+            let batch = batch.clone();
+
+            Closure::once_into_js(move |status: u16| inner.sent(batch, attempt, status))
+        };
+
+        shim::emit_web_fetch(
+            &self.url,
+            &self.headers,
+            &batch.body,
+            batch.body.len() <= KEEPALIVE_LIMIT,
+            &done,
+        );
+    }
+
+    fn sent(self: &Rc<Self>, batch: Rc<Batch>, attempt: u32, status: u16) {
+        let retry = matches!(status, 0 | 408 | 429 | 500..);
+
+        if retry && attempt < self.max_retries {
+            let backoff = self
+                .retry_backoff
+                .checked_mul(1 << cmp::min(attempt, 31))
+                .map(|backoff| cmp::min(backoff, MAX_RETRY_BACKOFF))
+                .unwrap_or(MAX_RETRY_BACKOFF);
+
+            let inner = self.clone();
+            timers::set_timeout(
+                &Closure::once_into_js(move || inner.attempt(batch, attempt + 1)),
+                duration_millis_f64(backoff),
+            );
+
+            return;
+        }
+
+        self.state.borrow_mut().sending = false;
+
+        if !batch.ids.is_empty() {
+            self.settled(batch.ids.clone(), retry);
+        }
+
+        self.schedule();
+    }
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/fetch.js")]
+    extern "C" {
+        pub fn emit_web_fetch(
+            url: &str,
+            headers: &JsValue,
+            body: &str,
+            keepalive: bool,
+            done: &JsValue,
+        );
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use crate::test_util::{get, sleep, Globals};
+
+    use emit::Emitter as _;
+    use js_sys::JSON;
+
+    #[wasm_bindgen(inline_js = "
+    export function stub_fetch(statuses) {
+        statuses = Array.from(statuses);
+
+        const requests = [];
+        globalThis.fetch = async (url, init) => {
+            requests.push({ url, ...init });
+
+            const status = statuses.length > 0 ? statuses.shift() : 200;
+            if (status === 0) {
+                throw new TypeError('failed to fetch');
+            }
+
+            return { status };
+        };
+
+        return requests;
+    }
+    ")]
+    extern "C" {
+        #[wasm_bindgen(js_name = stub_fetch)]
+        fn stub_fetch_js(statuses: Vec<u16>) -> Array;
+    }
+
+    fn stub_fetch(statuses: Vec<u16>) -> (Array, Globals) {
+        let globals = Globals::save(&["fetch"]);
+
+        (stub_fetch_js(statuses), globals)
+    }
+
+    #[wasm_bindgen_test]
+    async fn send_json_batch() {
+        let (requests, _globals) = stub_fetch(vec![]);
+
+        let emitter = fetch("http://localhost/ingest")
+            .header("Authorization", "Bearer token")
+            .batch_size(2)
+            .flush_interval(Duration::from_secs(60))
+            .build();
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+
+        sleep(0.0).await;
+
+        assert_eq!(1, requests.length());
+
+        let request = requests.get(0);
+
+        assert_eq!(
+            JsValue::from("http://localhost/ingest"),
+            get(&request, "url")
+        );
+        assert_eq!(JsValue::from("POST"), get(&request, "method"));
+        assert_eq!(JsValue::from(true), get(&request, "keepalive"));
+
+        let headers = Array::from(&get(&request, "headers"));
+        assert_eq!(
+            "Authorization,Bearer token,Content-Type,application/json",
+            String::from(headers.join(","))
+        );
+
+        let body = Array::from(&JSON::parse(&get(&request, "body").as_string().unwrap()).unwrap());
+        assert_eq!(2, body.length());
+        assert_eq!(JsValue::from("event 1"), get(&body.get(0), "msg"));
+        assert_eq!(JsValue::from("event {n}"), get(&body.get(0), "tpl"));
+        assert_eq!(JsValue::from(2), get(&get(&body.get(1), "props"), "n"));
+    }
+
+    #[wasm_bindgen_test]
+    async fn send_ndjson_on_interval() {
+        let (requests, _globals) = stub_fetch(vec![]);
+
+        let emitter = fetch("http://localhost/ingest")
+            .format(FetchFormat::Ndjson)
+            .flush_interval(Duration::from_millis(10))
+            .build();
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+
+        sleep(0.0).await;
+        assert_eq!(0, requests.length());
+
+        sleep(30.0).await;
+        assert_eq!(1, requests.length());
+
+        let body = get(&requests.get(0), "body").as_string().unwrap();
+        let lines = body.lines().collect::<Vec<_>>();
+
+        assert!(body.ends_with('\n'));
+        assert_eq!(1, lines.len());
+        assert_eq!(
+            JsValue::from("event 1"),
+            get(&JSON::parse(lines[0]).unwrap(), "msg")
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn retry_failed_requests() {
+        let (requests, _globals) = stub_fetch(vec![503, 0, 200]);
+
+        let emitter = fetch("http://localhost/ingest")
+            .batch_size(1)
+            .retry_backoff(Duration::from_millis(1))
+            .build();
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(30.0).await;
+
+        assert_eq!(3, requests.length());
+        assert_eq!(get(&requests.get(0), "body"), get(&requests.get(2), "body"));
+        assert!(emitter.blocking_flush(Duration::from_secs(1)));
+    }
+
+    #[wasm_bindgen_test]
+    async fn drop_client_errors() {
+        let (requests, _globals) = stub_fetch(vec![400]);
+
+        let emitter = fetch("http://localhost/ingest")
+            .batch_size(1)
+            .retry_backoff(Duration::from_millis(1))
+            .build();
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(30.0).await;
+
+        assert_eq!(1, requests.length());
+        assert!(emitter.blocking_flush(Duration::from_secs(1)));
+    }
+
+    #[wasm_bindgen_test]
+    async fn drop_oldest_when_full() {
+        let (requests, _globals) = stub_fetch(vec![]);
+
+        let emitter = fetch("http://localhost/ingest")
+            .max_queue_len(2)
+            .flush_interval(Duration::from_secs(60))
+            .build();
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+        emitter.emit(emit::evt!("event {n}", n: 3));
+
+        assert!(!emitter.blocking_flush(Duration::from_secs(1)));
+
+        sleep(0.0).await;
+
+        assert_eq!(1, requests.length());
+
+        let body =
+            Array::from(&JSON::parse(&get(&requests.get(0), "body").as_string().unwrap()).unwrap());
+        assert_eq!(2, body.length());
+        assert_eq!(JsValue::from("event 2"), get(&body.get(0), "msg"));
+        assert_eq!(JsValue::from("event 3"), get(&body.get(1), "msg"));
+    }
+}
@@ -0,0 +1,137 @@
+// Listens for user interactions for the `InteractionTracer` in `emit_web`.
+//
+// Listeners are added to the window in the capture phase, so they run before
+// any handlers on the page. Each interaction is reported to Rust with a
+// descriptor of its target, which returns an id for the interaction.
+//
+// The interaction is ended by a second listener on the window in the bubble
+// phase, once the event's handlers have run. Events that don't bubble, or
+// whose propagation is stopped, never reach it, so they're ended from a
+// microtask once their dispatch can't go any further instead. For events
+// dispatched by the browser, microtasks run between each listener, so that
+// microtask can't be queued as soon as the interaction begins. Ending an
+// interaction that's already ended does nothing.
+
+// Elements that users interact with, used instead of their descendants,
+// like an icon inside a button
+const INTERACTIVE = "a, button, input, select, textarea, summary, label, [role], [tabindex]";
+
+const MAX_TEXT_LEN = 64;
+
+function element(target) {
+    const el = target?.closest?.(INTERACTIVE) ?? target;
+
+    return typeof el?.tagName === "string" ? el : null;
+}
+
+function describe(el) {
+    let descriptor = el.tagName.toLowerCase();
+
+    if (el.id) {
+        descriptor += `#${el.id}`;
+    }
+
+    for (const attr of el.attributes ?? []) {
+        if (attr.name.startsWith("data-")) {
+            // Escape the value the same way as a quoted CSS string
+            const value = attr.value.replace(/["\\]/g, "\\$&");
+
+            descriptor += `[${attr.name}="${value}"]`;
+        }
+    }
+
+    return descriptor;
+}
+
+function text(el) {
+    const text = (el.innerText ?? el.textContent ?? "").trim().replace(/\s+/g, " ");
+
+    return text.length > MAX_TEXT_LEN ? `${text.slice(0, MAX_TEXT_LEN)}…` : text;
+}
+
+export function emit_web_interaction_install(events, recordText, begin, end) {
+    if (globalThis.document === undefined || typeof globalThis.addEventListener !== "function") {
+        return null;
+    }
+
+    const installed = { events, capture: null, bubble: null, pending: new WeakMap(), active: true };
+
+    const finish = (event) => {
+        const pending = installed.pending.get(event);
+
+        if (pending === undefined) {
+            return;
+        }
+
+        installed.pending.delete(event);
+        pending.cleanup?.();
+
+        // Interactions are ended in Rust when the tracer is dropped
+        if (installed.active) {
+            end(pending.id);
+        }
+    };
+
+    const finishSoon = (event) => queueMicrotask(() => finish(event));
+
+    installed.capture = (event) => {
+        const el = element(event.target);
+
+        const id = begin(
+            event.type,
+            el ? describe(el) : undefined,
+            el && recordText ? text(el) : undefined,
+        );
+
+        const pending = { id, cleanup: null };
+        installed.pending.set(event, pending);
+
+        // Listeners after the one that stops propagation don't run, or only
+        // run on the same element
+        for (const method of ["stopPropagation", "stopImmediatePropagation"]) {
+            const stop = event[method];
+
+            event[method] = function (...args) {
+                finishSoon(event);
+
+                return stop.apply(this, args);
+            };
+        }
+
+        // Events that don't bubble end at their target, after its own listeners
+        const target = event.target;
+
+        if (!event.bubbles && target !== globalThis && typeof target?.addEventListener === "function") {
+            const atTarget = (targetEvent) => {
+                if (targetEvent === event) {
+                    finishSoon(event);
+                }
+            };
+
+            target.addEventListener(event.type, atTarget);
+            pending.cleanup = () => target.removeEventListener(event.type, atTarget);
+        }
+    };
+
+    installed.bubble = finish;
+
+    for (const type of events) {
+        globalThis.addEventListener(type, installed.capture, { capture: true });
+        globalThis.addEventListener(type, installed.bubble);
+    }
+
+    return installed;
+}
+
+export function emit_web_interaction_uninstall(installed) {
+    if (!installed) {
+        return;
+    }
+
+    installed.active = false;
+
+    for (const type of installed.events) {
+        globalThis.removeEventListener(type, installed.capture, { capture: true });
+        globalThis.removeEventListener(type, installed.bubble);
+    }
+}
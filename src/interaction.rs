@@ -0,0 +1,599 @@
+/*!
+Spans for user interactions with the page.
+*/
+
+use alloc::{format, rc::Rc, string::String, vec, vec::Vec};
+use core::cell::{Cell, RefCell};
+
+use emit::{Clock as _, Ctxt as _, Filter as _, Props as _};
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+
+use crate::root;
+
+type Rt = emit::runtime::AmbientRuntime<'static>;
+type CtxtFrame = <&'static emit::runtime::AmbientCtxt<'static> as emit::Ctxt>::Frame;
+type Begin = dyn FnMut(String, Option<String>, Option<String>) -> u32;
+
+/**
+A [`TraceInteractionsBuilder`] for starting a span in `rt` for each user interaction with the page.
+*/
+pub fn trace_interactions(rt: &'static Rt) -> TraceInteractionsBuilder {
+    TraceInteractionsBuilder::new(rt)
+}
+
+/**
+A builder for an [`InteractionTracer`].
+*/
+pub struct TraceInteractionsBuilder {
+    rt: &'static Rt,
+    events: Vec<String>,
+    record_text: bool,
+}
+
+impl TraceInteractionsBuilder {
+    /**
+    Create a new builder for tracing interactions in `rt`.
+
+    Spans are also emitted through `rt`.
+    */
+    pub fn new(rt: &'static Rt) -> Self {
+        TraceInteractionsBuilder {
+            rt,
+            events: vec!["click".into(), "keydown".into(), "submit".into()],
+            record_text: false,
+        }
+    }
+
+    /**
+    Set the types of events to trace, like `click` or `change`.
+
+    The default events are `click`, `keydown`, and `submit`.
+    */
+    pub fn events(mut self, events: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.events = events.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /**
+    Whether to record the text of the element that was interacted with.
+
+    Text on the page can include personal data, so it's not recorded by default.
+    */
+    pub fn record_text(mut self, record_text: bool) -> Self {
+        self.record_text = record_text;
+        self
+    }
+
+    /**
+    Start tracing interactions.
+    */
+    pub fn install(self) -> InteractionTracer {
+        InteractionTracer::new(self)
+    }
+}
+
+/**
+Start a span for each user interaction with the page, like clicking a button or submitting a form.
+
+Events are observed by listeners on the window in the [capture phase](https://developer.mozilla.org/en-US/docs/Web/API/Event/eventPhase), so they run before any handlers on the page.
+The span for an interaction is current in the runtime's ambient context while the event's handlers run, so any spans they create are its children.
+That links the interaction to the work it triggered, like a slow Rust handler.
+The span ends when the event has finished propagating, observed by a listener on the window in the bubble phase.
+Work that handlers defer, like promises and timers, runs after the span has ended, so it isn't part of the interaction.
+Events that don't bubble end once the listeners on their target have run, and events whose propagation is stopped end as soon as the listener that stopped them returns.
+Handlers added to the window in the bubble phase after the tracer is installed also run after the span has ended.
+
+The span is a child of the span that was current when the event was dispatched, like the span for the current route from [`crate::trace_routes`].
+If there isn't one then it starts a new trace.
+
+Spans are emitted from the `emit_web::interaction` module, named after the event and its target, like `click button#save`, with the following props:
+
+- `event_type`: The type of the event, like `click`.
+- `target`: A descriptor of the element that was interacted with, made from its tag, id, and `data-*` attributes, like `button#save[data-action="save"]`.
+  Quotes and backslashes in attribute values are escaped with a backslash.
+  Events on an element inside a link, button, or other interactive element use that element as their target instead.
+- `target_text`: The text of the target, if it's enabled with [`TraceInteractionsBuilder::record_text`].
+
+Interactions are traced until the tracer is dropped.
+In environments without a DOM, like workers, this does nothing.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[wasm_bindgen]
+pub fn setup() {
+    let tracer = emit_web::trace_interactions(emit::runtime::shared()).install();
+
+    // Keep tracing interactions for the lifetime of the page
+    core::mem::forget(tracer);
+}
+```
+*/
+pub struct InteractionTracer {
+    installed: JsValue,
+    inner: Rc<Inner>,
+    _begin: Closure<Begin>,
+    _end: Closure<dyn FnMut(u32)>,
+}
+
+struct Inner {
+    rt: &'static Rt,
+    next_id: Cell<u32>,
+    // Interactions can be nested, like a `submit` dispatched while
+    // handling a `keydown`, so their frames are exited in reverse order
+    active: RefCell<Vec<Interaction>>,
+}
+
+struct Interaction {
+    id: u32,
+    frame: CtxtFrame,
+    span: InteractionSpan,
+}
+
+struct InteractionSpan {
+    ctxt: emit::SpanCtxt,
+    name: String,
+    event_type: String,
+    target: Option<String>,
+    target_text: Option<String>,
+    start: Option<emit::Timestamp>,
+}
+
+impl InteractionTracer {
+    fn new(builder: TraceInteractionsBuilder) -> Self {
+        let inner = Rc::new(Inner {
+            rt: builder.rt,
+            next_id: Cell::new(0),
+            active: RefCell::new(Vec::new()),
+        });
+
+        let begin = Closure::<Begin>::new({
+            let inner = inner.clone();
+
+            move |event_type: String, target: Option<String>, target_text: Option<String>| {
+                inner.begin(event_type, target, target_text)
+            }
+        });
+
+        let end = Closure::<dyn FnMut(u32)>::new({
+            let inner = inner.clone();
+
+            move |id: u32| inner.end(id)
+        });
+
+        let events = builder
+            .events
+            .iter()
+            .map(|event| JsValue::from(&**event))
+            .collect::<Array>();
+
+        let installed = shim::emit_web_interaction_install(
+            &events,
+            builder.record_text,
+            begin.as_ref(),
+            end.as_ref(),
+        );
+
+        InteractionTracer {
+            installed,
+            inner,
+            _begin: begin,
+            _end: end,
+        }
+    }
+}
+
+impl Drop for InteractionTracer {
+    fn drop(&mut self) {
+        shim::emit_web_interaction_uninstall(&self.installed);
+
+        let first = self
+            .inner
+            .active
+            .borrow()
+            .first()
+            .map(|interaction| interaction.id);
+        if let Some(first) = first {
+            self.inner.end(first);
+        }
+    }
+}
+
+impl Inner {
+    fn begin(
+        &self,
+        event_type: String,
+        target: Option<String>,
+        target_text: Option<String>,
+    ) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+
+        let ctxt = root::frame(self.rt.ctxt())
+            .call(|| emit::SpanCtxt::current(self.rt.ctxt()))
+            .new_child(self.rt.rng());
+
+        let name = match target {
+            Some(ref target) => format!("{event_type} {target}"),
+            None => event_type.clone(),
+        };
+
+        let span = InteractionSpan {
+            ctxt,
+            name,
+            event_type,
+            target,
+            target_text,
+            start: self.rt.clock().now(),
+        };
+
+        // Check the filter with the span before it has an extent, like `PageLoadSpan`
+        if !self.rt.filter().matches(span.to_span(emit::Empty)) {
+            return id;
+        }
+
+        let mut frame = self.rt.ctxt().open_push(ctxt);
+        self.rt.ctxt().enter(&mut frame);
+
+        self.active
+            .borrow_mut()
+            .push(Interaction { id, frame, span });
+
+        id
+    }
+
+    /**
+    End the interaction with `id`, along with any interactions that started while it was active.
+    */
+    fn end(&self, id: u32) {
+        // Take the interactions to end before emitting them, so emitters
+        // that dispatch events of their own don't find `active` borrowed
+        let ended = {
+            let mut active = self.active.borrow_mut();
+
+            let Some(index) = active.iter().position(|interaction| interaction.id == id) else {
+                return;
+            };
+
+            active.split_off(index)
+        };
+
+        for mut interaction in ended.into_iter().rev() {
+            self.rt.ctxt().exit(&mut interaction.frame);
+            self.rt.ctxt().close(interaction.frame);
+
+            let extent = interaction
+                .span
+                .start
+                .zip(self.rt.clock().now())
+                .map(|(start, end)| start..end);
+
+            self.rt.emit(interaction.span.to_span(extent));
+        }
+    }
+}
+
+impl InteractionSpan {
+    fn to_span(
+        &self,
+        extent: impl emit::extent::ToExtent,
+    ) -> emit::Span<'_, impl emit::Props + '_> {
+        emit::Span::new(
+            emit::path!("emit_web::interaction"),
+            emit::Str::new_ref(&self.name),
+            extent,
+            self.ctxt.and_props([
+                ("event_type", Some(emit::Value::from(&*self.event_type))),
+                ("target", self.target.as_deref().map(emit::Value::from)),
+                (
+                    "target_text",
+                    self.target_text.as_deref().map(emit::Value::from),
+                ),
+            ]),
+        )
+    }
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/interaction.js")]
+    extern "C" {
+        pub fn emit_web_interaction_install(
+            events: &JsValue,
+            record_text: bool,
+            begin: &JsValue,
+            end: &JsValue,
+        ) -> JsValue;
+        pub fn emit_web_interaction_uninstall(installed: &JsValue);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use std::sync::Mutex;
+
+    use js_sys::Function;
+
+    use crate::test_util::{get, tick, Globals, Root};
+
+    #[wasm_bindgen(inline_js = "
+    export function stub_dom() {
+        const target = new EventTarget();
+
+        globalThis.document = {};
+        globalThis.addEventListener = target.addEventListener.bind(target);
+        globalThis.removeEventListener = target.removeEventListener.bind(target);
+
+        const button = {
+            tagName: 'BUTTON',
+            id: 'save',
+            attributes: [
+                { name: 'class', value: 'primary' },
+                { name: 'data-action', value: 'save' },
+                { name: 'data-label', value: 'say \"hi\"' },
+            ],
+            textContent: '  Save   changes ',
+            closest() {
+                return this;
+            },
+        };
+
+        const icon = {
+            tagName: 'svg',
+            attributes: [],
+            closest() {
+                return button;
+            },
+        };
+
+        return {
+            dispatch: (type, handler) => {
+                const event = new Event(type);
+                Object.defineProperty(event, 'target', { value: icon });
+
+                // The window is the only target, so add the handler in the capture phase
+                // to run it between the tracer's listeners, like a handler on an element
+                target.addEventListener(type, handler, { once: true, capture: true });
+                target.dispatchEvent(event);
+            },
+        };
+    }
+    ")]
+    extern "C" {
+        #[derive(Clone)]
+        type Stub;
+
+        #[wasm_bindgen(js_name = stub_dom)]
+        fn stub_dom_js() -> Stub;
+
+        #[wasm_bindgen(method, structural)]
+        fn dispatch(this: &Stub, event_type: &str, handler: &JsValue);
+    }
+
+    fn stub_dom() -> (Stub, Globals) {
+        let globals = Globals::save(&["document", "addEventListener", "removeEventListener"]);
+
+        (stub_dom_js(), globals)
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Traced {
+        name: String,
+        event_type: String,
+        target: Option<String>,
+        target_text: Option<String>,
+        ctxt: emit::SpanCtxt,
+    }
+
+    static TRACED: Mutex<Vec<Traced>> = Mutex::new(Vec::new());
+
+    fn rt() -> &'static Rt {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+
+        rt_with_filter(&SLOT, emit::filter::from_fn(|_| true))
+    }
+
+    fn rt_with_filter(
+        slot: &'static emit::runtime::AmbientSlot,
+        filter: impl emit::Filter + Send + Sync + 'static,
+    ) -> &'static Rt {
+        let _ = slot.init(
+            emit::runtime::Runtime::new()
+                .with_filter(filter)
+                .with_emitter(emit::emitter::from_fn(|evt| {
+                    let props = evt.props();
+
+                    TRACED.lock().unwrap().push(Traced {
+                        name: props.pull("span_name").unwrap(),
+                        event_type: props.pull("event_type").unwrap(),
+                        target: props.pull("target"),
+                        target_text: props.pull("target_text"),
+                        ctxt: emit::SpanCtxt::new(
+                            props.pull("trace_id"),
+                            props.pull("span_parent"),
+                            props.pull("span_id"),
+                        ),
+                    })
+                }))
+                .with_ctxt(emit::platform::thread_local_ctxt::ThreadLocalCtxt::new())
+                .with_rng(crate::crypto_rng()),
+        );
+
+        slot.get()
+    }
+
+    /**
+    A handler that records the span that's current when it's called.
+    */
+    fn observe_current(rt: &'static Rt) -> (Rc<Cell<Option<emit::SpanCtxt>>>, JsValue) {
+        let current = Rc::new(Cell::new(None));
+
+        let handler = Closure::once_into_js({
+            let current = current.clone();
+
+            move || current.set(Some(emit::SpanCtxt::current(rt.ctxt())))
+        });
+
+        (current, handler)
+    }
+
+    async fn microtask() {
+        let _ = wasm_bindgen_futures::JsFuture::from(js_sys::Promise::resolve(&JsValue::UNDEFINED))
+            .await;
+    }
+
+    fn take() -> Vec<Traced> {
+        core::mem::take(&mut *TRACED.lock().unwrap())
+    }
+
+    #[wasm_bindgen_test]
+    async fn trace_clicks() {
+        let (stub, _globals) = stub_dom();
+        let rt = rt();
+
+        let tracer = trace_interactions(rt).install();
+
+        let current = Rc::new(Cell::new(None));
+        stub.dispatch(
+            "click",
+            &Closure::once_into_js({
+                let current = current.clone();
+
+                move || current.set(Some(emit::SpanCtxt::current(rt.ctxt())))
+            }),
+        );
+
+        // The interaction ends when the event has finished propagating
+        assert_eq!(
+            vec![Traced {
+                name: r#"click button#save[data-action="save"][data-label="say \"hi\""]"#.into(),
+                event_type: "click".into(),
+                target: Some(r#"button#save[data-action="save"][data-label="say \"hi\""]"#.into()),
+                target_text: None,
+                ctxt: current.get().unwrap(),
+            }],
+            take()
+        );
+
+        // The interaction is no longer current once it's ended
+        assert!(emit::SpanCtxt::current(rt.ctxt()).trace_id().is_none());
+
+        // The fallback timer doesn't emit the interaction again
+        tick().await;
+        assert!(take().is_empty());
+
+        drop(tracer);
+    }
+
+    #[wasm_bindgen_test]
+    async fn nested_interactions() {
+        let (stub, _globals) = stub_dom();
+        let rt = rt();
+
+        let tracer = trace_interactions(rt).record_text(true).install();
+
+        stub.dispatch(
+            "keydown",
+            &Closure::once_into_js({
+                let stub = stub.clone();
+
+                move || stub.dispatch("submit", &Closure::once_into_js(|| ()))
+            }),
+        );
+
+        let traced = take();
+        assert_eq!(
+            vec!["submit", "keydown"],
+            traced
+                .iter()
+                .map(|traced| &*traced.event_type)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(traced[1].ctxt.span_id(), traced[0].ctxt.span_parent());
+        assert_eq!(Some("Save changes".into()), traced[0].target_text);
+
+        assert!(emit::SpanCtxt::current(rt.ctxt()).trace_id().is_none());
+
+        drop(tracer);
+    }
+
+    #[wasm_bindgen_test]
+    async fn end_stopped_interactions_in_microtask() {
+        let (stub, _globals) = stub_dom();
+        let rt = rt();
+
+        let tracer = trace_interactions(rt).install();
+
+        stub.dispatch(
+            "click",
+            &Closure::once_into_js(|event: JsValue| {
+                get(&event, "stopImmediatePropagation")
+                    .unchecked_into::<Function>()
+                    .call0(&event)
+                    .unwrap();
+            }),
+        );
+
+        // The listener that ends the interaction wasn't reached
+        assert!(take().is_empty());
+
+        microtask().await;
+
+        assert_eq!(1, take().len());
+        assert!(emit::SpanCtxt::current(rt.ctxt()).trace_id().is_none());
+
+        drop(tracer);
+    }
+
+    #[wasm_bindgen_test]
+    async fn child_of_root_span() {
+        let (stub, _globals) = stub_dom();
+        let _root = Root::save();
+        let rt = rt();
+
+        let page = emit::SpanCtxt::new_root(crate::crypto_rng());
+        root::replace(page);
+
+        let tracer = trace_interactions(rt).install();
+
+        let (current, handler) = observe_current(rt);
+        stub.dispatch("click", &handler);
+
+        let traced = take();
+        assert_eq!(current.get().unwrap(), traced[0].ctxt);
+        assert_eq!(page.trace_id(), traced[0].ctxt.trace_id());
+        assert_eq!(page.span_id(), traced[0].ctxt.span_parent());
+
+        drop(tracer);
+    }
+
+    #[wasm_bindgen_test]
+    async fn skip_filtered_interactions() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+
+        let (stub, _globals) = stub_dom();
+        let rt = rt_with_filter(
+            &SLOT,
+            emit::filter::from_fn(|evt| {
+                evt.props().pull::<String, _>("event_type").as_deref() != Some("keydown")
+            }),
+        );
+
+        let tracer = trace_interactions(rt).install();
+
+        // The filtered interaction isn't made current while its handlers run
+        let (current, handler) = observe_current(rt);
+        stub.dispatch("keydown", &handler);
+
+        assert!(current.get().unwrap().trace_id().is_none());
+
+        tick().await;
+        assert!(take().is_empty());
+
+        drop(tracer);
+    }
+}
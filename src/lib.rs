@@ -5,6 +5,8 @@ Use [`emit`](https://docs.rs/emit) in WebAssembly applications targeting NodeJS
 
 `emit` itself and some emitters, like [`emit_otlp`](https://docs.rs/emit_otlp) support WebAssembly directly. This library includes support for emitting events to the [Console API](https://developer.mozilla.org/en-US/docs/Web/API/console). It also has alternative clocks and randomness using different web features. These aren't required for configuration, but can be used to more directly control the JavaScript APIs `emit` makes use of.
 
+`emit_web` includes JavaScript modules alongside its Rust code, so it needs a `wasm-bindgen` target that supports them, like `web`, `bundler`, `nodejs`, or `deno`. Earlier versions only used `js-sys`, so this is a breaking change for applications built with `--target no-modules`, which doesn't support JavaScript modules.
+
 # Getting started
 
 First, add `emit` and `emit_web` to your `Cargo.toml`:
@@ -14,7 +16,7 @@ First, add `emit` and `emit_web` to your `Cargo.toml`:
 version = "1"
 
 [dependencies.emit_web]
-version = "0.3.0"
+version = "0.4.0"
 ```
 
 Next, configure `emit` to use web APIs in its runtime:
@@ -31,6 +33,96 @@ pub fn setup() {
 ```
 
 The name of this `setup` function doesn't matter, you'll just need to call it somewhere early in your application.
+
+`emit`'s default ambient context is thread-local, so the current span is lost when control passes through a JavaScript promise or callback and back into Rust. Configure [`async_ctxt`] with `with_ctxt` to keep it in JavaScript's async context instead. See [`AsyncCtxt`] for the APIs it uses. This requires the `std` feature.
+
+Futures spawned with `wasm_bindgen_futures::spawn_local` don't carry the current span. Spawn them with [`spawn_local`] instead to keep the ambient context of the caller around every poll of the future. With [`task`], they can also be wrapped in a span that records how many times the future was polled and how long its longest poll blocked the main thread.
+
+To forward events into existing JavaScript tooling, like breadcrumbs in an error tracker, the [`js_callback`] function configures an emitter that calls a JavaScript function with each event as an object.
+
+If your JavaScript code is already instrumented with [OpenTelemetry](https://opentelemetry.io/docs/languages/js/), the [`otel_tracer`] function configures an emitter that turns completed spans into spans on an OpenTelemetry `Tracer`, so they join the same traces. Configure the tracer provider with the id generator from [`otel_id_generator`] to keep the ids of spans.
+
+Requests made with `fetch` can be traced with [`trace_fetch`], which wraps each one in a span. Requests to your own origins also carry a [W3C `traceparent`](https://www.w3.org/TR/trace-context/) header, so your servers can continue the trace.
+
+Server-rendered pages can continue the trace of the request that rendered them with [`page_load`], which starts a root span for the page as a child of the `traceparent` in a `<meta>` tag. Spans from the tracers in this library are its children, so they share the server's trace id. Your own spans can join it through [`root_ctxt`]. To see where the time to load the page went, [`record_page_load`] emits a `page_load` span with child spans for each phase of loading it, like DNS lookup and receiving the response, from the [Navigation Timing API](https://developer.mozilla.org/en-US/docs/Web/API/Performance_API/Navigation_timing).
+
+Single-page applications that change routes with the [History API](https://developer.mozilla.org/en-US/docs/Web/API/History_API) can start a new trace for each route with [`trace_routes`], so spans don't all end up in one trace for the whole session.
+
+To link what a user did to the work it triggered, [`trace_interactions`] starts a span for each interaction, like clicking a button or submitting a form. Spans created by the event's handlers are its children.
+
+Reports from the browser, like Content Security Policy violations, deprecation warnings, and interventions, can be emitted alongside your own events with [`observe_reports`], using the [Reporting API](https://developer.mozilla.org/en-US/docs/Web/API/Reporting_API).
+
+# Sending events to an HTTP endpoint
+
+The [`fetch`] function configures an emitter that sends batches of events as JSON to an HTTP endpoint using the [Fetch API](https://developer.mozilla.org/en-US/docs/Web/API/Fetch_API):
+
+```rust
+# use wasm_bindgen::prelude::*;
+# #[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn setup() {
+    let _ = emit::setup()
+        .emit_to(emit_web::fetch("https://example.com/ingest").build())
+        .try_init();
+}
+```
+
+Events still queued when the page is closed are lost, unless a beacon is configured with [`FetchEmitterBuilder::beacon_on_unload`] to send them using the [Beacon API](https://developer.mozilla.org/en-US/docs/Web/API/Beacon_API).
+
+To keep events while the device is offline, or across page reloads, wrap the emitter with [`offline`] to persist them in [IndexedDB](https://developer.mozilla.org/en-US/docs/Web/API/IndexedDB_API) until they're delivered.
+
+To live-tail events from devices without accessible developer tools, the [`websocket`] function configures an emitter that streams them over a [WebSocket](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket). The server can send back a filter to change which events are streamed on the fly.
+
+# Keeping recent events
+
+The [`storage_ring`] function configures an emitter that keeps the most recent events in [Web Storage](https://developer.mozilla.org/en-US/docs/Web/API/Web_Storage_API), where they survive page reloads.
+They can be retrieved from JavaScript with the exported `emitWebDumpLogs` function (see [`dump_logs`]), such as when a user is asked to share their logs with support.
+They can also be exported with [`export`] into a file to attach to bug reports, either as newline-delimited JSON, or in the Chrome Trace Event Format to open in [Perfetto](https://ui.perfetto.dev).
+
+# Sharing events between tabs and workers
+
+The [`broadcast_channel`] function configures an emitter that posts events to a named channel using the [Broadcast Channel API](https://developer.mozilla.org/en-US/docs/Web/API/Broadcast_Channel_API).
+A [`BroadcastChannelReceiver`] in another tab or worker on the same origin can emit them locally, so events from all of them can be seen in one console:
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[wasm_bindgen]
+pub fn setup() {
+    let receiver = emit_web::broadcast_channel_receiver("logs", emit_web::console());
+
+    // Keep receiving events for the lifetime of the page
+    core::mem::forget(receiver);
+}
+```
+
+Events emitted in a dedicated [Web Worker](https://developer.mozilla.org/en-US/docs/Web/API/Web_Workers_API) can be forwarded to the main thread with [`worker_bridge`], and emitted there with [`install_worker_bridge`].
+To keep traces connected when work is handed to a worker, [`attach_trace_context`] adds the current trace context to a message before it's posted, and [`restore_trace_context`] continues it in the worker.
+
+In multi-threaded builds using the `atomics` target feature, threads that share memory with the main thread can use [`shared_channel`] instead, which collects events in a lock-free ring buffer that's drained into an emitter on the main thread. This requires the `std` feature.
+
+Emitters that hold JavaScript values, or state shared with JavaScript callbacks, are only `Send` and `Sync` in single-threaded WebAssembly builds. With the `atomics` target feature they're neither, so they can't be set up as the global emitter, and need to be used on the main thread behind a `shared_channel`. These are [`FetchEmitter`], [`OfflineEmitter`], [`WebSocketEmitter`], [`StorageRingEmitter`], [`BroadcastChannelEmitter`], [`WorkerBridgeEmitter`], [`JsCallbackEmitter`], and [`OtelTracerEmitter`].
+
+# Capturing events in tests
+
+The [`capture`] function configures an emitter that captures events as JavaScript objects.
+Browser test suites can use the exported `emitWebEvents`, `emitWebClear`, and `emitWebWaitFor` functions to make assertions about them without scraping the console.
+
+To see events on devices without accessible developer tools during manual testing, the [`overlay`] function configures an emitter that shows recent events in a floating panel on the page itself.
+
+# Serialization
+
+Structured properties are converted into JavaScript values using [`serde`](https://docs.rs/serde) through the `serde` feature, which is enabled by default.
+
+If your application doesn't otherwise depend on `serde`, you can disable default features and enable the `sval` feature instead to use [`sval`](https://docs.rs/sval):
+
+```toml
+[dependencies.emit_web]
+version = "0.4.0"
+default-features = false
+features = ["std", "sval"]
+```
+
+If neither feature is enabled then only primitive values like strings, numbers, and booleans are converted into JavaScript values. Anything else is converted into a string.
 */
 
 #![doc(html_logo_url = "https://raw.githubusercontent.com/emit-rs/emit/main/asset/logo.svg")]
@@ -42,12 +134,73 @@ extern crate std;
 
 extern crate alloc;
 
-use alloc::{boxed::Box, string::ToString};
-use core::{ops::ControlFlow, time::Duration};
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use core::{ops::Deref, time::Duration};
 
-use emit::Props as _;
-use js_sys::{Date, Object, Reflect};
-use wasm_bindgen::prelude::*;
+use js_sys::Date;
+
+mod beacon;
+mod broadcast;
+mod callback;
+mod capture;
+#[cfg(feature = "std")]
+mod ctxt;
+mod export;
+mod fetch;
+mod interaction;
+mod offline;
+mod otel;
+mod overlay;
+mod page_load;
+mod propagate;
+mod record;
+mod reporting;
+mod root;
+mod route;
+mod ser;
+#[cfg(feature = "std")]
+mod shared;
+mod storage;
+mod task;
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test_util;
+mod trace_fetch;
+mod websocket;
+mod worker;
+
+#[cfg(feature = "std")]
+pub use self::ctxt::{async_ctxt, AsyncCtxt, AsyncCtxtFrame};
+#[cfg(feature = "std")]
+pub use self::shared::{shared_channel, SharedChannelEmitter, SharedChannelPump};
+
+pub use self::{
+    beacon::{beacon, Beacon},
+    broadcast::{
+        broadcast_channel, broadcast_channel_receiver, BroadcastChannelEmitter,
+        BroadcastChannelEmitterBuilder, BroadcastChannelReceiver,
+    },
+    callback::{js_callback, JsCallbackEmitter},
+    capture::{capture, captured_events, clear_captured_events, wait_for_event, CaptureEmitter},
+    export::{export, Export, ExportFormat},
+    fetch::{fetch, FetchEmitter, FetchEmitterBuilder, FetchFormat},
+    interaction::{trace_interactions, InteractionTracer, TraceInteractionsBuilder},
+    offline::{offline, OfflineEmitter, OfflineEmitterBuilder, OfflineTransport},
+    otel::{otel_id_generator, otel_tracer, OtelTracerEmitter},
+    overlay::{overlay, OverlayEmitter},
+    page_load::{page_load, record_page_load, PageLoadBuilder, PageLoadSpan},
+    propagate::{attach_trace_context, restore_trace_context},
+    reporting::{observe_reports, ReportObserver},
+    root::root_ctxt,
+    route::{trace_routes, RouteTracer, TraceRoutesBuilder},
+    storage::{dump_logs, storage_ring, StorageRingEmitter, WebStorage},
+    task::{spawn_local, task, TaskBuilder},
+    trace_fetch::{trace_fetch, FetchTracer, TraceFetchBuilder},
+    websocket::{websocket, WebSocketEmitter, WebSocketEmitterBuilder},
+    worker::{
+        install_worker_bridge, worker_bridge, WorkerBridgeEmitter, WorkerBridgeEmitterBuilder,
+    },
+};
 
 /**
 An emitter based on the [Console API](https://developer.mozilla.org/en-US/docs/Web/API/Console_API).
@@ -59,14 +212,31 @@ pub const fn console() -> ConsoleEmitter {
 /**
 An emitter based on the [Console API](https://developer.mozilla.org/en-US/docs/Web/API/Console_API).
 */
-pub struct ConsoleEmitter {}
+pub struct ConsoleEmitter {
+    always_use_map: bool,
+}
 
+#[allow(clippy::new_without_default)]
 impl ConsoleEmitter {
     /**
     Create a new instance of the console emitter.
     */
     pub const fn new() -> Self {
-        ConsoleEmitter {}
+        ConsoleEmitter {
+            always_use_map: false,
+        }
+    }
+
+    /**
+    Whether to always serialize maps in props as a JavaScript [`Map`](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Map).
+
+    By default, maps are serialized as plain objects as long as all their keys are strings.
+    If a map contains a key that isn't a string, like a number or a tuple, then it's serialized as a `Map` instead so keys don't get coerced to strings or collide.
+    Setting this to `true` will serialize all maps as a `Map`, regardless of their keys.
+    */
+    pub const fn always_use_map(mut self, always_use_map: bool) -> Self {
+        self.always_use_map = always_use_map;
+        self
     }
 }
 
@@ -74,17 +244,15 @@ impl emit::Emitter for ConsoleEmitter {
     fn emit<E: emit::event::ToEvent>(&self, evt: E) {
         let evt = evt.to_event();
 
-        let msg = evt.msg().to_string();
-        let extent = encode_extent(evt.extent());
-        let props = encode_props(evt.props());
+        let mut buf = ser::Buf::new(self.always_use_map);
 
-        match evt.props().pull("lvl") {
-            Some(emit::Level::Debug) => console::debug(&msg, extent, props),
-            Some(emit::Level::Info) => console::info(&msg, extent, props),
-            Some(emit::Level::Warn) => console::warn(&msg, extent, props),
-            Some(emit::Level::Error) => console::error(&msg, extent, props),
-            _ => console::log(&msg, extent, props),
-        }
+        buf.interned(level(&evt).unwrap_or("log"));
+        buf.msg(&evt);
+
+        buf.extent(evt.extent());
+        buf.props(evt.props());
+
+        buf.console();
     }
 
     fn blocking_flush(&self, _: core::time::Duration) -> bool {
@@ -94,32 +262,40 @@ impl emit::Emitter for ConsoleEmitter {
 
 impl emit::runtime::InternalEmitter for ConsoleEmitter {}
 
-fn encode_extent(extent: Option<&emit::Extent>) -> JsValue {
-    let Some(extent) = extent else {
-        return JsValue::NULL;
-    };
-
-    let map = Object::new();
+/**
+A value that can only be used on the thread that created it.
 
-    let timestamp_millis = duration_millis_f64(extent.as_point().to_unix());
+Emitters need to be `Send + Sync`, but JavaScript values and the state shared with callbacks aren't.
+Without the `atomics` target feature, WebAssembly only has a single thread, so they can't actually be shared.
+With it, or on any other target, `Local` isn't `Send` or `Sync`, and neither are the emitters that use it.
+*/
+struct Local<T>(T);
 
-    let _ = Reflect::set(
-        &map,
-        &JsValue::from_str("timestamp"),
-        &JsValue::from(Date::new(&JsValue::from_f64(timestamp_millis))),
-    );
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl<T> Send for Local<T> {}
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl<T> Sync for Local<T> {}
 
-    if let Some(len) = extent.len() {
-        let len_millis = duration_millis_f64(len);
+impl<T> Deref for Local<T> {
+    type Target = T;
 
-        let _ = Reflect::set(
-            &map,
-            &JsValue::from_str("milliseconds"),
-            &JsValue::from_f64(len_millis),
-        );
+    fn deref(&self) -> &T {
+        &self.0
     }
+}
 
-    map.into()
+/**
+The level of an event as a string, if it has one.
+
+The names of levels match the methods of the same name on `console`.
+*/
+fn level(evt: &emit::Event<impl emit::Props>) -> Option<&'static str> {
+    match evt.props().pull(emit::well_known::KEY_LVL)? {
+        emit::Level::Debug => Some("debug"),
+        emit::Level::Info => Some("info"),
+        emit::Level::Warn => Some("warn"),
+        emit::Level::Error => Some("error"),
+    }
 }
 
 fn duration_millis_f64(d: Duration) -> f64 {
@@ -129,44 +305,6 @@ fn duration_millis_f64(d: Duration) -> f64 {
     d_secs * 1_000.0 + d_subsec_nanos / 1_000_000.0
 }
 
-fn encode_props(props: impl emit::Props) -> JsValue {
-    struct PropsObject<P>(P);
-
-    impl<P: emit::Props> serde::Serialize for PropsObject<P> {
-        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-            use serde::ser::SerializeMap as _;
-
-            let mut map = serializer.serialize_map(None)?;
-
-            let mut r = Ok(());
-            let _ = self.0.for_each(|k, v| {
-                match (|| {
-                    map.serialize_key(&k)?;
-                    map.serialize_value(&v)
-                })() {
-                    Ok(()) => ControlFlow::Continue(()),
-                    Err(e) => {
-                        r = Err(e);
-                        ControlFlow::Break(())
-                    }
-                }
-            });
-            r?;
-
-            map.end()
-        }
-    }
-
-    to_jsvalue(PropsObject(props))
-}
-
-fn to_jsvalue(v: impl serde::Serialize) -> JsValue {
-    match ser::jsvalue(v) {
-        Ok(value) => value,
-        Err(err) => err,
-    }
-}
-
 /**
 A panic hook that emits panics as error events through the given `emitter`.
 
@@ -197,6 +335,7 @@ A clock based on the [Performance API](https://developer.mozilla.org/en-US/docs/
 */
 pub struct PerformanceClock {}
 
+#[allow(clippy::new_without_default)]
 impl PerformanceClock {
     /**
     Create a new instance of the performance clock.
@@ -220,13 +359,21 @@ impl emit::Clock for PerformanceClock {
 }
 
 fn performance_now() -> Duration {
+    performance_timestamp(performance::now())
+}
+
+/**
+The time since the Unix epoch of a high-resolution timestamp in milliseconds since `performance.timeOrigin`.
+
+This is the timestamp format used by `performance.now()` and the Performance Timeline.
+*/
+fn performance_timestamp(millis: f64) -> Duration {
     let origin_millis = performance::PERFORMANCE.with(|performance| performance.time_origin());
-    let now_millis = performance::now();
 
     let origin_nanos = (origin_millis * 1_000_000.0) as u128;
-    let now_nanos = (now_millis * 1_000_000.0) as u128;
+    let millis_nanos = (millis * 1_000_000.0) as u128;
 
-    let timestamp_nanos = origin_nanos + now_nanos;
+    let timestamp_nanos = origin_nanos + millis_nanos;
 
     let timestamp_secs = (timestamp_nanos / 1_000_000_000) as u64;
     let timestamp_subsec_nanos = (timestamp_nanos % 1_000_000_000) as u32;
@@ -246,6 +393,7 @@ A clock based on the [Date type](https://developer.mozilla.org/en-US/docs/Web/Ja
 */
 pub struct DateClock {}
 
+#[allow(clippy::new_without_default)]
 impl DateClock {
     /**
     Create a new instance of the date clock.
@@ -284,6 +432,7 @@ An RNG based on the [Crypto API](https://developer.mozilla.org/en-US/docs/Web/AP
 */
 pub struct CryptoRng {}
 
+#[allow(clippy::new_without_default)]
 impl CryptoRng {
     /**
     Create a new instance of the crypto RNG.
@@ -305,24 +454,6 @@ fn crypto_fill(buf: &mut [u8]) {
     crypto::get_random_values(buf);
 }
 
-mod console {
-    use wasm_bindgen::prelude::*;
-
-    #[wasm_bindgen]
-    extern "C" {
-        #[wasm_bindgen(js_namespace = console)]
-        pub fn log(msg: &str, extent: JsValue, props: JsValue);
-        #[wasm_bindgen(js_namespace = console)]
-        pub fn debug(msg: &str, extent: JsValue, props: JsValue);
-        #[wasm_bindgen(js_namespace = console)]
-        pub fn info(msg: &str, extent: JsValue, props: JsValue);
-        #[wasm_bindgen(js_namespace = console)]
-        pub fn warn(msg: &str, extent: JsValue, props: JsValue);
-        #[wasm_bindgen(js_namespace = console)]
-        pub fn error(msg: &str, extent: JsValue, props: JsValue);
-    }
-}
-
 mod performance {
     use wasm_bindgen::prelude::*;
 
@@ -341,438 +472,23 @@ mod performance {
     }
 }
 
-mod crypto {
+mod timers {
     use wasm_bindgen::prelude::*;
 
     #[wasm_bindgen]
     extern "C" {
-        #[wasm_bindgen(js_namespace = crypto, js_name = getRandomValues)]
-        pub fn get_random_values(buf: &mut [u8]);
+        #[wasm_bindgen(js_name = setTimeout)]
+        pub fn set_timeout(handler: &JsValue, timeout: f64);
     }
 }
 
-mod ser {
-    use alloc::string::ToString;
-    use core::fmt;
-
-    use js_sys::{Array, Object, Reflect, Uint8Array};
-    use serde::ser::{
-        Error, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
-        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer, StdError,
-    };
+mod crypto {
     use wasm_bindgen::prelude::*;
 
-    pub fn jsvalue(v: impl Serialize) -> Result<JsValue, JsValue> {
-        v.serialize(JsSerializer)
-            .map_err(|e| JsValue::from(e.to_string()))
-    }
-
-    #[derive(Debug)]
-    struct JsError;
-
-    impl From<JsValue> for JsError {
-        fn from(_: JsValue) -> Self {
-            JsError
-        }
-    }
-
-    impl fmt::Display for JsError {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            f.write_str("failed to serialize a value to JavaScript")
-        }
-    }
-
-    impl StdError for JsError {}
-
-    impl Error for JsError {
-        fn custom<T>(_: T) -> Self
-        where
-            T: fmt::Display,
-        {
-            JsError
-        }
-    }
-
-    struct JsSerializer;
-
-    struct JsArraySerializer {
-        variant: Option<&'static str>,
-        result: Array,
-    }
-
-    struct JsObjectSerializer {
-        variant: Option<&'static str>,
-        key: Option<JsValue>,
-        result: Object,
-    }
-
-    impl Serializer for JsSerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-        type SerializeSeq = JsArraySerializer;
-        type SerializeTuple = JsArraySerializer;
-        type SerializeTupleStruct = JsArraySerializer;
-        type SerializeTupleVariant = JsArraySerializer;
-        type SerializeMap = JsObjectSerializer;
-        type SerializeStruct = JsObjectSerializer;
-        type SerializeStructVariant = JsObjectSerializer;
-
-        fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(v))
-        }
-
-        fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-            self.serialize_i128(v as i128)
-        }
-
-        fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-            self.serialize_i128(v as i128)
-        }
-
-        fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-            self.serialize_i128(v as i128)
-        }
-
-        fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-            self.serialize_i128(v as i128)
-        }
-
-        fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-            if v.abs() > (i128::MAX >> 74) {
-                Ok(JsValue::bigint_from_str(&v.to_string()))
-            } else {
-                Ok(JsValue::from(v as f64))
-            }
-        }
-
-        fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-            self.serialize_u128(v as u128)
-        }
-
-        fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-            self.serialize_u128(v as u128)
-        }
-
-        fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-            self.serialize_u128(v as u128)
-        }
-
-        fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-            self.serialize_u128(v as u128)
-        }
-
-        fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-            if v > (u128::MAX >> 75) {
-                Ok(JsValue::bigint_from_str(&v.to_string()))
-            } else {
-                Ok(JsValue::from(v as f64))
-            }
-        }
-
-        fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(v as f64))
-        }
-
-        fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(v))
-        }
-
-        fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-            let mut buf = [0; 4];
-            let v = v.encode_utf8(&mut buf);
-
-            Ok(JsValue::from(&*v))
-        }
-
-        fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(v))
-        }
-
-        fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-            let buf = Uint8Array::from(v);
-
-            Ok(JsValue::from(buf))
-        }
-
-        fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::null())
-        }
-
-        fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            value.serialize(self)
-        }
-
-        fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::null())
-        }
-
-        fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(name))
-        }
-
-        fn serialize_unit_variant(
-            self,
-            _: &'static str,
-            _: u32,
-            variant: &'static str,
-        ) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(variant))
-        }
-
-        fn serialize_newtype_struct<T>(
-            self,
-            _: &'static str,
-            value: &T,
-        ) -> Result<Self::Ok, Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            value.serialize(self)
-        }
-
-        fn serialize_newtype_variant<T>(
-            self,
-            _: &'static str,
-            _: u32,
-            variant: &'static str,
-            value: &T,
-        ) -> Result<Self::Ok, Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            jsvariant(variant, jsvalue(value)?)
-        }
-
-        fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-            Ok(JsArraySerializer {
-                variant: None,
-                result: Array::new(),
-            })
-        }
-
-        fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> {
-            Ok(JsArraySerializer {
-                variant: None,
-                result: Array::new(),
-            })
-        }
-
-        fn serialize_tuple_struct(
-            self,
-            _: &'static str,
-            _: usize,
-        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-            Ok(JsArraySerializer {
-                variant: None,
-                result: Array::new(),
-            })
-        }
-
-        fn serialize_tuple_variant(
-            self,
-            _: &'static str,
-            _: u32,
-            variant: &'static str,
-            _: usize,
-        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-            Ok(JsArraySerializer {
-                variant: Some(variant),
-                result: Array::new(),
-            })
-        }
-
-        fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-            Ok(JsObjectSerializer {
-                variant: None,
-                key: None,
-                result: Object::new(),
-            })
-        }
-
-        fn serialize_struct(
-            self,
-            _: &'static str,
-            _: usize,
-        ) -> Result<Self::SerializeStruct, Self::Error> {
-            Ok(JsObjectSerializer {
-                variant: None,
-                key: None,
-                result: Object::new(),
-            })
-        }
-
-        fn serialize_struct_variant(
-            self,
-            _: &'static str,
-            _: u32,
-            variant: &'static str,
-            _: usize,
-        ) -> Result<Self::SerializeStructVariant, Self::Error> {
-            Ok(JsObjectSerializer {
-                variant: Some(variant),
-                key: None,
-                result: Object::new(),
-            })
-        }
-    }
-
-    impl SerializeSeq for JsArraySerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-
-        fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            self.result.push(&jsvalue(value)?);
-
-            Ok(())
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(self.result))
-        }
-    }
-
-    impl SerializeTuple for JsArraySerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-
-        fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            self.result.push(&jsvalue(value)?);
-
-            Ok(())
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(self.result))
-        }
-    }
-
-    impl SerializeTupleStruct for JsArraySerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-
-        fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            self.result.push(&jsvalue(value)?);
-
-            Ok(())
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(self.result))
-        }
-    }
-
-    impl SerializeTupleVariant for JsArraySerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-
-        fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            self.result.push(&jsvalue(value)?);
-
-            Ok(())
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            jsvariant(
-                self.variant
-                    .ok_or_else(|| JsError::custom("missing variant"))?,
-                JsValue::from(self.result),
-            )
-        }
-    }
-
-    impl SerializeMap for JsObjectSerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-
-        fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            self.key = Some(jsvalue(key)?);
-
-            Ok(())
-        }
-
-        fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            let key = self
-                .key
-                .take()
-                .ok_or_else(|| JsError::custom("missing key for a value"))?;
-
-            Reflect::set(&self.result, &key, &jsvalue(value)?)?;
-
-            Ok(())
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(self.result))
-        }
-    }
-
-    impl SerializeStruct for JsObjectSerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-
-        fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            Reflect::set(&self.result, &JsValue::from(key), &jsvalue(value)?)?;
-
-            Ok(())
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            Ok(JsValue::from(self.result))
-        }
-    }
-
-    impl SerializeStructVariant for JsObjectSerializer {
-        type Ok = JsValue;
-        type Error = JsError;
-
-        fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            Reflect::set(&self.result, &JsValue::from(key), &jsvalue(value)?)?;
-
-            Ok(())
-        }
-
-        fn end(self) -> Result<Self::Ok, Self::Error> {
-            jsvariant(
-                self.variant
-                    .ok_or_else(|| JsError::custom("missing variant"))?,
-                JsValue::from(self.result),
-            )
-        }
-    }
-
-    fn jsvariant(variant: &str, value: JsValue) -> Result<JsValue, JsError> {
-        let result = Object::new();
-
-        Reflect::set(&result, &JsValue::from(variant), &value)?;
-
-        Ok(JsValue::from(result))
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = crypto, js_name = getRandomValues)]
+        pub fn get_random_values(buf: &mut [u8]);
     }
 }
 
@@ -0,0 +1,143 @@
+// Persists events for the `OfflineEmitter` in `emit_web` using IndexedDB.
+//
+// Records are kept in a single object store keyed by their id. Each one
+// has a sequence number so they can be replayed and evicted in order.
+// The ids and sizes of stored records are also kept in memory, so the
+// size of the store can be capped without reading it back.
+
+const STORE = "events";
+
+function request(req) {
+    return new Promise((resolve, reject) => {
+        req.onsuccess = () => resolve(req.result);
+        req.onerror = () => reject(req.error);
+    });
+}
+
+function open(name) {
+    const req = indexedDB.open(name, 1);
+    req.onupgradeneeded = () => req.result.createObjectStore(STORE, { keyPath: "id" });
+
+    return request(req);
+}
+
+async function getAll(db) {
+    const records = await request(db.transaction(STORE, "readonly").objectStore(STORE).getAll());
+    records.sort((a, b) => a.seq - b.seq);
+
+    return records;
+}
+
+function write(store, f) {
+    store.db.then((db) => {
+        if (db === null) {
+            return;
+        }
+
+        // Writes may fail if the origin is out of storage
+        // Records that can't be stored are still sent
+        try {
+            const tx = db.transaction(STORE, "readwrite");
+            tx.onerror = (e) => e.preventDefault();
+
+            f(tx.objectStore(STORE));
+        } catch {}
+    });
+}
+
+function evict(store) {
+    const evicted = [];
+
+    while (store.entries.length > store.maxRecords || store.bytes > store.maxBytes) {
+        const entry = store.entries.shift();
+
+        store.bytes -= entry.len;
+        evicted.push(entry.id);
+    }
+
+    return evicted;
+}
+
+export function emit_web_store_open(name, maxRecords, maxBytes) {
+    const store = { db: null, seq: 0, entries: [], bytes: 0, maxRecords, maxBytes };
+
+    store.db = (async () => {
+        try {
+            const db = await open(name);
+
+            const records = await getAll(db);
+
+            store.entries.unshift(...records.map((record) => ({ id: record.id, len: record.json.length })));
+            store.bytes += records.reduce((bytes, record) => bytes + record.json.length, 0);
+            store.seq = records.reduce((seq, record) => Math.max(seq, record.seq + 1), store.seq);
+
+            return db;
+        } catch {
+            // IndexedDB may be unavailable, like in some private browsing modes
+            return null;
+        }
+    })();
+
+    return store;
+}
+
+export function emit_web_store_put(store, id, json) {
+    store.entries.push({ id, len: json.length });
+    store.bytes += json.length;
+
+    const evicted = evict(store);
+
+    write(store, (events) => {
+        for (const id of evicted) {
+            events.delete(id);
+        }
+
+        // Sequence numbers are assigned once existing records are loaded
+        if (!evicted.includes(id)) {
+            events.put({ id, seq: store.seq++, json });
+        }
+    });
+}
+
+export function emit_web_store_delete(store, ids) {
+    const deleted = new Set(ids);
+
+    store.entries = store.entries.filter((entry) => {
+        if (deleted.has(entry.id)) {
+            store.bytes -= entry.len;
+            return false;
+        }
+
+        return true;
+    });
+
+    write(store, (events) => {
+        for (const id of ids) {
+            events.delete(id);
+        }
+    });
+}
+
+export function emit_web_store_replay(store, f) {
+    store.db.then(async (db) => {
+        if (db === null) {
+            return;
+        }
+
+        try {
+            const records = await getAll(db);
+
+            f(records.map((record) => [record.id, record.json]));
+        } catch {}
+    });
+}
+
+export function emit_web_on_online(f) {
+    if (typeof addEventListener === "function") {
+        addEventListener("online", () => f());
+    }
+}
+
+export function emit_web_is_online() {
+    return typeof navigator === "undefined" || navigator.onLine !== false;
+}
@@ -0,0 +1,585 @@
+/*!
+Persist events in IndexedDB so they survive being offline or the page reloading.
+*/
+
+use alloc::{boxed::Box, collections::BTreeSet, rc::Rc, string::String, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    fmt::Write as _,
+    time::Duration,
+};
+
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+
+use crate::{crypto_fill, duration_millis_f64, ser, timers, FetchEmitter, Local};
+
+/**
+An [`OfflineEmitter`] that persists events in [IndexedDB](https://developer.mozilla.org/en-US/docs/Web/API/IndexedDB_API) before sending them through `transport`.
+
+See [`OfflineEmitterBuilder`] for configuration.
+*/
+pub fn offline<T: OfflineTransport>(transport: T) -> OfflineEmitterBuilder<T> {
+    OfflineEmitterBuilder::new(transport)
+}
+
+/**
+A transport that an [`OfflineEmitter`] sends stored events through.
+
+Transports send events in the background, and report back once they're finished with them, so delivered events can be removed from the store.
+[`FetchEmitter`] is a transport.
+*/
+pub trait OfflineTransport: emit::Emitter {
+    /**
+    Queue an event to send.
+
+    The `json` is a JSON object in the schema described by [`crate::FetchFormat`], with an additional `id` field.
+    */
+    fn push(&self, id: Rc<str>, json: String);
+
+    /**
+    Call `settled` with the ids of events once the transport is finished with them.
+
+    The second argument is whether the events should be kept to try again later, because they weren't delivered.
+    Events that are never settled stay in the store, and are sent again when the page next loads.
+    */
+    fn on_settled(&self, settled: Box<dyn Fn(Vec<Rc<str>>, bool)>);
+}
+
+/**
+A builder for an [`OfflineEmitter`].
+*/
+pub struct OfflineEmitterBuilder<T = FetchEmitter> {
+    transport: T,
+    name: String,
+    max_records: usize,
+    max_len: usize,
+    retry_interval: Duration,
+}
+
+impl<T: OfflineTransport> OfflineEmitterBuilder<T> {
+    /**
+    Create a new builder for an emitter that sends events through `transport`.
+    */
+    pub fn new(transport: T) -> Self {
+        OfflineEmitterBuilder {
+            transport,
+            name: "emit_web".into(),
+            max_records: 10_000,
+            max_len: 5 * 1024 * 1024,
+            retry_interval: Duration::from_secs(30),
+        }
+    }
+
+    /**
+    Set the name of the IndexedDB database to store events in.
+
+    The default name is `emit_web`.
+    */
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /**
+    Set the maximum number of events to store.
+
+    When the store is full, the oldest events are evicted to make room for new ones.
+    The default maximum is 10,000 events.
+    */
+    pub fn max_records(mut self, max_records: usize) -> Self {
+        self.max_records = max_records;
+        self
+    }
+
+    /**
+    Set the maximum combined length of stored events, in UTF-16 code units.
+
+    When the store is full, the oldest events are evicted to make room for new ones.
+    The default maximum is 5Mi code units.
+    */
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /**
+    Set the time to wait before sending events again when the transport fails to deliver them.
+
+    The default interval is 30 seconds.
+    */
+    pub fn retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /**
+    Build an [`OfflineEmitter`] from this configuration.
+
+    Any events stored by a previous page are replayed immediately.
+    */
+    pub fn build(self) -> OfflineEmitter<T>
+    where
+        T: 'static,
+    {
+        let inner = Rc::new(Inner {
+            store: shim::emit_web_store_open(&self.name, self.max_records, self.max_len),
+            transport: self.transport,
+            pending: RefCell::new(BTreeSet::new()),
+            retry_interval: self.retry_interval,
+            retry_scheduled: Cell::new(false),
+        });
+
+        let settled = Rc::downgrade(&inner);
+        inner.transport.on_settled(Box::new(move |ids, keep| {
+            if let Some(inner) = settled.upgrade() {
+                inner.settled(ids, keep);
+            }
+        }));
+
+        let online = Rc::downgrade(&inner);
+        shim::emit_web_on_online(
+            &Closure::<dyn FnMut()>::new(move || {
+                if let Some(inner) = online.upgrade() {
+                    inner.replay();
+                }
+            })
+            .into_js_value(),
+        );
+
+        inner.replay();
+
+        OfflineEmitter {
+            inner: Local(inner),
+        }
+    }
+}
+
+/**
+An emitter that persists events in [IndexedDB](https://developer.mozilla.org/en-US/docs/Web/API/IndexedDB_API) so they aren't lost while the device is offline, or when the page is reloaded.
+
+Each event is given a random id and stored before being sent through an [`OfflineTransport`], like a [`FetchEmitter`].
+Events are only removed from the store once they've been delivered, or rejected by the endpoint with a status that won't be retried.
+While [`navigator.onLine`](https://developer.mozilla.org/en-US/docs/Web/API/Navigator/onLine) is `false`, events are only stored.
+Stored events are replayed when the page comes back online, and when the emitter is created on the next page load.
+Events the transport fails to deliver are also replayed after a configured interval.
+
+Delivery is at-least-once, so the same event may be sent more than once.
+Events sent by this emitter have an `id` field that endpoints can use to deduplicate them.
+If IndexedDB isn't available, or the origin runs out of storage, then events are still sent, but aren't stored.
+
+Use [`offline`] to configure a new emitter.
+*/
+pub struct OfflineEmitter<T = FetchEmitter> {
+    inner: Local<Rc<Inner<T>>>,
+}
+
+struct Inner<T> {
+    store: JsValue,
+    transport: T,
+    // The ids of records queued in the transport
+    pending: RefCell<BTreeSet<Rc<str>>>,
+    retry_interval: Duration,
+    // Whether a timer will replay records the transport failed to deliver
+    retry_scheduled: Cell<bool>,
+}
+
+impl<T: OfflineTransport + 'static> emit::Emitter for OfflineEmitter<T> {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let id: Rc<str> = new_id().into();
+
+        let mut buf = ser::Buf::new(false);
+        buf.record(&evt, Some(&id));
+
+        let json = buf.json();
+
+        shim::emit_web_store_put(&self.inner.store, &id, &json);
+
+        if shim::emit_web_is_online() {
+            self.inner.send(id, json);
+        }
+    }
+
+    fn blocking_flush(&self, timeout: Duration) -> bool {
+        self.inner.transport.blocking_flush(timeout)
+    }
+}
+
+impl<T: OfflineTransport + 'static> Inner<T> {
+    fn send(&self, id: Rc<str>, json: String) {
+        // The record is already on its way
+        if !self.pending.borrow_mut().insert(id.clone()) {
+            return;
+        }
+
+        self.transport.push(id, json);
+    }
+
+    fn settled(self: &Rc<Self>, ids: Vec<Rc<str>>, keep: bool) {
+        {
+            let mut pending = self.pending.borrow_mut();
+
+            for id in &ids {
+                pending.remove(id);
+            }
+        }
+
+        if keep {
+            self.schedule_retry();
+        } else {
+            let ids = ids.iter().map(|id| JsValue::from(&**id)).collect::<Array>();
+
+            shim::emit_web_store_delete(&self.store, &ids);
+        }
+    }
+
+    /**
+    Replay stored records after the retry interval, unless that's already scheduled.
+
+    Replays while offline are left for when the page comes back online.
+    */
+    fn schedule_retry(self: &Rc<Self>) {
+        if self.retry_scheduled.replace(true) {
+            return;
+        }
+
+        let inner = Rc::downgrade(self);
+        timers::set_timeout(
+            &Closure::once_into_js(move || {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+
+                inner.retry_scheduled.set(false);
+
+                if shim::emit_web_is_online() {
+                    inner.replay();
+                }
+            }),
+            duration_millis_f64(self.retry_interval),
+        );
+    }
+
+    /**
+    Send any stored records that aren't already queued.
+    */
+    fn replay(self: &Rc<Self>) {
+        let inner = Rc::downgrade(self);
+
+        shim::emit_web_store_replay(
+            &self.store,
+            &Closure::once_into_js(move |records: JsValue| {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+
+                for record in Array::from(&records) {
+                    let record = Array::from(&record);
+
+                    if let (Some(id), Some(json)) =
+                        (record.get(0).as_string(), record.get(1).as_string())
+                    {
+                        inner.send(id.into(), json);
+                    }
+                }
+            }),
+        );
+    }
+}
+
+fn new_id() -> String {
+    let mut bytes = [0; 16];
+    crypto_fill(&mut bytes);
+
+    let mut id = String::with_capacity(32);
+    for b in bytes {
+        let _ = write!(id, "{b:02x}");
+    }
+
+    id
+}
+
+mod shim {
+    use js_sys::Array;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/offline.js")]
+    extern "C" {
+        pub fn emit_web_store_open(name: &str, max_records: usize, max_len: usize) -> JsValue;
+        pub fn emit_web_store_put(store: &JsValue, id: &str, json: &str);
+        pub fn emit_web_store_delete(store: &JsValue, ids: &Array);
+        pub fn emit_web_store_replay(store: &JsValue, f: &JsValue);
+        pub fn emit_web_on_online(f: &JsValue);
+        pub fn emit_web_is_online() -> bool;
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::Emitter as _;
+    use js_sys::JSON;
+
+    use crate::{
+        fetch,
+        test_util::{get, sleep, Globals},
+    };
+
+    // A minimal in-memory IndexedDB that supports just the operations the store uses
+    #[wasm_bindgen(inline_js = "
+    const databases = new Map();
+
+    function later(req, result) {
+        setTimeout(() => {
+            req.result = result;
+            req.onsuccess?.();
+        }, 0);
+
+        return req;
+    }
+
+    function database() {
+        const stores = new Map();
+
+        return {
+            createObjectStore(name, { keyPath }) {
+                stores.set(name, { keyPath, records: new Map() });
+            },
+            transaction(name) {
+                const store = stores.get(name);
+
+                return {
+                    objectStore: () => ({
+                        put: (v) => store.records.set(v[store.keyPath], structuredClone(v)),
+                        delete: (k) => store.records.delete(k),
+                        getAll: () => later({}, Array.from(store.records.values(), (v) => structuredClone(v))),
+                    }),
+                };
+            },
+            stores,
+        };
+    }
+
+    export function stub_env(online, statuses) {
+        statuses = Array.from(statuses);
+
+        globalThis.indexedDB = {
+            open(name) {
+                const req = {};
+
+                setTimeout(() => {
+                    if (!databases.has(name)) {
+                        req.result = database();
+                        databases.set(name, req.result);
+                        req.onupgradeneeded?.();
+                    }
+
+                    req.result = databases.get(name);
+                    req.onsuccess?.();
+                }, 0);
+
+                return req;
+            },
+        };
+
+        const listeners = [];
+        globalThis.addEventListener = (type, f) => type === 'online' && listeners.push(f);
+
+        const navigator = { onLine: online };
+        Object.defineProperty(globalThis, 'navigator', { configurable: true, value: navigator });
+
+        const requests = [];
+        globalThis.fetch = async (url, init) => {
+            requests.push(init.body);
+            return { status: statuses.length > 0 ? statuses.shift() : 200 };
+        };
+
+        return {
+            requests,
+            online: () => {
+                navigator.onLine = true;
+                listeners.forEach((f) => f());
+            },
+        };
+    }
+
+    export function stored(name) {
+        const records = databases.get(name)?.stores.get('events').records;
+
+        return records ? Array.from(records.values(), (record) => JSON.parse(record.json)) : [];
+    }
+    ")]
+    extern "C" {
+        #[wasm_bindgen(js_name = stub_env)]
+        fn stub_env_js(online: bool, statuses: Vec<u16>) -> JsValue;
+        fn stored(name: &str) -> Array;
+    }
+
+    fn stub_env(online: bool, statuses: Vec<u16>) -> (JsValue, Globals) {
+        let globals = Globals::save(&["indexedDB", "addEventListener", "navigator", "fetch"]);
+
+        (stub_env_js(online, statuses), globals)
+    }
+
+    fn requests(env: &JsValue) -> Vec<Array> {
+        Array::from(&get(env, "requests"))
+            .iter()
+            .map(|body| Array::from(&JSON::parse(&body.as_string().unwrap()).unwrap()))
+            .collect()
+    }
+
+    fn emitter(name: &str) -> OfflineEmitter {
+        offline(
+            fetch("http://localhost/ingest")
+                .batch_size(1)
+                .max_retries(0)
+                .build(),
+        )
+        .name(name)
+        .build()
+    }
+
+    #[wasm_bindgen_test]
+    async fn delete_delivered() {
+        let (env, _globals) = stub_env(true, vec![]);
+        let emitter = emitter("delete_delivered");
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(10.0).await;
+
+        let requests = requests(&env);
+        assert_eq!(1, requests.len());
+        assert_eq!(
+            32,
+            get(&requests[0].get(0), "id").as_string().unwrap().len()
+        );
+
+        assert_eq!(0, stored("delete_delivered").length());
+    }
+
+    #[wasm_bindgen_test]
+    async fn keep_undelivered() {
+        let (env, _globals) = stub_env(true, vec![503]);
+        let emitter = emitter("keep_undelivered");
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(10.0).await;
+
+        assert_eq!(1, requests(&env).len());
+        assert_eq!(1, stored("keep_undelivered").length());
+    }
+
+    #[wasm_bindgen_test]
+    async fn replay_when_online() {
+        let (env, _globals) = stub_env(false, vec![]);
+        let emitter = emitter("replay_when_online");
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+
+        sleep(10.0).await;
+
+        assert_eq!(0, requests(&env).len());
+        assert_eq!(2, stored("replay_when_online").length());
+
+        get(&env, "online")
+            .dyn_into::<js_sys::Function>()
+            .unwrap()
+            .call0(&JsValue::NULL)
+            .unwrap();
+
+        sleep(10.0).await;
+
+        let requests = requests(&env);
+        assert_eq!(2, requests.len());
+        assert_eq!(JsValue::from("event 1"), get(&requests[0].get(0), "msg"));
+        assert_eq!(JsValue::from("event 2"), get(&requests[1].get(0), "msg"));
+
+        assert_eq!(0, stored("replay_when_online").length());
+    }
+
+    #[wasm_bindgen_test]
+    async fn retry_after_interval() {
+        let (env, _globals) = stub_env(true, vec![503]);
+        let emitter = offline(
+            fetch("http://localhost/ingest")
+                .batch_size(1)
+                .max_retries(0)
+                .build(),
+        )
+        .name("retry_after_interval")
+        .retry_interval(Duration::from_millis(20))
+        .build();
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(10.0).await;
+
+        assert_eq!(1, requests(&env).len());
+        assert_eq!(1, stored("retry_after_interval").length());
+
+        sleep(40.0).await;
+
+        let requests = requests(&env);
+        assert_eq!(2, requests.len());
+        assert_eq!(
+            get(&requests[0].get(0), "id"),
+            get(&requests[1].get(0), "id")
+        );
+
+        assert_eq!(0, stored("retry_after_interval").length());
+    }
+
+    #[wasm_bindgen_test]
+    async fn replay_on_load() {
+        let (env, _globals) = stub_env(true, vec![503]);
+        let emitter = emitter("replay_on_load");
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(10.0).await;
+        drop(emitter);
+
+        let stored_id = get(&stored("replay_on_load").get(0), "id");
+
+        let _emitter = self::emitter("replay_on_load");
+
+        sleep(10.0).await;
+
+        let requests = requests(&env);
+        assert_eq!(2, requests.len());
+        assert_eq!(stored_id, get(&requests[1].get(0), "id"));
+
+        assert_eq!(0, stored("replay_on_load").length());
+    }
+
+    #[wasm_bindgen_test]
+    async fn evict_oldest() {
+        let (_env, _globals) = stub_env(false, vec![]);
+        let emitter = offline(fetch("http://localhost/ingest").build())
+            .name("evict_oldest")
+            .max_records(2)
+            .build();
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+        emitter.emit(emit::evt!("event {n}", n: 3));
+
+        sleep(10.0).await;
+
+        let stored = stored("evict_oldest");
+        let mut msgs = stored
+            .iter()
+            .map(|record| get(&record, "msg").as_string().unwrap())
+            .collect::<Vec<_>>();
+        msgs.sort();
+
+        assert_eq!(vec!["event 2", "event 3"], msgs);
+    }
+}
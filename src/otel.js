@@ -0,0 +1,160 @@
+// Starts and ends OpenTelemetry spans for the `OtelTracerEmitter` in `emit_web`.
+//
+// Parent contexts are built here instead of through `@opentelemetry/api`, so
+// the package doesn't need to be shared with Rust. The keys of contexts are
+// global symbols, so contexts built here are understood by the SDK. Ids of
+// spans can only be chosen by the tracer provider's id generator, so the one
+// returned by `emit_web_otel_id_generator` hands out the ids of the span
+// currently being started.
+
+const SPAN_KEY = Symbol.for("OpenTelemetry Context Key SPAN");
+const STATUS_ERROR = 2;
+const TRACE_FLAGS_SAMPLED = 1;
+
+// The ids of the span currently being started, if any
+let pending = null;
+
+class Context {
+    constructor(values = new Map()) {
+        this.values = values;
+    }
+
+    getValue(key) {
+        return this.values.get(key);
+    }
+
+    setValue(key, value) {
+        const values = new Map(this.values);
+        values.set(key, value);
+
+        return new Context(values);
+    }
+
+    deleteValue(key) {
+        const values = new Map(this.values);
+        values.delete(key);
+
+        return new Context(values);
+    }
+}
+
+// A span that only carries a context, like `NonRecordingSpan` in `@opentelemetry/api`
+function parent(traceId, spanId) {
+    const spanContext = { traceId, spanId, traceFlags: TRACE_FLAGS_SAMPLED, isRemote: false };
+
+    const span = {
+        spanContext: () => spanContext,
+        isRecording: () => false,
+        setAttribute: () => span,
+        setAttributes: () => span,
+        addEvent: () => span,
+        addLink: () => span,
+        addLinks: () => span,
+        setStatus: () => span,
+        updateName: () => span,
+        recordException: () => {},
+        end: () => {},
+    };
+
+    return span;
+}
+
+function isPrimitive(value) {
+    return typeof value === "string" || typeof value === "number" || typeof value === "boolean";
+}
+
+// Attributes can only be primitives or arrays of them, so anything else is written as JSON
+function attributes(props) {
+    const attributes = {};
+
+    for (const [key, value] of Object.entries(props)) {
+        if (value === null || value === undefined) {
+            continue;
+        }
+
+        if (isPrimitive(value) || (Array.isArray(value) && value.every(isPrimitive))) {
+            attributes[key] = value;
+        } else if (typeof value === "bigint") {
+            attributes[key] = value.toString();
+        } else {
+            attributes[key] = JSON.stringify(value, (_, value) => {
+                if (typeof value === "bigint") {
+                    return value.toString();
+                }
+
+                if (value instanceof Map) {
+                    return Array.from(value.entries());
+                }
+
+                return value;
+            });
+        }
+    }
+
+    return attributes;
+}
+
+function randomHex(bytes) {
+    const buf = new Uint8Array(bytes);
+    crypto.getRandomValues(buf);
+
+    return Array.from(buf, (b) => b.toString(16).padStart(2, "0")).join("");
+}
+
+const idGenerator = {
+    generateTraceId() {
+        const traceId = pending?.traceId;
+
+        if (traceId) {
+            pending.traceId = null;
+            return traceId;
+        }
+
+        return randomHex(16);
+    },
+
+    generateSpanId() {
+        const spanId = pending?.spanId;
+
+        if (spanId) {
+            pending.spanId = null;
+            return spanId;
+        }
+
+        return randomHex(8);
+    },
+};
+
+export function emit_web_otel_id_generator() {
+    return idGenerator;
+}
+
+export function emit_web_otel_span(tracer, span, start, end) {
+    let context = new Context();
+    if (span.parentId !== null) {
+        context = context.setValue(SPAN_KEY, parent(span.traceId, span.parentId));
+    }
+
+    pending = { traceId: span.traceId, spanId: span.spanId };
+
+    let otelSpan;
+    try {
+        otelSpan = tracer.startSpan(
+            span.name,
+            {
+                kind: span.kind ?? undefined,
+                startTime: start,
+                attributes: attributes(span.attributes),
+            },
+            context,
+        );
+    } finally {
+        pending = null;
+    }
+
+    if (span.err !== null) {
+        otelSpan.setStatus({ code: STATUS_ERROR, message: span.err });
+    }
+
+    otelSpan.end(end);
+}
@@ -0,0 +1,398 @@
+/*!
+Bridge spans into an OpenTelemetry tracer in JavaScript.
+*/
+
+use core::{ops::ControlFlow, time::Duration};
+
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+
+use crate::{ser, Local};
+
+/**
+An [`OtelTracerEmitter`] that turns completed spans into spans on `tracer`.
+*/
+pub fn otel_tracer(tracer: JsValue) -> OtelTracerEmitter {
+    OtelTracerEmitter::new(tracer)
+}
+
+/**
+An emitter that turns completed spans into spans on a [`Tracer`](https://open-telemetry.github.io/opentelemetry-js/interfaces/_opentelemetry_api.Tracer.html) from `@opentelemetry/api`.
+
+This lets spans from Rust join the same traces as spans from JavaScript code that's already instrumented with OpenTelemetry.
+Events that aren't spans, or that don't have a trace id, span id, and range extent, are ignored.
+
+Each span is mapped as follows:
+
+- The name is the `span_name` property, or the event's rendered message if it doesn't have one.
+- The kind is the `span_kind` property, if it has one.
+- The start and end times are the start and end of the event's extent.
+- The parent is the `span_parent` property, in the trace of the `trace_id` property.
+- The status is an error if the event has an `err` property, with its message.
+- Any other properties are attributes. Attributes that aren't strings, numbers, booleans, or arrays of them are written as JSON.
+
+The ids of spans in OpenTelemetry are generated by the tracer provider.
+To keep the `trace_id` and `span_id` properties, so the parents of spans can be found, configure the provider with the id generator returned by [`otel_id_generator`]:
+
+```js
+const provider = new WebTracerProvider({ idGenerator: emitWebOtelIdGenerator() });
+```
+
+Exceptions thrown by the tracer are caught and ignored.
+*/
+pub struct OtelTracerEmitter {
+    tracer: Local<JsValue>,
+}
+
+impl OtelTracerEmitter {
+    /**
+    Create a new emitter that turns completed spans into spans on `tracer`.
+    */
+    pub fn new(tracer: JsValue) -> Self {
+        OtelTracerEmitter {
+            tracer: Local(tracer),
+        }
+    }
+}
+
+impl emit::Emitter for OtelTracerEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        use emit::{
+            span::SpanKind,
+            well_known::{
+                KEY_ERR, KEY_EVT_KIND, KEY_SPAN_ID, KEY_SPAN_KIND, KEY_SPAN_NAME, KEY_SPAN_PARENT,
+                KEY_TRACE_ID,
+            },
+            Props as _,
+        };
+
+        let evt = evt.to_event();
+        let props = evt.props();
+
+        if props.pull::<emit::Kind, _>(KEY_EVT_KIND) != Some(emit::Kind::Span) {
+            return;
+        }
+
+        let (Some(extent), Some(trace_id), Some(span_id)) = (
+            evt.extent().and_then(|extent| extent.as_range()),
+            props.pull::<emit::TraceId, _>(KEY_TRACE_ID),
+            props.pull::<emit::SpanId, _>(KEY_SPAN_ID),
+        ) else {
+            return;
+        };
+
+        let mut buf = ser::Buf::new(false);
+
+        buf.object_begin();
+
+        buf.interned("name");
+        match props.get(KEY_SPAN_NAME) {
+            Some(name) => buf.display(name),
+            None => buf.msg(&evt),
+        }
+
+        // Kinds are numbered like `SpanKind` in `@opentelemetry/api`
+        buf.interned("kind");
+        match props.pull::<SpanKind, _>(KEY_SPAN_KIND) {
+            Some(SpanKind::Internal) => buf.number(0.0),
+            Some(SpanKind::Server) => buf.number(1.0),
+            Some(SpanKind::Client) => buf.number(2.0),
+            Some(SpanKind::Producer) => buf.number(3.0),
+            Some(SpanKind::Consumer) => buf.number(4.0),
+            _ => buf.null(),
+        }
+
+        buf.interned("traceId");
+        buf.display(trace_id);
+
+        buf.interned("spanId");
+        buf.display(span_id);
+
+        buf.interned("parentId");
+        match props.pull::<emit::SpanId, _>(KEY_SPAN_PARENT) {
+            Some(parent_id) => buf.display(parent_id),
+            None => buf.null(),
+        }
+
+        buf.interned("err");
+        match props.get(KEY_ERR) {
+            Some(err) => buf.display(err),
+            None => buf.null(),
+        }
+
+        buf.interned("attributes");
+        buf.object_begin();
+        let _ = props.for_each(|k, v| {
+            match k.get() {
+                KEY_EVT_KIND | KEY_TRACE_ID | KEY_SPAN_ID | KEY_SPAN_PARENT | KEY_SPAN_NAME
+                | KEY_SPAN_KIND | KEY_ERR => (),
+                k => {
+                    buf.interned(k);
+                    buf.value(v);
+                }
+            }
+
+            ControlFlow::Continue(())
+        });
+        buf.end();
+
+        buf.end();
+
+        // Exceptions can't unwind through `emit`, so they're discarded
+        let _ = shim::emit_web_otel_span(
+            &self.tracer,
+            &buf.decode(),
+            &hr_time(extent.start),
+            &hr_time(extent.end),
+        );
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        true
+    }
+}
+
+/**
+Get an id generator for an OpenTelemetry tracer provider that keeps the ids of spans from [`OtelTracerEmitter`]s.
+
+Spans that don't come from an [`OtelTracerEmitter`] are given random ids.
+
+This function is exported to JavaScript as `emitWebOtelIdGenerator`.
+*/
+#[wasm_bindgen(js_name = emitWebOtelIdGenerator)]
+pub fn otel_id_generator() -> JsValue {
+    shim::emit_web_otel_id_generator()
+}
+
+/**
+A timestamp as an `HrTime`, which is a pair of seconds and nanoseconds since the Unix epoch.
+
+This keeps the full precision of the timestamp, which a number of milliseconds can't.
+*/
+fn hr_time(ts: emit::Timestamp) -> Array {
+    let unix = ts.to_unix();
+
+    Array::of2(
+        &JsValue::from(unix.as_secs() as f64),
+        &JsValue::from(unix.subsec_nanos()),
+    )
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/otel.js")]
+    extern "C" {
+        pub fn emit_web_otel_id_generator() -> JsValue;
+        #[wasm_bindgen(catch)]
+        pub fn emit_web_otel_span(
+            tracer: &JsValue,
+            span: &JsValue,
+            start: &JsValue,
+            end: &JsValue,
+        ) -> Result<(), JsValue>;
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::Emitter as _;
+
+    use crate::test_util::get;
+
+    #[wasm_bindgen(inline_js = "
+    // A stand-in for `Tracer` from the OpenTelemetry SDK
+    export function stub_tracer(idGenerator) {
+        const tracer = { spans: [] };
+
+        tracer.startSpan = (name, options, context) => {
+            const parent = context.getValue(Symbol.for('OpenTelemetry Context Key SPAN'))?.spanContext();
+
+            const span = {
+                name,
+                kind: options.kind,
+                startTime: options.startTime,
+                attributes: options.attributes,
+                traceId: parent?.traceId ?? idGenerator.generateTraceId(),
+                spanId: idGenerator.generateSpanId(),
+                parentId: parent?.spanId,
+                status: undefined,
+                endTime: undefined,
+            };
+
+            tracer.spans.push(span);
+
+            return {
+                setStatus: (status) => {
+                    span.status = status;
+                },
+                end: (endTime) => {
+                    span.endTime = endTime;
+                },
+            };
+        };
+
+        return tracer;
+    }
+
+    export function start_js_span(tracer) {
+        tracer.startSpan('js', {}, { getValue: () => undefined });
+    }
+
+    export function throwing_tracer() {
+        return {
+            startSpan: () => {
+                throw new Error('failed to start span');
+            },
+        };
+    }
+    ")]
+    extern "C" {
+        fn stub_tracer(id_generator: &JsValue) -> JsValue;
+        fn start_js_span(tracer: &JsValue);
+        fn throwing_tracer() -> JsValue;
+    }
+
+    fn spans(tracer: &JsValue) -> Array {
+        Array::from(&get(tracer, "spans"))
+    }
+
+    fn ts(millis: u64) -> emit::Timestamp {
+        emit::Timestamp::from_unix(Duration::from_millis(millis)).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn export_spans() {
+        let tracer = stub_tracer(&otel_id_generator());
+        let emitter = otel_tracer(tracer.clone());
+
+        let trace_id = emit::TraceId::from_u128(1).unwrap();
+
+        emitter.emit(emit::evt!(
+            extent: ts(1200)..ts(1500),
+            "fetch {url} failed",
+            url: "/cart",
+            evt_kind: emit::Kind::Span,
+            span_name: "fetch",
+            span_kind: emit::span::SpanKind::Client,
+            trace_id,
+            span_id: emit::SpanId::from_u64(2).unwrap(),
+            span_parent: emit::SpanId::from_u64(1).unwrap(),
+            err: "timed out",
+        ));
+
+        emitter.emit(emit::evt!(
+            extent: ts(1000)..ts(2000),
+            "checkout",
+            evt_kind: emit::Kind::Span,
+            trace_id,
+            span_id: emit::SpanId::from_u64(1).unwrap(),
+        ));
+
+        let spans = spans(&tracer);
+        assert_eq!(2, spans.length());
+
+        let child = spans.get(0);
+        assert_eq!(JsValue::from("fetch"), get(&child, "name"));
+        assert_eq!(JsValue::from(2), get(&child, "kind"));
+        assert_eq!(
+            JsValue::from("00000000000000000000000000000001"),
+            get(&child, "traceId")
+        );
+        assert_eq!(JsValue::from("0000000000000002"), get(&child, "spanId"));
+        assert_eq!(JsValue::from("0000000000000001"), get(&child, "parentId"));
+
+        let start = Array::from(&get(&child, "startTime"));
+        assert_eq!(JsValue::from(1), start.get(0));
+        assert_eq!(JsValue::from(200_000_000), start.get(1));
+
+        let end = Array::from(&get(&child, "endTime"));
+        assert_eq!(JsValue::from(1), end.get(0));
+        assert_eq!(JsValue::from(500_000_000), end.get(1));
+
+        let status = get(&child, "status");
+        assert_eq!(JsValue::from(2), get(&status, "code"));
+        assert_eq!(JsValue::from("timed out"), get(&status, "message"));
+
+        let attributes = get(&child, "attributes");
+        assert_eq!(JsValue::from("/cart"), get(&attributes, "url"));
+        assert_eq!(
+            1,
+            js_sys::Object::keys(attributes.unchecked_ref::<js_sys::Object>()).length()
+        );
+
+        let root = spans.get(1);
+        assert_eq!(JsValue::from("checkout"), get(&root, "name"));
+        assert!(get(&root, "kind").is_undefined());
+        assert_eq!(
+            JsValue::from("00000000000000000000000000000001"),
+            get(&root, "traceId")
+        );
+        assert_eq!(JsValue::from("0000000000000001"), get(&root, "spanId"));
+        assert!(get(&root, "parentId").is_undefined());
+        assert!(get(&root, "status").is_undefined());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn ignore_events_that_arent_spans() {
+        let tracer = stub_tracer(&otel_id_generator());
+        let emitter = otel_tracer(tracer.clone());
+
+        let trace_id = emit::TraceId::from_u128(1).unwrap();
+        let span_id = emit::SpanId::from_u64(1).unwrap();
+
+        emitter.emit(emit::evt!(
+            extent: ts(1000)..ts(2000),
+            "not a span",
+            trace_id,
+            span_id,
+        ));
+
+        emitter.emit(emit::evt!(
+            extent: ts(1000),
+            "not completed",
+            evt_kind: emit::Kind::Span,
+            trace_id,
+            span_id,
+        ));
+
+        assert_eq!(0, spans(&tracer).length());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn generate_random_ids() {
+        let tracer = stub_tracer(&otel_id_generator());
+
+        // Spans started outside of the emitter get random ids
+        start_js_span(&tracer);
+
+        let span = spans(&tracer).get(0);
+
+        assert_eq!(32, get(&span, "traceId").as_string().unwrap().len());
+        assert_eq!(16, get(&span, "spanId").as_string().unwrap().len());
+        assert_ne!(
+            JsValue::from("00000000000000000000000000000000"),
+            get(&span, "traceId")
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn ignore_exceptions() {
+        let emitter = otel_tracer(throwing_tracer());
+
+        emitter.emit(emit::evt!(
+            extent: ts(1000)..ts(2000),
+            "span",
+            evt_kind: emit::Kind::Span,
+            trace_id: emit::TraceId::from_u128(1).unwrap(),
+            span_id: emit::SpanId::from_u64(1).unwrap(),
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+// Renders events into a floating panel for the `OverlayEmitter` in `emit_web`.
+//
+// The panel is created when the first event is emitted. It can be collapsed
+// into a small badge, which is tapped to expand it again, or toggled with
+// Ctrl+Shift+L. Props are only formatted once an event is expanded or searched.
+
+const COLOURS = {
+    debug: "#9e9e9e",
+    info: "#64b5f6",
+    warn: "#ffb74d",
+    error: "#e57373",
+    log: "#e0e0e0",
+};
+
+let overlay = null;
+
+// Convert values that `JSON.stringify` can't represent
+function replacer(key, value) {
+    if (typeof value === "bigint") {
+        return value.toString();
+    }
+
+    if (value instanceof Map) {
+        return Array.from(value.entries());
+    }
+
+    if (value instanceof Uint8Array) {
+        return Array.from(value);
+    }
+
+    return value;
+}
+
+function element(tag, css) {
+    const el = document.createElement(tag);
+    el.style.cssText = css;
+
+    return el;
+}
+
+function create(collapsed) {
+    const root = element("div", "position:fixed;right:8px;bottom:8px;z-index:2147483647;font:12px/1.4 monospace;color:#eee;");
+    root.setAttribute("data-emit-web-overlay", "");
+
+    const badge = element("button", "font:inherit;color:inherit;background:rgba(20,20,20,0.92);border:0;border-radius:4px;padding:4px 8px;");
+
+    const panel = element("div", "flex-direction:column;width:min(640px,calc(100vw - 16px));max-height:40vh;background:rgba(20,20,20,0.92);border-radius:4px;");
+
+    const header = element("div", "display:flex;gap:4px;padding:4px;");
+    const search = element("input", "flex:1;font:inherit;");
+    search.placeholder = "Search";
+    const collapse = element("button", "font:inherit;");
+    collapse.textContent = "−";
+    header.append(search, collapse);
+
+    const list = element("div", "overflow:auto;");
+    panel.append(header, list);
+
+    root.append(badge, panel);
+    document.body.append(root);
+
+    const o = { doc: document, badge, panel, search, list, entries: [], errors: 0, collapsed };
+
+    badge.addEventListener("click", () => toggle(o));
+    collapse.addEventListener("click", () => toggle(o));
+    search.addEventListener("input", () => o.entries.forEach((entry) => filter(o, entry)));
+    document.addEventListener("keydown", (e) => {
+        if (e.ctrlKey && e.shiftKey && (e.key === "L" || e.key === "l")) {
+            e.preventDefault?.();
+            toggle(o);
+        }
+    });
+
+    return o;
+}
+
+function toggle(o) {
+    o.collapsed = !o.collapsed;
+    render(o);
+}
+
+function render(o) {
+    o.badge.style.display = o.collapsed ? "" : "none";
+    o.panel.style.display = o.collapsed ? "none" : "flex";
+    o.badge.textContent = `emit ${o.entries.length}` + (o.errors > 0 ? ` ⚠ ${o.errors}` : "");
+}
+
+function propsText(entry) {
+    entry.props ??= JSON.stringify(entry.evt.props, replacer, 2);
+
+    return entry.props;
+}
+
+function filter(o, entry) {
+    const query = o.search.value.trim().toLowerCase();
+    const matches = query === ""
+        || entry.evt.msg.toLowerCase().includes(query)
+        || propsText(entry).toLowerCase().includes(query);
+
+    entry.el.style.display = matches ? "" : "none";
+}
+
+function push(o, evt, maxEvents) {
+    const lvl = evt.lvl ?? "log";
+    const colour = COLOURS[lvl] ?? COLOURS.log;
+
+    const el = element("details", `border-left:3px solid ${colour};padding:2px 4px;`);
+    el.setAttribute("data-lvl", lvl);
+
+    const summary = element("summary", `color:${colour};`);
+    const ts = evt.extent?.timestamp;
+    summary.textContent = `${ts ? ts.toISOString().slice(11, 23) + " " : ""}${lvl.toUpperCase()} ${evt.msg}`;
+    el.append(summary);
+
+    const entry = { el, lvl, evt, props: null, expanded: false };
+
+    el.addEventListener("toggle", () => {
+        if (el.open && !entry.expanded) {
+            entry.expanded = true;
+
+            const props = element("pre", "margin:0;white-space:pre-wrap;");
+            props.textContent = propsText(entry);
+            el.append(props);
+        }
+    });
+
+    o.list.append(el);
+    o.entries.push(entry);
+    if (lvl === "error") {
+        o.errors++;
+    }
+
+    while (o.entries.length > maxEvents) {
+        const oldest = o.entries.shift();
+        oldest.el.remove();
+
+        if (oldest.lvl === "error") {
+            o.errors--;
+        }
+    }
+
+    filter(o, entry);
+    render(o);
+
+    // Keep the latest event in view
+    o.list.scrollTop = o.list.scrollHeight;
+}
+
+export function emit_web_overlay_push(evt, maxEvents, collapsed) {
+    if (typeof document === "undefined" || !document.body) {
+        return;
+    }
+
+    if (overlay === null || overlay.doc !== document) {
+        overlay = create(collapsed);
+    }
+
+    push(overlay, evt, maxEvents);
+}
@@ -0,0 +1,322 @@
+/*!
+Show recent events in a panel on the page itself.
+*/
+
+use core::{cmp, time::Duration};
+
+use crate::{level, ser};
+
+/**
+An emitter that shows recent events in a floating panel on the page.
+*/
+pub const fn overlay() -> OverlayEmitter {
+    OverlayEmitter::new()
+}
+
+/**
+An emitter that shows recent events in a floating, collapsible panel on the page.
+
+On devices without accessible developer tools, like mobile Safari and embedded webviews, the console that [`crate::ConsoleEmitter`] writes to can't be seen.
+This emitter renders events into the page itself, so testers can see them.
+
+Each event shows its timestamp, level, and message, colored by its level.
+Events can be expanded to show their props, and searched by their message or props.
+The panel can be collapsed into a badge that shows the number of events and errors.
+Tapping the badge, or pressing Ctrl+Shift+L, toggles the panel.
+
+The panel is created when the first event is emitted.
+This emitter does nothing outside of a page.
+*/
+pub struct OverlayEmitter {
+    max_events: usize,
+    collapsed: bool,
+}
+
+impl OverlayEmitter {
+    /**
+    Create a new instance of the overlay emitter.
+    */
+    pub const fn new() -> Self {
+        OverlayEmitter {
+            max_events: 200,
+            collapsed: true,
+        }
+    }
+
+    /**
+    Set the maximum number of events to show.
+
+    The oldest events are removed to make room for new ones.
+    The default maximum is 200 events.
+    */
+    pub const fn max_events(mut self, max_events: usize) -> Self {
+        self.max_events = max_events;
+        self
+    }
+
+    /**
+    Whether the panel starts collapsed into a badge.
+
+    The default is `true`.
+    */
+    pub const fn collapsed(mut self, collapsed: bool) -> Self {
+        self.collapsed = collapsed;
+        self
+    }
+}
+
+impl Default for OverlayEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl emit::Emitter for OverlayEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let mut buf = ser::Buf::new(false);
+
+        buf.object_begin();
+
+        buf.interned("msg");
+        buf.msg(&evt);
+
+        buf.interned("lvl");
+        match level(&evt) {
+            Some(lvl) => buf.interned(lvl),
+            None => buf.null(),
+        }
+
+        buf.interned("extent");
+        buf.extent(evt.extent());
+
+        buf.interned("props");
+        buf.props(evt.props());
+
+        buf.end();
+
+        shim::emit_web_overlay_push(&buf.decode(), cmp::max(1, self.max_events), self.collapsed);
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        true
+    }
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/overlay.js")]
+    extern "C" {
+        pub fn emit_web_overlay_push(evt: &JsValue, max_events: usize, collapsed: bool);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::Emitter as _;
+    use js_sys::Array;
+    use wasm_bindgen::prelude::*;
+
+    use crate::test_util::{get, Globals};
+
+    #[wasm_bindgen(inline_js = "
+    class Element {
+        constructor(tag) {
+            this.tagName = tag.toUpperCase();
+            this.children = [];
+            this.parent = null;
+            this.style = {};
+            this.attributes = {};
+            this.listeners = {};
+            this.textContent = '';
+            this.value = '';
+            this.open = false;
+        }
+
+        append(...children) {
+            for (const child of children) {
+                child.parent = this;
+                this.children.push(child);
+            }
+        }
+
+        remove() {
+            this.parent.children.splice(this.parent.children.indexOf(this), 1);
+            this.parent = null;
+        }
+
+        setAttribute(name, value) {
+            this.attributes[name] = value;
+        }
+
+        addEventListener(type, f) {
+            (this.listeners[type] ??= []).push(f);
+        }
+
+        dispatch(type, e = {}) {
+            (this.listeners[type] ?? []).forEach((f) => f(e));
+        }
+    }
+
+    function parts() {
+        const [badge, panel] = document.body.children[0].children;
+        const [header, list] = panel.children;
+
+        return { badge, panel, search: header.children[0], list };
+    }
+
+    export function stub_document() {
+        const doc = new Element('#document');
+        doc.body = new Element('body');
+        doc.createElement = (tag) => new Element(tag);
+
+        globalThis.document = doc;
+    }
+
+    export function entries() {
+        return parts().list.children.map((el) => ({
+            lvl: el.attributes['data-lvl'],
+            summary: el.children[0].textContent,
+            shown: el.style.display !== 'none',
+            props: el.children[1]?.textContent,
+        }));
+    }
+
+    export function expand(i) {
+        const el = parts().list.children[i];
+
+        el.open = true;
+        el.dispatch('toggle');
+    }
+
+    export function search(query) {
+        const search = parts().search;
+
+        search.value = query;
+        search.dispatch('input');
+    }
+
+    export function press_shortcut() {
+        document.dispatch('keydown', { key: 'L', ctrlKey: true, shiftKey: true });
+    }
+
+    export function is_collapsed() {
+        return parts().panel.style.display === 'none';
+    }
+
+    export function badge() {
+        return parts().badge.textContent;
+    }
+    ")]
+    extern "C" {
+        #[wasm_bindgen(js_name = stub_document)]
+        fn stub_document_js();
+        fn entries() -> Array;
+        fn expand(i: u32);
+        fn search(query: &str);
+        fn press_shortcut();
+        fn is_collapsed() -> bool;
+        fn badge() -> String;
+    }
+
+    fn stub_document() -> Globals {
+        let globals = Globals::save(&["document"]);
+        stub_document_js();
+
+        globals
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn show_events() {
+        let _globals = stub_document();
+
+        let emitter = overlay();
+
+        emitter.emit(emit::evt!(
+            extent: emit::Timestamp::from_unix(Duration::from_secs(1)).unwrap(),
+            "event {n}",
+            n: 1,
+        ));
+        emitter.emit(emit::error_evt!("failed {n}", n: 2));
+
+        let entries = entries();
+        assert_eq!(2, entries.length());
+
+        let evt = entries.get(0);
+        assert_eq!(JsValue::from("log"), get(&evt, "lvl"));
+        assert_eq!(
+            JsValue::from("00:00:01.000 LOG event 1"),
+            get(&evt, "summary")
+        );
+        assert!(get(&evt, "props").is_undefined());
+
+        assert_eq!(JsValue::from("error"), get(&entries.get(1), "lvl"));
+        assert_eq!("emit 2 \u{26a0} 1", badge());
+
+        expand(0);
+
+        let props = get(&self::entries().get(0), "props").as_string().unwrap();
+        assert!(props.contains(r#""n": 1"#));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn keep_max_events() {
+        let _globals = stub_document();
+
+        let emitter = overlay().max_events(2);
+
+        for n in 1..=3 {
+            emitter.emit(emit::evt!("event {n}", n));
+        }
+
+        let summaries = entries()
+            .iter()
+            .map(|evt| get(&evt, "summary").as_string().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["LOG event 2", "LOG event 3"], summaries);
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn search_and_toggle() {
+        let _globals = stub_document();
+
+        let emitter = overlay().collapsed(false);
+
+        emitter.emit(emit::evt!("checkout {step}", step: "payment"));
+        emitter.emit(emit::evt!("navigation", route: "/cart"));
+
+        assert!(!is_collapsed());
+
+        let shown = || {
+            entries()
+                .iter()
+                .map(|evt| get(&evt, "shown").as_bool().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Events are searched by their message and props
+        search("PAYMENT");
+        assert_eq!(vec![true, false], shown());
+
+        search("/cart");
+        assert_eq!(vec![false, true], shown());
+
+        search("");
+        assert_eq!(vec![true, true], shown());
+
+        press_shortcut();
+        assert!(is_collapsed());
+
+        press_shortcut();
+        assert!(!is_collapsed());
+    }
+}
@@ -0,0 +1,36 @@
+// Reads trace context and navigation timing for the `PageLoadSpan` in `emit_web`.
+//
+// Server-rendered pages can carry the trace context of the request that
+// rendered them, either in a `<meta name="traceparent">` tag or in a global
+// variable set by an inline script.
+
+export function emit_web_page_load_traceparent(global) {
+    if (global !== undefined && typeof globalThis[global] === "string") {
+        return globalThis[global];
+    }
+
+    const meta = globalThis.document?.querySelector?.('meta[name="traceparent"]');
+
+    return typeof meta?.content === "string" ? meta.content : undefined;
+}
+
+export function emit_web_page_load_on_load(f) {
+    if (typeof performance?.getEntriesByType !== "function") {
+        return;
+    }
+
+    // `loadEventEnd` is only set once all `load` handlers have run
+    const read = () => setTimeout(() => {
+        const [timing] = performance.getEntriesByType("navigation");
+
+        if (timing) {
+            f(timing.toJSON());
+        }
+    });
+
+    if (globalThis.document?.readyState === "complete") {
+        read();
+    } else {
+        globalThis.addEventListener?.("load", read, { once: true });
+    }
+}
@@ -0,0 +1,658 @@
+/*!
+A root span for loading the page.
+*/
+
+use alloc::string::String;
+
+use emit::{Filter as _, Props as _};
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+use crate::{crypto_rng, performance_now, performance_timestamp, propagate, root};
+
+type Rt = emit::runtime::AmbientRuntime<'static>;
+
+/**
+A [`PageLoadBuilder`] for starting the root span of the page in `rt`.
+*/
+pub const fn page_load(rt: &'static Rt) -> PageLoadBuilder {
+    PageLoadBuilder::new(rt)
+}
+
+/**
+Emit a `page_load` span, and child spans for each phase of loading the page, through `emitter` once the page has loaded.
+
+The spans are built from the page's [`PerformanceNavigationTiming`](https://developer.mozilla.org/en-US/docs/Web/API/PerformanceNavigationTiming), so they're the same for every session regardless of when this function is called.
+They're emitted from the `emit_web::page_load` module.
+The root span is named `page_load` and extends from [`performance.timeOrigin`](https://developer.mozilla.org/en-US/docs/Web/API/Performance/timeOrigin) to the end of the `load` event, with the following props:
+
+- `navigation_type`: How the page was navigated to, like `navigate` or `reload`.
+
+It has a child span for each phase that happened while loading the page:
+
+- `redirect`: Following redirects.
+- `dns lookup`: Looking up the domain name.
+- `connect`: Establishing a connection, including TLS.
+- `request`: Sending the request, until the first byte of the response.
+- `response`: Receiving the response.
+- `dom interactive`: Parsing the document, until it became interactive.
+- `DOMContentLoaded`: Running the `DOMContentLoaded` event handlers.
+
+If the page carries a `<meta name="traceparent">` tag then the root span is a child of the server's span.
+Use [`PageLoadSpan::complete_on_load`] instead to make the root span the root of the rest of the page.
+In environments without the Navigation Timing API this does nothing.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[wasm_bindgen]
+pub fn setup() {
+    emit_web::record_page_load(emit_web::console());
+}
+```
+*/
+pub fn record_page_load(emitter: impl emit::Emitter + 'static) {
+    let ctxt = server_parent(None).new_child(crypto_rng());
+
+    on_load(move |timing| timing.emit(emitter, ctxt));
+}
+
+/**
+A builder for a [`PageLoadSpan`].
+*/
+pub struct PageLoadBuilder {
+    rt: &'static Rt,
+    global: Option<String>,
+}
+
+impl PageLoadBuilder {
+    /**
+    Create a new builder for the page-load span in `rt`.
+
+    The span is also emitted through `rt`.
+    */
+    pub const fn new(rt: &'static Rt) -> Self {
+        PageLoadBuilder { rt, global: None }
+    }
+
+    /**
+    Read the server's `traceparent` from a global variable named `global`.
+
+    If the variable isn't set to a string then the `<meta name="traceparent">` tag is used instead.
+    */
+    pub fn traceparent_global(mut self, global: impl Into<String>) -> Self {
+        self.global = Some(global.into());
+        self
+    }
+
+    /**
+    Start the page-load span.
+    */
+    pub fn start(self) -> PageLoadSpan {
+        PageLoadSpan::new(self)
+    }
+}
+
+/**
+The root span of a page, starting when the browser began navigating to it.
+
+Pages rendered by a server can continue the trace of the request that rendered them by including its [W3C `traceparent`](https://www.w3.org/TR/trace-context/#traceparent-header) in a tag like:
+
+```html
+<meta name="traceparent" content="00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01">
+```
+
+The page-load span is then a child of the server's span, sharing its trace id.
+The `traceparent` can also be read from a global variable with [`PageLoadBuilder::traceparent_global`].
+If there's no valid `traceparent` then the page-load span starts a new trace.
+
+When it's started, the page-load span becomes the root span of the page, returned by [`crate::root_ctxt`], until a [`crate::RouteTracer`] replaces it.
+Spans from the tracers in this library that start while no other span is current are its children, so the whole page shares one trace.
+Start it as early as possible, before any other spans are created.
+If the runtime's filter doesn't match the span then it neither becomes the root span nor is emitted.
+
+The span is emitted from the `emit_web::page_load` module, named `page_load`.
+Its extent starts at [`performance.timeOrigin`](https://developer.mozilla.org/en-US/docs/Web/API/Performance/timeOrigin).
+Call [`PageLoadSpan::complete_on_load`] to end it when the page has loaded, with child spans for each phase of loading it, as described in [`record_page_load`].
+Otherwise, it ends when it's completed by calling [`PageLoadSpan::complete`] or dropping it.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[wasm_bindgen]
+pub fn setup() {
+    let _ = emit::setup()
+        .emit_to(emit_web::console())
+        .try_init();
+
+    emit_web::page_load(emit::runtime::shared())
+        .start()
+        .complete_on_load();
+}
+```
+*/
+pub struct PageLoadSpan {
+    rt: Option<&'static Rt>,
+    ctxt: emit::SpanCtxt,
+}
+
+impl PageLoadSpan {
+    fn new(builder: PageLoadBuilder) -> Self {
+        let rt = builder.rt;
+
+        let ctxt = server_parent(builder.global.as_deref()).new_child(rt.rng());
+
+        // Check the filter with the span before it has an extent, like `emit`'s own spans
+        if !rt.filter().matches(emit::Span::new(
+            emit::path!("emit_web::page_load"),
+            "page_load",
+            emit::Empty,
+            ctxt,
+        )) {
+            return PageLoadSpan { rt: None, ctxt };
+        }
+
+        root::replace(ctxt);
+
+        PageLoadSpan { rt: Some(rt), ctxt }
+    }
+
+    /**
+    The trace and span ids of the page-load span.
+    */
+    pub fn ctxt(&self) -> &emit::SpanCtxt {
+        &self.ctxt
+    }
+
+    /**
+    Complete the span, ending it now.
+    */
+    pub fn complete(self) {
+        drop(self);
+    }
+
+    /**
+    Complete the span once the page has loaded, ending it when the `load` event does.
+
+    Child spans are emitted for each phase of loading the page, as described in [`record_page_load`].
+    If the span didn't match the runtime's filter when it was started then nothing is emitted.
+    In environments without the Navigation Timing API the span is never completed.
+    */
+    pub fn complete_on_load(mut self) {
+        let Some(rt) = self.rt.take() else {
+            return;
+        };
+
+        let ctxt = self.ctxt;
+
+        on_load(move |timing| timing.emit(emit::emitter::from_fn(|evt| rt.emit(evt)), ctxt));
+    }
+}
+
+impl Drop for PageLoadSpan {
+    fn drop(&mut self) {
+        let Some(rt) = self.rt else {
+            return;
+        };
+
+        let extent = timestamp(0.0)
+            .zip(emit::Timestamp::from_unix(performance_now()))
+            .map(|(start, end)| start..end);
+
+        rt.emit(emit::Span::new(
+            emit::path!("emit_web::page_load"),
+            "page_load",
+            extent,
+            self.ctxt,
+        ));
+    }
+}
+
+/**
+The span context of the server that rendered the page, or an empty context if there isn't one.
+*/
+fn server_parent(global: Option<&str>) -> emit::SpanCtxt {
+    shim::emit_web_page_load_traceparent(global)
+        .and_then(|traceparent| propagate::parse_traceparent(&traceparent))
+        .map(|(trace_id, span_id)| emit::SpanCtxt::new(Some(trace_id), None, Some(span_id)))
+        .unwrap_or_else(emit::SpanCtxt::empty)
+}
+
+/**
+Call `f` with the page's navigation timing once the `load` event has ended.
+*/
+fn on_load(f: impl FnOnce(NavigationTiming) + 'static) {
+    shim::emit_web_page_load_on_load(&Closure::once_into_js(move |timing: JsValue| {
+        f(NavigationTiming::from_js(&timing))
+    }));
+}
+
+fn timestamp(millis: f64) -> Option<emit::Timestamp> {
+    emit::Timestamp::from_unix(performance_timestamp(millis))
+}
+
+/**
+The timings of a [`PerformanceNavigationTiming`](https://developer.mozilla.org/en-US/docs/Web/API/PerformanceNavigationTiming), in milliseconds since `performance.timeOrigin`.
+
+Phases that didn't happen, like redirects for pages that weren't redirected, have timings of `0`.
+*/
+struct NavigationTiming {
+    navigation_type: Option<String>,
+    redirect_start: f64,
+    redirect_end: f64,
+    domain_lookup_start: f64,
+    domain_lookup_end: f64,
+    connect_start: f64,
+    connect_end: f64,
+    request_start: f64,
+    response_start: f64,
+    response_end: f64,
+    dom_interactive: f64,
+    dom_content_loaded_event_start: f64,
+    dom_content_loaded_event_end: f64,
+    load_event_end: f64,
+}
+
+impl NavigationTiming {
+    fn from_js(timing: &JsValue) -> Self {
+        let get = |field: &str| {
+            Reflect::get(timing, &JsValue::from(field))
+                .ok()
+                .and_then(|value| value.as_f64())
+                .unwrap_or(0.0)
+        };
+
+        NavigationTiming {
+            navigation_type: Reflect::get(timing, &JsValue::from("type"))
+                .ok()
+                .and_then(|value| value.as_string()),
+            redirect_start: get("redirectStart"),
+            redirect_end: get("redirectEnd"),
+            domain_lookup_start: get("domainLookupStart"),
+            domain_lookup_end: get("domainLookupEnd"),
+            connect_start: get("connectStart"),
+            connect_end: get("connectEnd"),
+            request_start: get("requestStart"),
+            response_start: get("responseStart"),
+            response_end: get("responseEnd"),
+            dom_interactive: get("domInteractive"),
+            dom_content_loaded_event_start: get("domContentLoadedEventStart"),
+            dom_content_loaded_event_end: get("domContentLoadedEventEnd"),
+            load_event_end: get("loadEventEnd"),
+        }
+    }
+
+    fn phases(&self) -> [(&'static str, f64, f64); 7] {
+        [
+            ("redirect", self.redirect_start, self.redirect_end),
+            (
+                "dns lookup",
+                self.domain_lookup_start,
+                self.domain_lookup_end,
+            ),
+            ("connect", self.connect_start, self.connect_end),
+            ("request", self.request_start, self.response_start),
+            ("response", self.response_start, self.response_end),
+            ("dom interactive", self.response_end, self.dom_interactive),
+            (
+                "DOMContentLoaded",
+                self.dom_content_loaded_event_start,
+                self.dom_content_loaded_event_end,
+            ),
+        ]
+    }
+
+    /**
+    Emit the `page_load` span with the ids in `ctxt`, and a child span for each phase that happened.
+    */
+    fn emit(&self, emitter: impl emit::Emitter, ctxt: emit::SpanCtxt) {
+        let mdl = emit::path!("emit_web::page_load");
+        let extent = |start: f64, end: f64| {
+            timestamp(start)
+                .zip(timestamp(end))
+                .map(|(start, end)| start..end)
+        };
+
+        emitter.emit(emit::Span::new(
+            mdl.by_ref(),
+            "page_load",
+            extent(0.0, self.load_event_end),
+            ctxt.and_props(
+                self.navigation_type
+                    .as_deref()
+                    .map(|navigation_type| ("navigation_type", navigation_type)),
+            ),
+        ));
+
+        let rng = crypto_rng();
+
+        for (name, start, end) in self.phases() {
+            if end <= 0.0 || end < start {
+                continue;
+            }
+
+            emitter.emit(emit::Span::new(
+                mdl.by_ref(),
+                name,
+                extent(start, end),
+                ctxt.new_child(&rng),
+            ));
+        }
+    }
+}
+
+mod shim {
+    use alloc::string::String;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/page_load.js")]
+    extern "C" {
+        pub fn emit_web_page_load_traceparent(global: Option<&str>) -> Option<String>;
+        pub fn emit_web_page_load_on_load(f: &JsValue);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use std::sync::Mutex;
+
+    use crate::test_util::{tick, Globals, Root};
+
+    #[wasm_bindgen(inline_js = "
+    export function set_global(name, value) {
+        globalThis[name] = value;
+    }
+
+    export function stub_loaded_page() {
+        const performance = globalThis.performance;
+
+        globalThis.document = { readyState: 'complete' };
+        globalThis.performance = {
+            timeOrigin: performance.timeOrigin,
+            now: () => performance.now(),
+            getEntriesByType: (type) => type !== 'navigation' ? [] : [{
+                toJSON: () => ({
+                    type: 'reload',
+                    domainLookupStart: 5,
+                    domainLookupEnd: 15,
+                    requestStart: 20,
+                    responseStart: 60,
+                    responseEnd: 80,
+                    loadEventEnd: 200,
+                }),
+            }],
+        };
+    }
+    ")]
+    extern "C" {
+        #[wasm_bindgen(js_name = set_global)]
+        fn set_global_js(name: &str, value: &str);
+        #[wasm_bindgen(js_name = stub_loaded_page)]
+        fn stub_loaded_page_js();
+    }
+
+    fn stub_loaded_page() -> Globals {
+        let globals = Globals::save(&["document", "performance"]);
+        stub_loaded_page_js();
+
+        globals
+    }
+
+    fn set_global(name: &str, value: &str) -> Globals {
+        let globals = Globals::save(&[name]);
+        set_global_js(name, value);
+
+        globals
+    }
+
+    type Emitted = Mutex<Vec<(String, bool, Option<emit::SpanCtxt>)>>;
+
+    fn collect(emitted: &'static Emitted) -> impl emit::Emitter + Send + Sync + 'static {
+        emit::emitter::from_fn(move |evt| {
+            let props = evt.props();
+
+            emitted.lock().unwrap().push((
+                props.pull("span_name").unwrap(),
+                evt.extent()
+                    .map(|extent| extent.is_range())
+                    .unwrap_or(false),
+                Some(emit::SpanCtxt::new(
+                    props.pull("trace_id"),
+                    props.pull("span_parent"),
+                    props.pull("span_id"),
+                )),
+            ))
+        })
+    }
+
+    fn rt(slot: &'static emit::runtime::AmbientSlot, emitted: &'static Emitted) -> &'static Rt {
+        rt_with_filter(slot, emitted, emit::filter::from_fn(|_| true))
+    }
+
+    fn rt_with_filter(
+        slot: &'static emit::runtime::AmbientSlot,
+        emitted: &'static Emitted,
+        filter: impl emit::Filter + Send + Sync + 'static,
+    ) -> &'static Rt {
+        let _ = slot.init(
+            emit::runtime::Runtime::new()
+                .with_filter(filter)
+                .with_emitter(collect(emitted))
+                .with_ctxt(emit::platform::thread_local_ctxt::ThreadLocalCtxt::new())
+                .with_rng(crate::crypto_rng()),
+        );
+
+        slot.get()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn continue_server_trace() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let rt = rt(&SLOT, &EMITTED);
+
+        let _globals = set_global(
+            "serverTraceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        );
+        let _root = Root::save();
+
+        let page_load = page_load(rt)
+            .traceparent_global("serverTraceparent")
+            .start();
+
+        let ctxt = *page_load.ctxt();
+
+        assert_eq!(
+            Some(&emit::TraceId::from_u128(0x4bf92f3577b34da6a3ce929d0e0e4736).unwrap()),
+            ctxt.trace_id()
+        );
+        assert_eq!(
+            Some(&emit::SpanId::from_u64(0x00f067aa0ba902b7).unwrap()),
+            ctxt.span_parent()
+        );
+
+        // The page-load span is the root of later spans, without being left current
+        assert!(emit::SpanCtxt::current(rt.ctxt()).trace_id().is_none());
+
+        let child = crate::root_ctxt().new_child(rt.rng());
+        assert_eq!(ctxt.trace_id(), child.trace_id());
+        assert_eq!(ctxt.span_id(), child.span_parent());
+
+        page_load.complete();
+
+        assert_eq!(
+            vec![("page_load".into(), true, Some(ctxt))],
+            *EMITTED.lock().unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn start_new_trace() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let rt = rt(&SLOT, &EMITTED);
+
+        let _globals = set_global("invalidTraceparent", "not a traceparent");
+        let _root = Root::save();
+
+        let page_load = page_load(rt)
+            .traceparent_global("invalidTraceparent")
+            .start();
+
+        assert!(page_load.ctxt().trace_id().is_some());
+        assert!(page_load.ctxt().span_parent().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn skip_filtered_span() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let rt = rt_with_filter(
+            &SLOT,
+            &EMITTED,
+            emit::filter::from_fn(|evt| evt.mdl() != "emit_web::page_load"),
+        );
+
+        let _root = Root::save();
+        let before = crate::root_ctxt();
+
+        let page_load = page_load(rt).start();
+
+        // The span doesn't become the root span
+        assert_eq!(before, crate::root_ctxt());
+
+        page_load.complete();
+
+        assert!(EMITTED.lock().unwrap().is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    async fn record_once_loaded() {
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let _globals = stub_loaded_page();
+
+        record_page_load(collect(&EMITTED));
+        tick().await;
+
+        let emitted = core::mem::take(&mut *EMITTED.lock().unwrap());
+        let names = emitted
+            .iter()
+            .map(|(name, _, _)| &**name)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec!["page_load", "dns lookup", "request", "response"],
+            names
+        );
+
+        // The root span starts a new trace, and the phases are its children
+        let root = emitted[0].2.unwrap();
+        assert!(root.trace_id().is_some());
+        assert!(root.span_parent().is_none());
+
+        for (_, is_range, ctxt) in &emitted {
+            assert!(is_range);
+            assert_eq!(root.trace_id(), ctxt.unwrap().trace_id());
+        }
+
+        for (_, _, ctxt) in &emitted[1..] {
+            assert_eq!(root.span_id(), ctxt.unwrap().span_parent());
+        }
+    }
+
+    #[wasm_bindgen_test]
+    async fn complete_on_load() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let rt = rt(&SLOT, &EMITTED);
+
+        let _globals = stub_loaded_page();
+        let _root = Root::save();
+
+        let page_load = page_load(rt).start();
+        let ctxt = *page_load.ctxt();
+
+        page_load.complete_on_load();
+
+        // The span isn't emitted until the page has loaded
+        assert!(EMITTED.lock().unwrap().is_empty());
+
+        tick().await;
+
+        let emitted = EMITTED.lock().unwrap();
+
+        assert_eq!(("page_load".into(), true, Some(ctxt)), emitted[0]);
+        assert_eq!(4, emitted.len());
+        assert!(emitted[1..]
+            .iter()
+            .all(|(_, _, child)| child.unwrap().span_parent() == ctxt.span_id()));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn emit_navigation_timing() {
+        let timing = NavigationTiming::from_js(
+            &js_sys::JSON::parse(
+                r#"{
+                    "type": "navigate",
+                    "redirectStart": 0,
+                    "redirectEnd": 0,
+                    "domainLookupStart": 5,
+                    "domainLookupEnd": 15,
+                    "connectStart": 15,
+                    "connectEnd": 40,
+                    "requestStart": 41,
+                    "responseStart": 90,
+                    "responseEnd": 120,
+                    "domInteractive": 300,
+                    "domContentLoadedEventStart": 310,
+                    "domContentLoadedEventEnd": 320,
+                    "loadEventEnd": 500
+                }"#,
+            )
+            .unwrap(),
+        );
+
+        let emitted = Mutex::new(Vec::new());
+
+        let root = emit::SpanCtxt::new_root(crate::crypto_rng());
+        timing.emit(
+            emit::emitter::from_fn(|evt| {
+                let extent = evt.extent().unwrap().as_range().unwrap();
+
+                emitted.lock().unwrap().push((
+                    evt.props().pull::<String, _>("span_name").unwrap(),
+                    (extent.end.duration_since(extent.start).unwrap()).as_millis(),
+                    evt.props().pull::<emit::SpanId, _>("span_parent"),
+                    evt.props().pull::<String, _>("navigation_type"),
+                ))
+            }),
+            root,
+        );
+
+        let parent = root.span_id().copied();
+
+        assert_eq!(
+            vec![
+                ("page_load".into(), 500, None, Some("navigate".into())),
+                ("dns lookup".into(), 10, parent, None),
+                ("connect".into(), 25, parent, None),
+                ("request".into(), 49, parent, None),
+                ("response".into(), 30, parent, None),
+                ("dom interactive".into(), 180, parent, None),
+                ("DOMContentLoaded".into(), 10, parent, None),
+            ],
+            emitted.into_inner().unwrap()
+        );
+    }
+}
@@ -0,0 +1,233 @@
+/*!
+Propagate trace context between threads and services.
+
+Trace context is carried as a [W3C `traceparent`](https://www.w3.org/TR/trace-context/#traceparent-header).
+*/
+
+use alloc::{format, string::String};
+
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+const TRACEPARENT: &str = "traceparent";
+
+/**
+Attach the trace context of `ctxt` to `message`, so a worker can continue the trace when it handles it.
+
+The trace and span ids of the current span in `ctxt` are set as a `traceparent` field on `message` before it's passed to [`postMessage`](https://developer.mozilla.org/en-US/docs/Web/API/Worker/postMessage).
+In the worker, [`restore_trace_context`] makes that span the parent of any spans created while handling the message.
+
+Returns `false` without changing `message` if `ctxt` has no current span, or if `message` isn't an object.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[emit::span("send job")]
+fn send(job: JsValue, post: impl Fn(&JsValue)) {
+    emit_web::attach_trace_context(emit::runtime::shared().ctxt(), &job);
+
+    post(&job);
+}
+```
+*/
+pub fn attach_trace_context(ctxt: impl emit::Ctxt, message: &JsValue) -> bool {
+    let current = emit::SpanCtxt::current(ctxt);
+
+    let (Some(trace_id), Some(span_id)) = (current.trace_id(), current.span_id()) else {
+        return false;
+    };
+
+    if !message.is_object() {
+        return false;
+    }
+
+    Reflect::set(
+        message,
+        &JsValue::from(TRACEPARENT),
+        &JsValue::from(traceparent(trace_id, span_id)),
+    )
+    .unwrap_or(false)
+}
+
+/**
+Restore the trace context attached to `message` by [`attach_trace_context`] in `ctxt`.
+
+The returned frame makes the span that posted `message` the parent of any spans created while it's active.
+If `message` doesn't carry a valid trace context then the frame leaves `ctxt` unchanged.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[emit::span("handle job")]
+fn handle(job: &JsValue) {
+    // ..
+}
+
+fn on_message(job: JsValue) {
+    emit_web::restore_trace_context(emit::runtime::shared().ctxt(), &job).call(|| handle(&job));
+}
+```
+*/
+pub fn restore_trace_context<C: emit::Ctxt>(ctxt: C, message: &JsValue) -> emit::Frame<C> {
+    let parent = Reflect::get(message, &JsValue::from(TRACEPARENT))
+        .ok()
+        .and_then(|traceparent| parse_traceparent(&traceparent.as_string()?));
+
+    match parent {
+        Some((trace_id, span_id)) => {
+            emit::SpanCtxt::new(Some(trace_id), None, Some(span_id)).push(ctxt)
+        }
+        None => emit::Frame::push(ctxt, emit::Empty),
+    }
+}
+
+/**
+Format a `traceparent` for a span.
+
+Spans are always marked as sampled, since `emit` doesn't make sampling decisions up-front.
+*/
+pub(crate) fn traceparent(trace_id: &emit::TraceId, span_id: &emit::SpanId) -> String {
+    format!("00-{trace_id}-{span_id}-01")
+}
+
+/**
+Parse the trace and span ids from a `traceparent`.
+
+Later versions of the format may add fields, which are ignored.
+*/
+pub(crate) fn parse_traceparent(traceparent: &str) -> Option<(emit::TraceId, emit::SpanId)> {
+    let mut parts = traceparent.trim().split('-');
+
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let span_id = parts.next()?;
+    let flags = parts.next()?;
+
+    if version.len() != 2 || version == "ff" || flags.len() != 2 {
+        return None;
+    }
+
+    // Every field is lowercase hex
+    if ![version, trace_id, span_id, flags].iter().all(|part| {
+        part.bytes()
+            .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase())
+    }) {
+        return None;
+    }
+
+    if version == "00" && parts.next().is_some() {
+        return None;
+    }
+
+    Some((
+        emit::TraceId::try_from_hex(trace_id).ok()?,
+        emit::SpanId::try_from_hex(span_id).ok()?,
+    ))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::platform::thread_local_ctxt::ThreadLocalCtxt;
+    use js_sys::Object;
+
+    fn ids() -> (emit::TraceId, emit::SpanId) {
+        (
+            emit::TraceId::from_u128(0x4bf92f3577b34da6a3ce929d0e0e4736).unwrap(),
+            emit::SpanId::from_u64(0x00f067aa0ba902b7).unwrap(),
+        )
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn attach_and_restore() {
+        let (trace_id, span_id) = ids();
+
+        let main = ThreadLocalCtxt::new();
+        let message = JsValue::from(Object::new());
+
+        let attached = emit::SpanCtxt::new(Some(trace_id), None, Some(span_id))
+            .push(&main)
+            .call(|| attach_trace_context(main, &message));
+
+        assert!(attached);
+        assert_eq!(
+            JsValue::from("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            Reflect::get(&message, &JsValue::from(TRACEPARENT)).unwrap()
+        );
+
+        let worker = ThreadLocalCtxt::new();
+
+        let child = restore_trace_context(&worker, &message)
+            .call(|| emit::SpanCtxt::current(worker).new_child(crate::crypto_rng()));
+
+        assert_eq!(Some(&trace_id), child.trace_id());
+        assert_eq!(Some(&span_id), child.span_parent());
+        assert_ne!(Some(&span_id), child.span_id());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn attach_without_context() {
+        let (trace_id, span_id) = ids();
+
+        let ctxt = ThreadLocalCtxt::new();
+
+        let message = JsValue::from(Object::new());
+        assert!(!attach_trace_context(ctxt, &message));
+        assert!(!Reflect::has(&message, &JsValue::from(TRACEPARENT)).unwrap());
+
+        let attached = emit::SpanCtxt::new(Some(trace_id), None, Some(span_id))
+            .push(&ctxt)
+            .call(|| attach_trace_context(ctxt, &JsValue::from("job")));
+        assert!(!attached);
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn restore_without_context() {
+        let ctxt = ThreadLocalCtxt::new();
+
+        for message in [
+            JsValue::from(Object::new()),
+            JsValue::from("job"),
+            JsValue::NULL,
+        ] {
+            let current =
+                restore_trace_context(&ctxt, &message).call(|| emit::SpanCtxt::current(ctxt));
+
+            assert!(current.trace_id().is_none());
+        }
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn parse_traceparents() {
+        let (trace_id, span_id) = ids();
+
+        assert_eq!(
+            Some((trace_id, span_id)),
+            parse_traceparent(&traceparent(&trace_id, &span_id))
+        );
+        assert_eq!(
+            Some((trace_id, span_id)),
+            parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra")
+        );
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "0g-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "0A-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-zz",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-0F",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00F067AA0BA902B7-01",
+        ] {
+            assert_eq!(None, parse_traceparent(invalid), "{invalid}");
+        }
+    }
+}
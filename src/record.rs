@@ -0,0 +1,444 @@
+/*!
+Reconstruct events from records written by [`crate::ser::Buf::record`].
+
+Records are received as JSON, so props are limited to the values JSON can represent.
+*/
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, ops::ControlFlow};
+
+use js_sys::{Array, Object, Reflect, JSON};
+use wasm_bindgen::prelude::*;
+
+use emit::well_known::{KEY_MDL, KEY_MSG, KEY_TPL, KEY_TS, KEY_TS_START};
+
+/**
+An event reconstructed from a record.
+*/
+pub(crate) struct Received {
+    extent: Option<emit::Extent>,
+    mdl: String,
+    tpl: Vec<TplPart>,
+    props: Vec<(String, Prop)>,
+}
+
+enum TplPart {
+    Text(String),
+    Hole(String),
+}
+
+/**
+A property value received in a record.
+*/
+pub(crate) enum Prop {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Number(f64),
+    Str(String),
+    Array(Vec<Prop>),
+    Object(Vec<(String, Prop)>),
+}
+
+impl Received {
+    /**
+    Parse a record from its JSON representation.
+    */
+    pub fn from_json(json: &str) -> Option<Self> {
+        Self::from_js(&JSON::parse(json).ok()?)
+    }
+
+    /**
+    Reconstruct an event from a record that's been parsed from JSON.
+
+    Records without a template fall back to using their rendered message as a literal.
+    */
+    pub fn from_js(record: &JsValue) -> Option<Self> {
+        if !record.is_object() {
+            return None;
+        }
+
+        let tpl = match get(record, KEY_TPL).as_string() {
+            Some(tpl) => parse_tpl(&tpl),
+            None => alloc::vec![TplPart::Text(get(record, KEY_MSG).as_string()?)],
+        };
+
+        let ts = |key| {
+            get(record, key)
+                .as_string()
+                .and_then(|ts| emit::Timestamp::try_from_str(&ts).ok())
+        };
+        let extent = match (ts(KEY_TS_START), ts(KEY_TS)) {
+            (Some(start), Some(end)) => Some(emit::Extent::range(start..end)),
+            (None, Some(ts)) => Some(emit::Extent::point(ts)),
+            _ => None,
+        };
+
+        let props = match Prop::from_js(&get(record, "props")) {
+            Prop::Object(props) => props,
+            _ => Vec::new(),
+        };
+
+        Some(Received {
+            extent,
+            mdl: get(record, KEY_MDL).as_string().unwrap_or_default(),
+            tpl,
+            props,
+        })
+    }
+
+    /**
+    Emit the reconstructed event through `emitter`, along with some additional props.
+
+    Props in `extra` replace any received props with the same key.
+    */
+    pub fn emit(&self, emitter: impl emit::Emitter, extra: impl emit::Props) {
+        let tpl = self
+            .tpl
+            .iter()
+            .map(|part| match part {
+                TplPart::Text(text) => emit::template::Part::text_ref(text),
+                TplPart::Hole(label) => emit::template::Part::hole_ref(label),
+            })
+            .collect::<Vec<_>>();
+
+        emitter.emit(emit::Event::new(
+            emit::Path::new_ref_raw(&self.mdl),
+            emit::Template::new_ref(&tpl),
+            self.extent.clone(),
+            ReceivedProps {
+                props: &self.props,
+                extra,
+            },
+        ));
+    }
+}
+
+struct ReceivedProps<'a, P> {
+    props: &'a [(String, Prop)],
+    extra: P,
+}
+
+impl<'a, P: emit::Props> emit::Props for ReceivedProps<'a, P> {
+    fn for_each<'kv, F: FnMut(emit::Str<'kv>, emit::Value<'kv>) -> ControlFlow<()>>(
+        &'kv self,
+        mut for_each: F,
+    ) -> ControlFlow<()> {
+        self.extra.for_each(&mut for_each)?;
+
+        for (k, v) in self.props {
+            if self.extra.get(&**k).is_none() {
+                for_each(emit::Str::new_ref(k), emit::value::ToValue::to_value(v))?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+/**
+Parse a template, like `"event {n}"`, into its text and holes.
+
+Braces that are doubled, like `{{`, are text.
+*/
+fn parse_tpl(tpl: &str) -> Vec<TplPart> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+
+    let mut chars = tpl.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => match chars.as_str().split_once('}') {
+                Some((label, rest)) => {
+                    if !text.is_empty() {
+                        parts.push(TplPart::Text(core::mem::take(&mut text)));
+                    }
+
+                    parts.push(TplPart::Hole(label.into()));
+                    chars = rest.chars();
+                }
+                // An unterminated hole is treated as text
+                None => text.push(c),
+            },
+            c => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(TplPart::Text(text));
+    }
+
+    parts
+}
+
+impl Prop {
+    /**
+    Convert a value that's been parsed from JSON.
+    */
+    pub fn from_js(value: &JsValue) -> Self {
+        // The largest integer a `Number` can represent exactly
+        const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+        if let Some(v) = value.as_bool() {
+            Prop::Bool(v)
+        } else if let Some(v) = value.as_f64() {
+            if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v) && v == (v as i64) as f64 {
+                Prop::Int(v as i64)
+            } else {
+                Prop::Number(v)
+            }
+        } else if let Some(v) = value.as_string() {
+            Prop::Str(v)
+        } else if Array::is_array(value) {
+            Prop::Array(
+                Array::from(value)
+                    .iter()
+                    .map(|v| Prop::from_js(&v))
+                    .collect(),
+            )
+        } else if value.is_object() {
+            Prop::Object(
+                Object::keys(value.unchecked_ref::<Object>())
+                    .iter()
+                    .filter_map(|k| {
+                        let v = Prop::from_js(&Reflect::get(value, &k).ok()?);
+
+                        Some((k.as_string()?, v))
+                    })
+                    .collect(),
+            )
+        } else {
+            Prop::Null
+        }
+    }
+}
+
+impl emit::value::ToValue for Prop {
+    fn to_value(&self) -> emit::Value<'_> {
+        match self {
+            Prop::Null => emit::Value::null(),
+            Prop::Bool(v) => emit::Value::from(*v),
+            Prop::Int(v) => emit::Value::from(*v),
+            Prop::Number(v) => emit::Value::from(*v),
+            Prop::Str(v) => emit::Value::from(&**v),
+            Prop::Array(_) | Prop::Object(_) => {
+                #[cfg(feature = "serde")]
+                {
+                    emit::Value::from_serde(self)
+                }
+                #[cfg(all(not(feature = "serde"), feature = "sval"))]
+                {
+                    emit::Value::from_sval(self)
+                }
+                #[cfg(not(any(feature = "serde", feature = "sval")))]
+                {
+                    emit::Value::from_display(self)
+                }
+            }
+        }
+    }
+}
+
+/**
+Write a value as JSON.
+
+This is only used for arrays and objects when neither `serde` nor `sval` is enabled.
+*/
+impl fmt::Display for Prop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Prop::Null => f.write_str("null"),
+            Prop::Bool(v) => fmt::Display::fmt(v, f),
+            Prop::Int(v) => fmt::Display::fmt(v, f),
+            Prop::Number(v) => fmt::Display::fmt(v, f),
+            Prop::Str(v) => fmt::Debug::fmt(v, f),
+            Prop::Array(v) => {
+                f.write_str("[")?;
+
+                for (i, v) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+
+                    fmt::Display::fmt(v, f)?;
+                }
+
+                f.write_str("]")
+            }
+            Prop::Object(v) => {
+                f.write_str("{")?;
+
+                for (i, (k, v)) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+
+                    write!(f, "{k:?}:{v}")?;
+                }
+
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Prop {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Prop::Null => serializer.serialize_unit(),
+            Prop::Bool(v) => serializer.serialize_bool(*v),
+            Prop::Int(v) => serializer.serialize_i64(*v),
+            Prop::Number(v) => serializer.serialize_f64(*v),
+            Prop::Str(v) => serializer.serialize_str(v),
+            Prop::Array(v) => serializer.collect_seq(v),
+            Prop::Object(v) => serializer.collect_map(v.iter().map(|(k, v)| (k, v))),
+        }
+    }
+}
+
+#[cfg(feature = "sval")]
+impl sval::Value for Prop {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        match self {
+            Prop::Null => stream.null(),
+            Prop::Bool(v) => stream.bool(*v),
+            Prop::Int(v) => stream.i64(*v),
+            Prop::Number(v) => stream.f64(*v),
+            Prop::Str(v) => stream.value(&**v),
+            Prop::Array(v) => {
+                stream.seq_begin(Some(v.len()))?;
+
+                for v in v {
+                    stream.seq_value_begin()?;
+                    stream.value(v)?;
+                    stream.seq_value_end()?;
+                }
+
+                stream.seq_end()
+            }
+            Prop::Object(v) => {
+                stream.map_begin(Some(v.len()))?;
+
+                for (k, v) in v {
+                    stream.map_key_begin()?;
+                    stream.value(&**k)?;
+                    stream.map_key_end()?;
+
+                    stream.map_value_begin()?;
+                    stream.value(v)?;
+                    stream.map_value_end()?;
+                }
+
+                stream.map_end()
+            }
+        }
+    }
+}
+
+fn get(value: &JsValue, key: &str) -> JsValue {
+    Reflect::get(value, &JsValue::from(key)).unwrap_or(JsValue::UNDEFINED)
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use core::{cell::RefCell, time::Duration};
+    use std::rc::Rc;
+
+    use emit::Props as _;
+
+    use crate::ser::Buf;
+
+    fn ts(secs: u64) -> emit::Timestamp {
+        emit::Timestamp::from_unix(Duration::from_secs(secs)).unwrap()
+    }
+
+    fn round_trip(evt: &emit::Event<impl emit::Props>) -> Received {
+        let mut buf = Buf::new(false);
+        buf.record(evt, None);
+
+        Received::from_json(&buf.json()).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn reconstruct_event() {
+        let received = round_trip(&emit::evt!(
+            extent: ts(1)..ts(2),
+            "event {n} with {{braces}}",
+            n: 1,
+            source: "original",
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736",
+        ));
+
+        let emitted = Rc::new(RefCell::new(Vec::new()));
+
+        received.emit(
+            emit::emitter::from_fn({
+                let emitted = emitted.clone();
+
+                move |evt| {
+                    emitted.borrow_mut().push((
+                        evt.msg().to_string(),
+                        evt.tpl().to_string(),
+                        evt.mdl().to_string(),
+                        evt.extent().cloned(),
+                        evt.props().pull::<i64, _>("n"),
+                        evt.props().pull::<emit::TraceId, _>("trace_id"),
+                        evt.props().pull::<String, _>("source"),
+                    ))
+                }
+            }),
+            ("source", "tab"),
+        );
+
+        let emitted = emitted.borrow();
+        let (msg, tpl, mdl, extent, n, trace_id, source) = &emitted[0];
+
+        assert_eq!("event 1 with {braces}", msg);
+        assert_eq!("event {n} with {{braces}}", tpl);
+        assert_eq!(module_path!(), mdl);
+        assert_eq!(
+            Some(ts(1)..ts(2)),
+            extent
+                .as_ref()
+                .and_then(|extent| extent.as_range().cloned())
+        );
+        assert_eq!(Some(1), *n);
+        assert_eq!(
+            Some(emit::TraceId::from_u128(0x4bf92f3577b34da6a3ce929d0e0e4736).unwrap()),
+            *trace_id
+        );
+        assert_eq!(Some("tab"), source.as_deref());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn parse_templates() {
+        let labels = |tpl| {
+            parse_tpl(tpl)
+                .into_iter()
+                .map(|part| match part {
+                    TplPart::Text(text) => format!("t:{text}"),
+                    TplPart::Hole(label) => format!("h:{label}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["t:a ", "h:b", "t: c"], labels("a {b} c"));
+        assert_eq!(vec!["t:{a} ", "h:b"], labels("{{a}} {b}"));
+        assert_eq!(vec!["t:a {b"], labels("a {b"));
+        assert!(labels("").is_empty());
+    }
+}
@@ -0,0 +1,29 @@
+// Observes reports from the browser for the `ReportObserver` in `emit_web`.
+//
+// Each report is passed back to Rust with its type, the URL of the document
+// that generated it, and its body as a plain object. Events are built from
+// reports in Rust.
+
+export function emit_web_reporting_observe(f) {
+    if (typeof ReportingObserver === "undefined") {
+        return null;
+    }
+
+    const observer = new ReportingObserver(
+        (reports) => {
+            for (const report of reports) {
+                f(report.type, report.url, report.body?.toJSON?.() ?? report.body ?? {});
+            }
+        },
+        // Include reports from before the observer was created
+        { buffered: true },
+    );
+
+    observer.observe();
+
+    return observer;
+}
+
+export function emit_web_reporting_disconnect(observer) {
+    observer?.disconnect();
+}
@@ -0,0 +1,491 @@
+/*!
+Emit reports from the browser using the Reporting API.
+*/
+
+use alloc::{string::String, vec::Vec};
+use core::ops::ControlFlow;
+
+use emit::{well_known::KEY_LVL, Clock as _};
+use wasm_bindgen::prelude::*;
+
+use crate::record::Prop;
+
+type Rt = emit::runtime::AmbientRuntime<'static>;
+
+/**
+A [`ReportObserver`] that emits reports from the browser through `rt`.
+*/
+pub fn observe_reports(rt: &'static Rt) -> ReportObserver {
+    ReportObserver::new(rt)
+}
+
+/**
+Emit reports from the browser, like Content Security Policy violations and deprecation warnings, using a [`ReportingObserver`](https://developer.mozilla.org/en-US/docs/Web/API/ReportingObserver).
+
+These reports would otherwise only be seen in the browser's developer tools, or by a server configured as a `report-to` endpoint.
+Each report is emitted as an event from the `emit_web::reporting` module, with the following props:
+
+- `report_type`: The type of the report, like `"csp-violation"` or `"deprecation"`.
+- `url`: The URL of the document that generated the report.
+- `lvl`: The level of the event. See below for details.
+- Each field of the report's body, with its name converted to snake case, like `blocked_url` for `blockedURL`.
+
+The level of the event depends on the type of the report:
+
+- `csp-violation`: `error` if the violated policy is enforced, or `warn` if it's only reported.
+- `deprecation` and `intervention`: `warn`.
+- `crash`: `error`.
+- Any other type: `warn`.
+
+Reports are emitted through the runtime's filter and emitter, and timestamped by its clock.
+Reports generated before the observer is created are also emitted.
+Reports are emitted until the observer is dropped.
+In environments without the Reporting API this does nothing.
+*/
+pub struct ReportObserver {
+    observer: JsValue,
+}
+
+impl ReportObserver {
+    /**
+    Start emitting reports from the browser through `rt`.
+    */
+    pub fn new(rt: &'static Rt) -> Self {
+        let observer = shim::emit_web_reporting_observe(
+            &Closure::<dyn FnMut(String, Option<String>, JsValue)>::new(
+                move |report_type: String, url: Option<String>, body: JsValue| {
+                    Report::from_js(report_type, url, &body).emit(rt);
+                },
+            )
+            .into_js_value(),
+        );
+
+        ReportObserver { observer }
+    }
+}
+
+impl Drop for ReportObserver {
+    fn drop(&mut self) {
+        shim::emit_web_reporting_disconnect(&self.observer);
+    }
+}
+
+/**
+A report received from the browser.
+*/
+struct Report {
+    report_type: String,
+    url: Option<String>,
+    // The fields of the report's body, with their names in snake case
+    body: Vec<(String, Prop)>,
+}
+
+impl Report {
+    fn from_js(report_type: String, url: Option<String>, body: &JsValue) -> Self {
+        let body = match Prop::from_js(body) {
+            Prop::Object(fields) => fields
+                .into_iter()
+                .map(|(k, v)| (snake_case(&k), v))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Report {
+            report_type,
+            url,
+            body,
+        }
+    }
+
+    fn field(&self, key: &str) -> Option<&str> {
+        self.body.iter().find_map(|(k, v)| match v {
+            Prop::Str(v) if k == key => Some(&**v),
+            _ => None,
+        })
+    }
+
+    fn describe(&self) -> (emit::Template<'static>, emit::Level) {
+        match &*self.report_type {
+            "csp-violation" => (
+                emit::tpl!(
+                    "Content Security Policy directive {effective_directive} violated by {blocked_url}"
+                ),
+                if self.field("disposition") == Some("report") {
+                    emit::Level::Warn
+                } else {
+                    emit::Level::Error
+                },
+            ),
+            "deprecation" | "intervention" => (
+                if self.field("message").is_some() {
+                    emit::tpl!("{message}")
+                } else {
+                    emit::tpl!("{report_type} report")
+                },
+                emit::Level::Warn,
+            ),
+            "crash" => (
+                if self.field("reason").is_some() {
+                    emit::tpl!("page crashed: {reason}")
+                } else {
+                    emit::tpl!("page crashed")
+                },
+                emit::Level::Error,
+            ),
+            _ => (emit::tpl!("{report_type} report"), emit::Level::Warn),
+        }
+    }
+
+    fn emit(&self, rt: &Rt) {
+        let (tpl, lvl) = self.describe();
+
+        rt.emit(emit::Event::new(
+            emit::mdl!(),
+            tpl,
+            rt.clock().now(),
+            ReportProps { report: self, lvl },
+        ));
+    }
+}
+
+struct ReportProps<'a> {
+    report: &'a Report,
+    lvl: emit::Level,
+}
+
+impl emit::Props for ReportProps<'_> {
+    fn for_each<'kv, F: FnMut(emit::Str<'kv>, emit::Value<'kv>) -> ControlFlow<()>>(
+        &'kv self,
+        mut for_each: F,
+    ) -> ControlFlow<()> {
+        use emit::value::ToValue as _;
+
+        for_each(emit::Str::new(KEY_LVL), self.lvl.to_value())?;
+        for_each(
+            emit::Str::new("report_type"),
+            emit::Value::from(&*self.report.report_type),
+        )?;
+
+        if let Some(url) = &self.report.url {
+            for_each(emit::Str::new("url"), emit::Value::from(&**url))?;
+        }
+
+        for (k, v) in &self.report.body {
+            // Fields of the body don't replace the props of the report itself
+            if !matches!(&**k, KEY_LVL | "report_type" | "url") {
+                for_each(emit::Str::new_ref(k), v.to_value())?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+/**
+Convert a field name from camel case into snake case, like `blockedURL` into `blocked_url`.
+
+Runs of capitals are treated as a single word, so `URL` becomes `url` and `HTTPStatus` becomes `http_status`.
+*/
+fn snake_case(key: &str) -> String {
+    let mut snake = String::with_capacity(key.len() + 4);
+
+    let mut prev: Option<char> = None;
+    let mut chars = key.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii_uppercase() {
+            let starts_word = match prev {
+                Some(prev) if prev.is_ascii_uppercase() => {
+                    // The last capital in a run starts the next word, like `S` in `HTTPStatus`
+                    chars.peek().is_some_and(|next| next.is_ascii_lowercase())
+                }
+                Some(prev) => prev.is_ascii_alphanumeric(),
+                None => false,
+            };
+
+            if starts_word {
+                snake.push('_');
+            }
+
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+
+        prev = Some(c);
+    }
+
+    snake
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/reporting.js")]
+    extern "C" {
+        pub fn emit_web_reporting_observe(f: &JsValue) -> JsValue;
+        pub fn emit_web_reporting_disconnect(observer: &JsValue);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use std::sync::Mutex;
+
+    use emit::Props as _;
+
+    use crate::test_util::Globals;
+
+    #[wasm_bindgen(inline_js = "
+    export function stub_reporting_observer() {
+        const observers = [];
+
+        globalThis.ReportingObserver = class {
+            constructor(callback, options) {
+                this.callback = callback;
+                this.options = options;
+                this.connected = false;
+
+                observers.push(this);
+            }
+
+            observe() {
+                this.connected = true;
+            }
+
+            disconnect() {
+                this.connected = false;
+            }
+        };
+
+        return {
+            report: (type, body) => {
+                const report = { type, url: 'https://example.com/', body: { toJSON: () => body } };
+
+                for (const observer of observers) {
+                    if (observer.connected) {
+                        observer.callback([report], observer);
+                    }
+                }
+            },
+            buffered: () => observers.every((observer) => observer.options.buffered),
+        };
+    }
+
+    export function remove_reporting_observer() {
+        delete globalThis.ReportingObserver;
+    }
+    ")]
+    extern "C" {
+        type Stub;
+
+        #[wasm_bindgen(js_name = stub_reporting_observer)]
+        fn stub_reporting_observer_js() -> Stub;
+        fn remove_reporting_observer();
+
+        #[wasm_bindgen(method, structural)]
+        fn report(this: &Stub, report_type: &str, body: &JsValue);
+        #[wasm_bindgen(method, structural)]
+        fn buffered(this: &Stub) -> bool;
+    }
+
+    fn stub_reporting_observer() -> (Stub, Globals) {
+        let globals = Globals::save(&["ReportingObserver"]);
+
+        (stub_reporting_observer_js(), globals)
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Reported {
+        mdl: String,
+        msg: String,
+        lvl: Option<emit::Level>,
+        report_type: String,
+        url: String,
+        source_file: Option<String>,
+        line_number: Option<i64>,
+        timestamp: Option<emit::Timestamp>,
+    }
+
+    type Emitted = Mutex<Vec<Reported>>;
+
+    // Reports are timestamped a second after the epoch
+    struct FixedClock;
+
+    impl emit::Clock for FixedClock {
+        fn now(&self) -> Option<emit::Timestamp> {
+            emit::Timestamp::from_unix(core::time::Duration::from_secs(1))
+        }
+    }
+
+    fn rt(slot: &'static emit::runtime::AmbientSlot, emitted: &'static Emitted) -> &'static Rt {
+        let _ = slot.init(
+            emit::runtime::Runtime::new()
+                .with_emitter(emit::emitter::from_fn(move |evt| {
+                    let props = evt.props();
+
+                    emitted.lock().unwrap().push(Reported {
+                        mdl: evt.mdl().to_string(),
+                        msg: evt.msg().to_string(),
+                        lvl: props.pull("lvl"),
+                        report_type: props.pull("report_type").unwrap(),
+                        url: props.pull("url").unwrap(),
+                        source_file: props.pull("source_file"),
+                        line_number: props.pull("line_number"),
+                        timestamp: evt.extent().map(|extent| *extent.as_point()),
+                    })
+                }))
+                .with_clock(FixedClock),
+        );
+
+        slot.get()
+    }
+
+    fn body(json: &str) -> JsValue {
+        js_sys::JSON::parse(json).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn emit_csp_violations() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let (stub, _globals) = stub_reporting_observer();
+
+        let _observer = observe_reports(rt(&SLOT, &EMITTED));
+
+        assert!(stub.buffered());
+
+        stub.report(
+            "csp-violation",
+            &body(
+                r#"{"blockedURL":"https://cdn.example.com/app.js","effectiveDirective":"script-src-elem","disposition":"enforce"}"#,
+            ),
+        );
+        stub.report(
+            "csp-violation",
+            &body(
+                r#"{"blockedURL":"inline","effectiveDirective":"style-src","disposition":"report"}"#,
+            ),
+        );
+
+        let report = |msg: &str, lvl| Reported {
+            mdl: "emit_web::reporting".into(),
+            msg: msg.into(),
+            lvl: Some(lvl),
+            report_type: "csp-violation".into(),
+            url: "https://example.com/".into(),
+            source_file: None,
+            line_number: None,
+            timestamp: FixedClock.now(),
+        };
+
+        assert_eq!(
+            vec![
+                report(
+                    "Content Security Policy directive script-src-elem violated by https://cdn.example.com/app.js",
+                    emit::Level::Error
+                ),
+                report(
+                    "Content Security Policy directive style-src violated by inline",
+                    emit::Level::Warn
+                ),
+            ],
+            *EMITTED.lock().unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn emit_deprecations_and_interventions() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let (stub, _globals) = stub_reporting_observer();
+
+        let _observer = observe_reports(rt(&SLOT, &EMITTED));
+
+        stub.report(
+            "deprecation",
+            &body(
+                r#"{"id":"UnloadHandler","message":"Unload event listeners are deprecated","sourceFile":"https://example.com/app.js","lineNumber":12}"#,
+            ),
+        );
+        stub.report("intervention", &body(r#"{"id":"HeavyAd"}"#));
+        stub.report("crash", &body(r#"{"reason":"oom"}"#));
+
+        assert_eq!(
+            vec![
+                (
+                    "Unload event listeners are deprecated".into(),
+                    Some(emit::Level::Warn),
+                    Some("https://example.com/app.js".into()),
+                    Some(12),
+                ),
+                (
+                    "intervention report".into(),
+                    Some(emit::Level::Warn),
+                    None,
+                    None
+                ),
+                (
+                    "page crashed: oom".into(),
+                    Some(emit::Level::Error),
+                    None,
+                    None
+                ),
+            ],
+            EMITTED
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|report| (
+                    report.msg.clone(),
+                    report.lvl,
+                    report.source_file.clone(),
+                    report.line_number
+                ))
+                .collect::<Vec<(String, _, _, _)>>()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn disconnect_on_drop() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+        static EMITTED: Emitted = Mutex::new(Vec::new());
+
+        let (stub, _globals) = stub_reporting_observer();
+
+        let observer = observe_reports(rt(&SLOT, &EMITTED));
+
+        drop(observer);
+
+        stub.report("deprecation", &body(r#"{"message":"deprecated"}"#));
+        assert!(EMITTED.lock().unwrap().is_empty());
+
+        // Without the Reporting API, nothing is observed
+        remove_reporting_observer();
+
+        drop(observe_reports(rt(&SLOT, &EMITTED)));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn snake_case_field_names() {
+        for (expected, key) in [
+            ("blocked_url", "blockedURL"),
+            ("url", "URL"),
+            ("http_status", "HTTPStatus"),
+            ("source_file", "sourceFile"),
+            ("column_number", "columnNumber"),
+            ("id", "id"),
+        ] {
+            assert_eq!(expected, snake_case(key));
+        }
+    }
+}
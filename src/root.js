@@ -0,0 +1,15 @@
+// Holds the root span of the page for `emit_web`, as a `traceparent`.
+//
+// The root span outlives any single call into Rust, so it can't be kept in a
+// frame of the ambient context, which is always exited before control returns
+// to JavaScript.
+
+let root = undefined;
+
+export function emit_web_root_get() {
+    return root;
+}
+
+export function emit_web_root_set(traceparent) {
+    root = traceparent;
+}
@@ -0,0 +1,71 @@
+/*!
+The root span of the page.
+*/
+
+use crate::propagate;
+
+/**
+The trace and span ids of the page's root span, or an empty context if there isn't one.
+
+The root span is set by [`crate::PageLoadSpan`] when it starts, and replaced by [`crate::RouteTracer`] each time the page navigates to a new route.
+Spans from [`crate::trace_fetch`], [`crate::trace_interactions`], and [`crate::task`] that start while no other span is current are its children.
+
+The root span is never made current in the ambient context itself, since it lasts longer than any call into Rust.
+Other spans can be made its children by pushing it onto the ambient context:
+
+```rust
+#[emit::span("handle message")]
+fn handle_message() {
+    // ..
+}
+
+fn on_message() {
+    emit_web::root_ctxt()
+        .push(emit::runtime::shared().ctxt())
+        .call(handle_message);
+}
+```
+*/
+pub fn root_ctxt() -> emit::SpanCtxt {
+    shim::emit_web_root_get()
+        .and_then(|traceparent| propagate::parse_traceparent(&traceparent))
+        .map(|(trace_id, span_id)| emit::SpanCtxt::new(Some(trace_id), None, Some(span_id)))
+        .unwrap_or_else(emit::SpanCtxt::empty)
+}
+
+/**
+Replace the root span of the page with `root`, returning the previous one.
+*/
+pub(crate) fn replace(root: emit::SpanCtxt) -> emit::SpanCtxt {
+    let previous = root_ctxt();
+
+    shim::emit_web_root_set(
+        root.trace_id()
+            .zip(root.span_id())
+            .map(|(trace_id, span_id)| propagate::traceparent(trace_id, span_id)),
+    );
+
+    previous
+}
+
+/**
+A frame that makes the root span current in `ctxt`, unless there's already a current span.
+*/
+pub(crate) fn frame<C: emit::Ctxt>(ctxt: C) -> emit::Frame<C> {
+    if emit::SpanCtxt::current(&ctxt).span_id().is_some() {
+        emit::Frame::current(ctxt)
+    } else {
+        root_ctxt().push(ctxt)
+    }
+}
+
+mod shim {
+    use alloc::string::String;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/root.js")]
+    extern "C" {
+        pub fn emit_web_root_get() -> Option<String>;
+        pub fn emit_web_root_set(traceparent: Option<String>);
+    }
+}
@@ -0,0 +1,79 @@
+// Observes history navigations for the `RouteTracer` in `emit_web`.
+//
+// Navigations with `history.pushState` and `popstate` events are reported
+// back to Rust with the new path and how it was navigated to. Only changes
+// to the path are reported, not the query string or fragment.
+//
+// Navigations are reported from a timer, rather than from within the call to
+// `pushState`, so the current span can be replaced without any other spans
+// from the code that navigated still being active.
+
+export function emit_web_route_install(navigate) {
+    const history = globalThis.history;
+
+    if (typeof history?.pushState !== "function" || typeof globalThis.addEventListener !== "function") {
+        return null;
+    }
+
+    const installed = {
+        pushState: history.pushState,
+        // `pushState` is normally inherited from `History.prototype`
+        ownPushState: Object.prototype.hasOwnProperty.call(history, "pushState"),
+        path: globalThis.location?.pathname,
+        onPopState: null,
+        active: true,
+    };
+
+    const report = (navigation_type) => {
+        const path = globalThis.location?.pathname;
+
+        if (path === undefined || path === installed.path) {
+            return;
+        }
+
+        installed.path = path;
+        setTimeout(() => {
+            // The tracer may have been dropped before the timer fired
+            if (installed.active) {
+                navigate(path, navigation_type);
+            }
+        });
+    };
+
+    history.pushState = function pushState(...args) {
+        const result = installed.pushState.apply(this, args);
+
+        report("push");
+
+        return result;
+    };
+
+    installed.onPopState = () => report("pop");
+    globalThis.addEventListener("popstate", installed.onPopState);
+
+    return installed;
+}
+
+export function emit_web_route_uninstall(installed) {
+    if (!installed) {
+        return;
+    }
+
+    installed.active = false;
+
+    if (installed.ownPushState) {
+        globalThis.history.pushState = installed.pushState;
+    } else {
+        delete globalThis.history.pushState;
+    }
+
+    globalThis.removeEventListener("popstate", installed.onPopState);
+}
+
+export function emit_web_route_on_idle(f, timeout) {
+    if (typeof requestIdleCallback === "function") {
+        requestIdleCallback(f, { timeout });
+    } else {
+        setTimeout(f, timeout);
+    }
+}
@@ -0,0 +1,518 @@
+/*!
+Root spans for routes in single-page applications.
+*/
+
+use alloc::{boxed::Box, rc::Rc, string::String};
+use core::{
+    cell::{OnceCell, RefCell},
+    time::Duration,
+};
+
+use emit::{Clock as _, Filter as _, Props as _};
+use wasm_bindgen::prelude::*;
+
+use crate::{duration_millis_f64, root};
+
+type Rt = emit::runtime::AmbientRuntime<'static>;
+type Template = Box<dyn Fn(&str) -> Option<String>>;
+
+/**
+A [`TraceRoutesBuilder`] for starting a new root span in `rt` each time the page navigates to a new route.
+*/
+pub const fn trace_routes(rt: &'static Rt) -> TraceRoutesBuilder {
+    TraceRoutesBuilder::new(rt)
+}
+
+/**
+A builder for a [`RouteTracer`].
+*/
+pub struct TraceRoutesBuilder {
+    rt: &'static Rt,
+    template: Option<Template>,
+    idle_timeout: Option<Duration>,
+}
+
+impl TraceRoutesBuilder {
+    /**
+    Create a new builder for tracing routes in `rt`.
+
+    Spans are also emitted through `rt`.
+    */
+    pub const fn new(rt: &'static Rt) -> Self {
+        TraceRoutesBuilder {
+            rt,
+            template: None,
+            idle_timeout: None,
+        }
+    }
+
+    /**
+    Map the path of the page to the template of the route that matched it, like `/users/:id` for `/users/42`.
+
+    The template is used as the name of the span, so paths with different parameters are grouped together.
+    If `template` returns `None` then the path itself is used.
+    */
+    pub fn template(mut self, template: impl Fn(&str) -> Option<String> + 'static) -> Self {
+        self.template = Some(Box::new(template));
+        self
+    }
+
+    /**
+    End the span for a route once the browser is idle after navigating to it, or after `timeout`, whichever comes first.
+
+    Idleness is detected with [`requestIdleCallback`](https://developer.mozilla.org/en-US/docs/Web/API/Window/requestIdleCallback), where it's available.
+    The span stays the page's root span until the next navigation, so later spans remain in its trace.
+    By default, the span for a route ends when the page navigates to the next one.
+    */
+    pub fn end_on_idle(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /**
+    Start tracing routes.
+    */
+    pub fn install(self) -> RouteTracer {
+        RouteTracer::new(self)
+    }
+}
+
+/**
+Start a new root span each time the page navigates to a new route with the [History API](https://developer.mozilla.org/en-US/docs/Web/API/History_API).
+
+Single-page applications change routes with `history.pushState` and the `popstate` event rather than loading a new page.
+Without a new root span for each route, every span on the page would belong to the same trace.
+
+When the path of the page changes, the span for the previous route ends, and a new span starts in a new trace.
+The new span becomes the page's root span, returned by [`crate::root_ctxt`], until the next navigation.
+Spans from the tracers in this library that start while no other span is current are its children.
+If the runtime's filter doesn't match the span for a route then it's not emitted, and the page has no root span until the next navigation.
+When the tracer is dropped, the page's root span goes back to the one it had before the first navigation.
+Changes to only the query string or fragment don't start a new span.
+
+Spans are emitted from the `emit_web::route` module, named after the template of the route (see [`TraceRoutesBuilder::template`]), with the following props:
+
+- `route`: The template of the route.
+- `navigation_type`: `push` for navigations with `history.pushState`, or `pop` for navigations with the back and forward buttons.
+
+Routes are traced until the tracer is dropped.
+In environments without the History API this does nothing.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[wasm_bindgen]
+pub fn setup() {
+    let tracer = emit_web::trace_routes(emit::runtime::shared())
+        .template(|path| path.starts_with("/users/").then(|| "/users/:id".into()))
+        .install();
+
+    // Keep tracing routes for the lifetime of the page
+    core::mem::forget(tracer);
+}
+```
+*/
+pub struct RouteTracer {
+    installed: JsValue,
+    inner: Rc<Inner>,
+    _navigate: Closure<dyn FnMut(String, String)>,
+}
+
+struct Inner {
+    rt: &'static Rt,
+    template: Option<Template>,
+    idle_timeout: Option<Duration>,
+    // The span for the current route, shared with the callback that ends it when idle
+    current: RefCell<Option<Rc<RefCell<Option<RouteSpan>>>>>,
+    // The root span of the page before the first navigation
+    previous_root: OnceCell<emit::SpanCtxt>,
+}
+
+struct RouteSpan {
+    ctxt: emit::SpanCtxt,
+    route: String,
+    navigation_type: String,
+    start: Option<emit::Timestamp>,
+}
+
+impl RouteTracer {
+    fn new(builder: TraceRoutesBuilder) -> Self {
+        let inner = Rc::new(Inner {
+            rt: builder.rt,
+            template: builder.template,
+            idle_timeout: builder.idle_timeout,
+            current: RefCell::new(None),
+            previous_root: OnceCell::new(),
+        });
+
+        let navigate = Closure::<dyn FnMut(String, String)>::new({
+            let inner = inner.clone();
+
+            move |path: String, navigation_type: String| inner.navigate(&path, navigation_type)
+        });
+
+        let installed = shim::emit_web_route_install(navigate.as_ref());
+
+        RouteTracer {
+            installed,
+            inner,
+            _navigate: navigate,
+        }
+    }
+}
+
+impl Drop for RouteTracer {
+    fn drop(&mut self) {
+        shim::emit_web_route_uninstall(&self.installed);
+
+        self.inner.end_current();
+
+        if let Some(previous_root) = self.inner.previous_root.get() {
+            root::replace(*previous_root);
+        }
+    }
+}
+
+impl Inner {
+    fn navigate(&self, path: &str, navigation_type: String) {
+        self.end_current();
+
+        let route = self
+            .template
+            .as_ref()
+            .and_then(|template| template(path))
+            .unwrap_or_else(|| path.into());
+
+        let ctxt = emit::SpanCtxt::empty().new_child(self.rt.rng());
+
+        let span = RouteSpan {
+            ctxt,
+            route,
+            navigation_type,
+            start: self.rt.clock().now(),
+        };
+
+        // Check the filter with the span before it has an extent, like `PageLoadSpan`
+        let enabled = self.rt.filter().matches(span.to_span(emit::Empty));
+
+        // Spans of a filtered route would otherwise be children of the previous route, which has ended
+        let previous_root = root::replace(if enabled {
+            ctxt
+        } else {
+            emit::SpanCtxt::empty()
+        });
+        let _ = self.previous_root.set(previous_root);
+
+        if !enabled {
+            return;
+        }
+
+        let span = Rc::new(RefCell::new(Some(span)));
+
+        if let Some(timeout) = self.idle_timeout {
+            let rt = self.rt;
+            let span = span.clone();
+
+            shim::emit_web_route_on_idle(
+                &Closure::once_into_js(move || {
+                    if let Some(span) = span.borrow_mut().take() {
+                        span.complete(rt);
+                    }
+                }),
+                duration_millis_f64(timeout),
+            );
+        }
+
+        *self.current.borrow_mut() = Some(span);
+    }
+
+    /**
+    End the span for the current route, if it hasn't already ended.
+    */
+    fn end_current(&self) {
+        let span = self
+            .current
+            .borrow_mut()
+            .take()
+            .and_then(|span| span.borrow_mut().take());
+
+        if let Some(span) = span {
+            span.complete(self.rt);
+        }
+    }
+}
+
+impl RouteSpan {
+    fn to_span(
+        &self,
+        extent: impl emit::extent::ToExtent,
+    ) -> emit::Span<'_, impl emit::Props + '_> {
+        emit::Span::new(
+            emit::path!("emit_web::route"),
+            emit::Str::new_ref(&self.route),
+            extent,
+            self.ctxt.and_props([
+                ("route", emit::Value::from(&*self.route)),
+                ("navigation_type", emit::Value::from(&*self.navigation_type)),
+            ]),
+        )
+    }
+
+    fn complete(self, rt: &Rt) {
+        let extent = self
+            .start
+            .zip(rt.clock().now())
+            .map(|(start, end)| start..end);
+
+        rt.emit(self.to_span(extent));
+    }
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/route.js")]
+    extern "C" {
+        pub fn emit_web_route_install(navigate: &JsValue) -> JsValue;
+        pub fn emit_web_route_uninstall(installed: &JsValue);
+        pub fn emit_web_route_on_idle(f: &JsValue, timeout: f64);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+
+    use crate::test_util::{tick, Globals, Root};
+
+    #[wasm_bindgen(inline_js = "
+    export function stub_history() {
+        const target = new EventTarget();
+
+        globalThis.addEventListener = target.addEventListener.bind(target);
+        globalThis.removeEventListener = target.removeEventListener.bind(target);
+
+        globalThis.location = { pathname: '/' };
+        globalThis.history = {
+            pushState(state, title, url) {
+                globalThis.location.pathname = new URL(url, 'https://example.com').pathname;
+            },
+        };
+
+        return {
+            push: (url) => globalThis.history.pushState(null, '', url),
+            pop: (url) => {
+                globalThis.location.pathname = url;
+                target.dispatchEvent(new Event('popstate'));
+            },
+        };
+    }
+    ")]
+    extern "C" {
+        type Stub;
+
+        #[wasm_bindgen(js_name = stub_history)]
+        fn stub_history_js() -> Stub;
+
+        #[wasm_bindgen(method, structural)]
+        fn push(this: &Stub, url: &str);
+        #[wasm_bindgen(method, structural)]
+        fn pop(this: &Stub, url: &str);
+    }
+
+    fn stub_history() -> (Stub, Globals) {
+        let globals = Globals::save(&[
+            "addEventListener",
+            "removeEventListener",
+            "location",
+            "history",
+        ]);
+
+        (stub_history_js(), globals)
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Traced {
+        name: String,
+        route: String,
+        navigation_type: String,
+        ctxt: emit::SpanCtxt,
+    }
+
+    static TRACED: Mutex<Vec<Traced>> = Mutex::new(Vec::new());
+
+    fn rt() -> &'static Rt {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+
+        rt_with_filter(&SLOT, emit::filter::from_fn(|_| true))
+    }
+
+    fn rt_with_filter(
+        slot: &'static emit::runtime::AmbientSlot,
+        filter: impl emit::Filter + Send + Sync + 'static,
+    ) -> &'static Rt {
+        let _ = slot.init(
+            emit::runtime::Runtime::new()
+                .with_filter(filter)
+                .with_emitter(emit::emitter::from_fn(|evt| {
+                    let props = evt.props();
+
+                    TRACED.lock().unwrap().push(Traced {
+                        name: props.pull("span_name").unwrap(),
+                        route: props.pull("route").unwrap(),
+                        navigation_type: props.pull("navigation_type").unwrap(),
+                        ctxt: emit::SpanCtxt::new(
+                            props.pull("trace_id"),
+                            props.pull("span_parent"),
+                            props.pull("span_id"),
+                        ),
+                    })
+                }))
+                .with_ctxt(emit::platform::thread_local_ctxt::ThreadLocalCtxt::new())
+                .with_rng(crate::crypto_rng()),
+        );
+
+        slot.get()
+    }
+
+    fn take() -> Vec<Traced> {
+        core::mem::take(&mut *TRACED.lock().unwrap())
+    }
+
+    #[wasm_bindgen_test]
+    async fn new_root_span_per_route() {
+        let (stub, _globals) = stub_history();
+        let _root = Root::save();
+        let rt = rt();
+
+        let page = emit::SpanCtxt::new_root(crate::crypto_rng());
+        root::replace(page);
+
+        let tracer = trace_routes(rt)
+            .template(|path| path.starts_with("/users/").then(|| "/users/:id".into()))
+            .install();
+
+        stub.push("/users/42?tab=profile");
+        tick().await;
+
+        let first = crate::root_ctxt();
+        assert!(first.trace_id().is_some());
+        assert_ne!(page.trace_id(), first.trace_id());
+
+        // The route is the root span without being left current
+        assert!(emit::SpanCtxt::current(rt.ctxt()).trace_id().is_none());
+
+        // Changes to the query string aren't new routes
+        stub.push("/users/42?tab=settings");
+        tick().await;
+        assert!(take().is_empty());
+
+        stub.pop("/about");
+        tick().await;
+
+        let second = crate::root_ctxt();
+        assert_ne!(first.trace_id(), second.trace_id());
+
+        drop(tracer);
+
+        assert_eq!(
+            vec![
+                Traced {
+                    name: "/users/:id".into(),
+                    route: "/users/:id".into(),
+                    navigation_type: "push".into(),
+                    ctxt: first,
+                },
+                Traced {
+                    name: "/about".into(),
+                    route: "/about".into(),
+                    navigation_type: "pop".into(),
+                    ctxt: second,
+                },
+            ],
+            take()
+        );
+
+        // The root span from before the first navigation is restored
+        assert_eq!(page, crate::root_ctxt());
+    }
+
+    #[wasm_bindgen_test]
+    async fn end_on_idle() {
+        let (stub, _globals) = stub_history();
+        let _root = Root::save();
+        let rt = rt();
+
+        let tracer = trace_routes(rt)
+            .end_on_idle(Duration::from_millis(1))
+            .install();
+
+        stub.push("/settings");
+        tick().await;
+        tick().await;
+
+        assert_eq!(1, take().len());
+
+        // The route stays the root span after its span ends
+        assert!(crate::root_ctxt().trace_id().is_some());
+
+        drop(tracer);
+        assert!(take().is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    async fn ignore_navigations_after_drop() {
+        let (stub, _globals) = stub_history();
+        let _root = Root::save();
+        let before = crate::root_ctxt();
+        let rt = rt();
+
+        let tracer = trace_routes(rt).install();
+
+        // The navigation is reported from a timer that fires after the tracer is dropped
+        stub.push("/settings");
+        drop(tracer);
+        tick().await;
+
+        assert!(take().is_empty());
+        assert_eq!(before, crate::root_ctxt());
+    }
+
+    #[wasm_bindgen_test]
+    async fn skip_filtered_routes() {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+
+        let (stub, _globals) = stub_history();
+        let _root = Root::save();
+        let rt = rt_with_filter(
+            &SLOT,
+            emit::filter::from_fn(|evt| {
+                evt.props().pull::<String, _>("route").as_deref() != Some("/private")
+            }),
+        );
+
+        let tracer = trace_routes(rt).install();
+
+        stub.push("/home");
+        tick().await;
+        assert!(crate::root_ctxt().span_id().is_some());
+
+        // The filtered route doesn't become the root span, and the previous route has ended
+        stub.push("/private");
+        tick().await;
+        assert!(crate::root_ctxt().span_id().is_none());
+
+        drop(tracer);
+
+        assert_eq!(
+            vec!["/home"],
+            take()
+                .iter()
+                .map(|traced| &*traced.route)
+                .collect::<Vec<_>>()
+        );
+    }
+}
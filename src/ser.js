@@ -0,0 +1,197 @@
+// Decodes values encoded by the `ser` module in `emit_web`.
+//
+// Values are encoded by Rust into a compact buffer so they can be
+// converted into JavaScript in a single call. Strings that are seen
+// repeatedly, like property names, are interned. Their definitions
+// are sent once and kept in `strings` for later buffers to refer to.
+
+const NULL = 0;
+const FALSE = 1;
+const TRUE = 2;
+const NUMBER = 3;
+const BIGINT = 4;
+const STR = 5;
+const STR_REF = 6;
+const BYTES = 7;
+const DATE = 8;
+const ARRAY = 9;
+const OBJECT = 10;
+const MAP = 11;
+const VARIANT = 12;
+const END = 13;
+
+const decoder = new TextDecoder();
+const strings = [];
+
+class Reader {
+    constructor(bytes) {
+        this.bytes = bytes;
+        this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+        this.pos = 0;
+    }
+
+    done() {
+        return this.pos >= this.bytes.length;
+    }
+
+    u8() {
+        return this.bytes[this.pos++];
+    }
+
+    u32() {
+        const v = this.view.getUint32(this.pos, true);
+        this.pos += 4;
+        return v;
+    }
+
+    f64() {
+        const v = this.view.getFloat64(this.pos, true);
+        this.pos += 8;
+        return v;
+    }
+
+    raw() {
+        const len = this.u32();
+        const raw = this.bytes.subarray(this.pos, this.pos + len);
+        this.pos += len;
+        return raw;
+    }
+
+    str() {
+        const raw = this.raw();
+
+        // `TextDecoder` can't read from views over shared memory
+        return decoder.decode(raw.buffer instanceof ArrayBuffer ? raw : raw.slice());
+    }
+
+    value() {
+        const tag = this.u8();
+
+        switch (tag) {
+            case NULL:
+                return null;
+            case FALSE:
+                return false;
+            case TRUE:
+                return true;
+            case NUMBER:
+                return this.f64();
+            case BIGINT:
+                return BigInt(this.str());
+            case STR:
+                return this.str();
+            case STR_REF:
+                return strings[this.u32()];
+            case BYTES:
+                return this.raw().slice();
+            case DATE:
+                return new Date(this.f64());
+            case ARRAY:
+                return this.array();
+            case OBJECT:
+                return this.object();
+            case MAP:
+                return this.map(new Map());
+            case VARIANT: {
+                const variant = this.value();
+                const value = this.value();
+
+                return { [variant]: value };
+            }
+            default:
+                throw new Error(`unexpected tag ${tag}`);
+        }
+    }
+
+    array() {
+        const array = [];
+
+        while (this.bytes[this.pos] !== END) {
+            array.push(this.value());
+        }
+        this.pos++;
+
+        return array;
+    }
+
+    object() {
+        const object = {};
+
+        while (this.bytes[this.pos] !== END) {
+            const key = this.value();
+
+            // Keys that aren't strings would be coerced
+            // so switch to a `Map` to preserve them
+            if (typeof key !== "string") {
+                const map = new Map(Object.entries(object));
+                map.set(key, this.value());
+
+                return this.map(map);
+            }
+
+            object[key] = this.value();
+        }
+        this.pos++;
+
+        return object;
+    }
+
+    map(map) {
+        while (this.bytes[this.pos] !== END) {
+            const key = this.value();
+            map.set(key, this.value());
+        }
+        this.pos++;
+
+        return map;
+    }
+}
+
+// Convert values that `JSON.stringify` can't represent
+function replacer(key, value) {
+    if (typeof value === "bigint") {
+        return value.toString();
+    }
+
+    if (value instanceof Map) {
+        return Array.from(value.entries());
+    }
+
+    if (value instanceof Uint8Array) {
+        return Array.from(value);
+    }
+
+    return value;
+}
+
+function define(defs) {
+    const reader = new Reader(defs);
+
+    while (!reader.done()) {
+        const id = reader.u32();
+        strings[id] = reader.str();
+    }
+}
+
+export function emit_web_decode(defs, values) {
+    define(defs);
+
+    return new Reader(values).value();
+}
+
+export function emit_web_json(defs, values) {
+    return JSON.stringify(emit_web_decode(defs, values), replacer);
+}
+
+export function emit_web_console(defs, values) {
+    define(defs);
+
+    const reader = new Reader(values);
+
+    const lvl = reader.value();
+    const msg = reader.value();
+    const extent = reader.value();
+    const props = reader.value();
+
+    console[lvl](msg, extent, props);
+}
@@ -0,0 +1,700 @@
+/*!
+Convert Rust values into JavaScript ones.
+
+Values are written into a compact buffer by [`Buf`] and decoded by a small JavaScript shim in a single call.
+This avoids crossing the boundary between WebAssembly and JavaScript for every field of every value.
+Strings that are likely to be seen repeatedly, like property names, field names, and literal templates, are interned.
+Keys of maps aren't interned, because they're arbitrary data that could fill the table of interned strings.
+*/
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Write as _},
+    ops::ControlFlow,
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::duration_millis_f64;
+
+/**
+Tests for maps that each serialization framework should pass.
+
+`$write` is the method on [`Buf`] that writes a value using the framework.
+`$key` is a map key that isn't used anywhere else, so it can't have been interned by another test.
+The tests module invoking this needs `BTreeMap` and `get` in scope.
+*/
+#[cfg(all(test, target_arch = "wasm32"))]
+#[cfg(any(feature = "serde", feature = "sval"))]
+macro_rules! map_tests {
+    ($write:ident, $key:literal) => {
+        fn decode_map<K: Ord, V>(
+            entries: impl IntoIterator<Item = (K, V)>,
+            always_use_map: bool,
+            write: impl FnOnce(&mut Buf, &BTreeMap<K, V>),
+        ) -> JsValue {
+            let mut buf = Buf::new(always_use_map);
+            write(&mut buf, &entries.into_iter().collect());
+
+            buf.decode()
+        }
+
+        #[wasm_bindgen_test]
+        #[test]
+        fn map_string_keys_object() {
+            let value = decode_map([("a", 1), ("b", 2)], false, |buf, map| buf.$write(map));
+
+            assert!(!value.is_instance_of::<js_sys::Map>());
+            assert_eq!(JsValue::from(2), get(&value, "b"));
+        }
+
+        #[wasm_bindgen_test]
+        #[test]
+        fn map_non_string_keys_map() {
+            let value = decode_map([(1u64, "a"), (2u64, "b")], false, |buf, map| {
+                buf.$write(map)
+            });
+
+            let map = value.dyn_into::<js_sys::Map>().unwrap();
+            assert_eq!(2, map.size());
+            assert_eq!(JsValue::from("b"), map.get(&JsValue::from(2)));
+            assert!(map.get(&JsValue::from("2")).is_undefined());
+        }
+
+        #[wasm_bindgen_test]
+        #[test]
+        fn map_tuple_keys_map() {
+            let value = decode_map([((1u8, 2u8), "a"), ((3u8, 4u8), "b")], false, |buf, map| {
+                buf.$write(map)
+            });
+
+            let map = value.dyn_into::<js_sys::Map>().unwrap();
+            assert_eq!(2, map.size());
+        }
+
+        #[wasm_bindgen_test]
+        #[test]
+        fn map_always_use_map() {
+            let value = decode_map([("a", 1)], true, |buf, map| buf.$write(map));
+
+            let map = value.dyn_into::<js_sys::Map>().unwrap();
+            assert_eq!(JsValue::from(1), map.get(&JsValue::from("a")));
+        }
+
+        #[wasm_bindgen_test]
+        #[test]
+        fn map_keys_not_interned() {
+            let mut buf = Buf::new(false);
+            buf.$write(&BTreeMap::from([($key, 1)]));
+            assert!(buf.defs.is_empty());
+
+            assert_eq!(JsValue::from(1), get(&buf.decode(), $key));
+        }
+    };
+}
+
+#[cfg(feature = "serde")]
+mod serde_support;
+#[cfg(feature = "sval")]
+mod sval_support;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const NUMBER: u8 = 3;
+const BIGINT: u8 = 4;
+const STR: u8 = 5;
+const STR_REF: u8 = 6;
+#[cfg(any(feature = "serde", feature = "sval"))]
+const BYTES: u8 = 7;
+const DATE: u8 = 8;
+#[cfg(any(feature = "serde", feature = "sval"))]
+const ARRAY: u8 = 9;
+const OBJECT: u8 = 10;
+#[cfg(any(feature = "serde", feature = "sval"))]
+const MAP: u8 = 11;
+#[cfg(any(feature = "serde", feature = "sval"))]
+const VARIANT: u8 = 12;
+const END: u8 = 13;
+
+/**
+The error written in place of a value that fails to serialize.
+*/
+#[cfg(any(feature = "serde", feature = "sval"))]
+struct Error;
+
+#[cfg(any(feature = "serde", feature = "sval"))]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("failed to serialize a value to JavaScript")
+    }
+}
+
+/**
+A buffer of encoded values.
+*/
+pub struct Buf {
+    // Maps can only be written by `serde` or `sval`
+    #[cfg_attr(not(any(feature = "serde", feature = "sval")), allow(dead_code))]
+    always_use_map: bool,
+    defs: Vec<u8>,
+    values: Vec<u8>,
+}
+
+impl Buf {
+    pub fn new(always_use_map: bool) -> Self {
+        Buf {
+            always_use_map,
+            defs: Vec::new(),
+            values: Vec::with_capacity(128),
+        }
+    }
+
+    /**
+    Decode the first value written to the buffer.
+    */
+    pub fn decode(self) -> JsValue {
+        let value = shim::emit_web_decode(&self.defs, &self.values);
+        intern::shipped(&self.defs);
+
+        value
+    }
+
+    /**
+    Decode the first value written to the buffer and convert it into JSON.
+
+    Values that JSON can't represent directly are converted into ones it can.
+    A `BigInt` is written as a string, a `Map` as an array of key-value pairs, and a `Uint8Array` as an array of numbers.
+    */
+    pub fn json(self) -> String {
+        let json = shim::emit_web_json(&self.defs, &self.values);
+        intern::shipped(&self.defs);
+
+        json
+    }
+
+    /**
+    Write the buffer to the console.
+
+    The buffer must contain a level, message, extent, and props.
+    */
+    pub fn console(self) {
+        shim::emit_web_console(&self.defs, &self.values);
+        intern::shipped(&self.defs);
+    }
+
+    /**
+    Write a value to the buffer.
+
+    Values are written using `serde` if it's enabled, or `sval` if it's enabled.
+    If neither is enabled then only primitive values are written.
+    Anything else is written as a string.
+    */
+    pub fn value(&mut self, v: emit::Value) {
+        #[cfg(feature = "serde")]
+        {
+            self.serde(v)
+        }
+        #[cfg(all(not(feature = "serde"), feature = "sval"))]
+        {
+            self.sval(v)
+        }
+        #[cfg(not(any(feature = "serde", feature = "sval")))]
+        {
+            self.primitive(v)
+        }
+    }
+
+    /**
+    Write a primitive value to the buffer without `serde` or `sval`.
+    */
+    #[cfg(not(any(feature = "serde", feature = "sval")))]
+    fn primitive(&mut self, v: emit::Value) {
+        if v.is_null() {
+            self.null();
+        } else if let Some(v) = v.to_borrowed_str() {
+            self.str(v);
+        } else if let Some(v) = v.by_ref().cast::<bool>() {
+            self.bool(v);
+        } else if let Some(v) = v.by_ref().cast::<i64>() {
+            self.int(v as i128);
+        } else if let Some(v) = v.by_ref().cast::<u64>() {
+            self.uint(v as u128);
+        } else if let Some(v) = v.by_ref().cast::<f64>() {
+            self.number(v);
+        } else {
+            self.display(v);
+        }
+    }
+
+    /**
+    Write a set of properties to the buffer as an object.
+    */
+    pub fn props(&mut self, props: impl emit::Props) {
+        self.object_begin();
+
+        let _ = props.for_each(|k, v| {
+            self.interned(k.get());
+            self.value(v);
+
+            ControlFlow::Continue(())
+        });
+
+        self.end();
+    }
+
+    /**
+    Write an extent to the buffer as an object.
+    */
+    pub fn extent(&mut self, extent: Option<&emit::Extent>) {
+        let Some(extent) = extent else {
+            self.null();
+            return;
+        };
+
+        self.object_begin();
+
+        self.interned("timestamp");
+        self.timestamp(*extent.as_point());
+
+        if let Some(len) = extent.len() {
+            self.interned("milliseconds");
+            self.number(duration_millis_f64(len));
+        }
+
+        self.end();
+    }
+
+    /**
+    Write a timestamp to the buffer as a `Date`.
+    */
+    pub fn timestamp(&mut self, ts: emit::Timestamp) {
+        self.tag(DATE);
+        self.f64(duration_millis_f64(ts.to_unix()));
+    }
+
+    /**
+    Write an event to the buffer as a record.
+
+    Records are objects with the event's module, template, rendered message, timestamps, and props.
+    They're the schema used by emitters that send events somewhere other than the console.
+    Timestamps are written as RFC3339 strings so they survive a round-trip through JSON with full precision.
+    Records that need to be identified, like ones that may be sent more than once, also have an id.
+    */
+    pub fn record(&mut self, evt: &emit::Event<impl emit::Props>, id: Option<&str>) {
+        use emit::well_known::{KEY_MDL, KEY_MSG, KEY_TPL, KEY_TS, KEY_TS_START};
+
+        self.object_begin();
+
+        if let Some(id) = id {
+            self.interned("id");
+            self.str(id);
+        }
+
+        if let Some(extent) = evt.extent() {
+            if let Some(range) = extent.as_range() {
+                self.interned(KEY_TS_START);
+                self.display(range.start);
+            }
+
+            self.interned(KEY_TS);
+            self.display(extent.as_point());
+        }
+
+        self.interned(KEY_MDL);
+        self.display(evt.mdl());
+
+        self.interned(KEY_TPL);
+        self.tpl(evt);
+
+        self.interned(KEY_MSG);
+        self.msg(evt);
+
+        self.interned("props");
+        self.props(evt.props());
+
+        self.end();
+    }
+
+    /**
+    Write the template of an event to the buffer as a string.
+    */
+    pub fn tpl(&mut self, evt: &emit::Event<impl emit::Props>) {
+        // Literal templates are likely to be seen again
+        // Braces in them need to be escaped, so they're written like other templates
+        match evt.tpl().as_literal() {
+            Some(tpl) if !tpl.get().contains(['{', '}']) => self.interned(tpl.get()),
+            _ => self.display(evt.tpl()),
+        }
+    }
+
+    /**
+    Write the rendered message of an event to the buffer as a string.
+    */
+    pub fn msg(&mut self, evt: &emit::Event<impl emit::Props>) {
+        match evt.tpl().as_literal() {
+            Some(msg) => self.interned(msg.get()),
+            None => self.display(evt.msg()),
+        }
+    }
+
+    /**
+    Write a string to the buffer that's likely to be seen again.
+    */
+    pub fn interned(&mut self, v: &str) {
+        match intern::id(v, &mut self.defs) {
+            Some(id) => {
+                self.tag(STR_REF);
+                self.u32(id);
+            }
+            None => self.str(v),
+        }
+    }
+
+    /**
+    Write a string to the buffer.
+    */
+    pub fn str(&mut self, v: &str) {
+        self.tag(STR);
+        self.raw(v.as_bytes());
+    }
+
+    /**
+    Write a formatted string to the buffer.
+    */
+    pub fn display(&mut self, v: impl fmt::Display) {
+        self.tag(STR);
+
+        let len_at = self.raw_begin();
+        let _ = write!(self, "{v}");
+        self.raw_end(len_at);
+    }
+
+    pub fn null(&mut self) {
+        self.tag(NULL);
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.tag(if v { TRUE } else { FALSE });
+    }
+
+    fn int(&mut self, v: i128) {
+        // Integers outside of the safe range for a `Number` are written as a `BigInt`
+        if v.unsigned_abs() > (i128::MAX >> 74) as u128 {
+            self.tag(BIGINT);
+            self.raw(v.to_string().as_bytes());
+        } else {
+            self.number(v as f64);
+        }
+    }
+
+    fn uint(&mut self, v: u128) {
+        if v > (u128::MAX >> 75) {
+            self.tag(BIGINT);
+            self.raw(v.to_string().as_bytes());
+        } else {
+            self.number(v as f64);
+        }
+    }
+
+    pub fn number(&mut self, v: f64) {
+        self.tag(NUMBER);
+        self.f64(v);
+    }
+
+    pub fn object_begin(&mut self) {
+        self.tag(OBJECT);
+    }
+
+    pub fn end(&mut self) {
+        self.tag(END);
+    }
+
+    fn tag(&mut self, tag: u8) {
+        self.values.push(tag);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.values.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.values.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn raw(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.values.extend_from_slice(v);
+    }
+
+    fn raw_begin(&mut self) -> usize {
+        let len_at = self.values.len();
+        self.u32(0);
+
+        len_at
+    }
+
+    fn raw_fragment(&mut self, v: &[u8]) {
+        self.values.extend_from_slice(v);
+    }
+
+    fn raw_end(&mut self, len_at: usize) {
+        let len = (self.values.len() - len_at - 4) as u32;
+
+        self.values[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+    }
+}
+
+#[cfg(any(feature = "serde", feature = "sval"))]
+impl Buf {
+    fn array_begin(&mut self) {
+        self.tag(ARRAY);
+    }
+
+    fn map_begin(&mut self) {
+        self.tag(if self.always_use_map { MAP } else { OBJECT });
+    }
+
+    fn variant(&mut self, variant: &str) {
+        self.tag(VARIANT);
+        self.interned(variant);
+    }
+
+    /**
+    Discard any values written since `checkpoint` and write an error in their place.
+    */
+    fn error(&mut self, checkpoint: usize) {
+        self.values.truncate(checkpoint);
+        self.display(Error);
+    }
+
+    fn checkpoint(&self) -> usize {
+        self.values.len()
+    }
+}
+
+impl fmt::Write for Buf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.raw_fragment(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+mod intern {
+    use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+    use core::cell::RefCell;
+
+    // Interned strings are kept per-thread, because each thread
+    // has its own instance of the JavaScript shim
+    std::thread_local! {
+        static STRINGS: RefCell<BTreeMap<Box<str>, Interned>> = const { RefCell::new(BTreeMap::new()) };
+    }
+
+    const MAX_STRINGS: usize = 4096;
+    const MAX_STRING_LEN: usize = 128;
+
+    struct Interned {
+        id: u32,
+        // Whether the string's definition has been decoded.
+        // A buffer that's still being written may have defined
+        // a string that another buffer decoded before it sees
+        shipped: bool,
+    }
+
+    pub fn id(v: &str, defs: &mut Vec<u8>) -> Option<u32> {
+        if v.len() > MAX_STRING_LEN {
+            return None;
+        }
+
+        STRINGS
+            .try_with(|strings| {
+                let mut strings = strings.try_borrow_mut().ok()?;
+
+                if let Some(interned) = strings.get(v) {
+                    if !interned.shipped {
+                        define(defs, interned.id, v);
+                    }
+
+                    return Some(interned.id);
+                }
+
+                if strings.len() >= MAX_STRINGS {
+                    return None;
+                }
+
+                let id = strings.len() as u32;
+
+                strings.insert(v.into(), Interned { id, shipped: false });
+                define(defs, id, v);
+
+                Some(id)
+            })
+            .ok()
+            .flatten()
+    }
+
+    pub fn shipped(mut defs: &[u8]) {
+        if defs.is_empty() {
+            return;
+        }
+
+        let _ = STRINGS.try_with(|strings| {
+            let Ok(mut strings) = strings.try_borrow_mut() else {
+                return;
+            };
+
+            while defs.len() >= 8 {
+                let len = u32::from_le_bytes([defs[4], defs[5], defs[6], defs[7]]) as usize;
+                let v = core::str::from_utf8(&defs[8..8 + len]).unwrap_or_default();
+
+                if let Some(interned) = strings.get_mut(v) {
+                    interned.shipped = true;
+                }
+
+                defs = &defs[8 + len..];
+            }
+        });
+    }
+
+    fn define(defs: &mut Vec<u8>, id: u32, v: &str) {
+        defs.extend_from_slice(&id.to_le_bytes());
+        defs.extend_from_slice(&(v.len() as u32).to_le_bytes());
+        defs.extend_from_slice(v.as_bytes());
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod intern {
+    use alloc::vec::Vec;
+
+    pub fn id(_: &str, _: &mut Vec<u8>) -> Option<u32> {
+        None
+    }
+
+    pub fn shipped(_: &[u8]) {}
+}
+
+mod shim {
+    use alloc::string::String;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/ser.js")]
+    extern "C" {
+        pub fn emit_web_decode(defs: &[u8], values: &[u8]) -> JsValue;
+        pub fn emit_web_console(defs: &[u8], values: &[u8]);
+        pub fn emit_web_json(defs: &[u8], values: &[u8]) -> String;
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use crate::test_util::get;
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn props() {
+        let props = [
+            ("a", emit::Value::from(1)),
+            ("b", emit::Value::from("text")),
+            ("c", emit::Value::from(true)),
+            ("d", emit::Value::from(u64::MAX)),
+        ];
+
+        for _ in 0..2 {
+            let mut buf = Buf::new(false);
+            buf.props(&props);
+
+            let value = buf.decode();
+
+            assert_eq!(JsValue::from(1), get(&value, "a"));
+            assert_eq!(JsValue::from("text"), get(&value, "b"));
+            assert_eq!(JsValue::from(true), get(&value, "c"));
+            assert!(get(&value, "d").is_bigint());
+        }
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn extent() {
+        let mut buf = Buf::new(false);
+        buf.extent(Some(&emit::Extent::range(
+            emit::Timestamp::from_unix(core::time::Duration::from_secs(1)).unwrap()
+                ..emit::Timestamp::from_unix(core::time::Duration::from_millis(1500)).unwrap(),
+        )));
+
+        let value = buf.decode();
+
+        assert_eq!(
+            1500.0,
+            get(&value, "timestamp")
+                .dyn_into::<js_sys::Date>()
+                .unwrap()
+                .get_time()
+        );
+        assert_eq!(JsValue::from(500), get(&value, "milliseconds"));
+
+        let mut buf = Buf::new(false);
+        buf.extent(None);
+
+        assert!(buf.decode().is_null());
+    }
+
+    #[wasm_bindgen_bench]
+    fn bench_encode_props(c: &mut Criterion) {
+        let props = [
+            ("a", emit::Value::from(1)),
+            ("b", emit::Value::from("some text")),
+            ("c", emit::Value::from(true)),
+            ("d", emit::Value::from(3.5)),
+            ("e", emit::Value::from("more text")),
+            ("f", emit::Value::from(42)),
+        ];
+
+        // Build props with serde, the way they were encoded before buffers
+        struct PropsObject<P>(P);
+
+        impl<P: emit::Props> serde::Serialize for PropsObject<P> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeMap as _;
+
+                let mut map = serializer.serialize_map(None)?;
+
+                let mut r = Ok(());
+                let _ = self.0.for_each(|k, v| match map.serialize_entry(&k, &v) {
+                    Ok(()) => core::ops::ControlFlow::Continue(()),
+                    Err(e) => {
+                        r = Err(e);
+                        core::ops::ControlFlow::Break(())
+                    }
+                });
+                r?;
+
+                map.end()
+            }
+        }
+
+        c.bench_function("encode_props_serde", |b| {
+            let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+
+            b.iter(|| serde::Serialize::serialize(&PropsObject(&props), &serializer).unwrap())
+        });
+
+        // Build props from a buffer
+        c.bench_function("encode_props_buf", |b| {
+            b.iter(|| {
+                let mut buf = Buf::new(false);
+                buf.props(&props);
+
+                buf.decode()
+            })
+        });
+    }
+}
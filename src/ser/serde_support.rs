@@ -0,0 +1,573 @@
+use core::fmt;
+
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant, Serializer, StdError,
+};
+
+use super::Buf;
+
+impl Buf {
+    /**
+    Write a value to the buffer using `serde`.
+
+    If the value fails to serialize then an error message is written instead.
+    */
+    pub fn serde(&mut self, v: impl Serialize) {
+        let checkpoint = self.checkpoint();
+
+        if v.serialize(&mut *self).is_err() {
+            self.error(checkpoint);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JsError;
+
+impl fmt::Display for JsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&super::Error, f)
+    }
+}
+
+impl StdError for JsError {}
+
+impl serde::ser::Error for JsError {
+    fn custom<T>(_: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        JsError
+    }
+}
+
+impl Serializer for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.bool(v);
+
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.int(v);
+
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.uint(v);
+
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.number(v);
+
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        let mut buf = [0; 4];
+        let v = v.encode_utf8(&mut buf);
+
+        self.str(v);
+
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.str(v);
+
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.tag(super::BYTES);
+        self.raw(v);
+
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.null();
+
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.null();
+
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.interned(name);
+
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.interned(variant);
+
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.variant(variant);
+
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        self.array_begin();
+
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.array_begin();
+
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.array_begin();
+
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.variant(variant);
+        self.array_begin();
+
+        Ok(self)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        self.map_begin();
+
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.object_begin();
+
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.variant(variant);
+        self.object_begin();
+
+        Ok(self)
+    }
+}
+
+impl SerializeSeq for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Buf::end(self);
+
+        Ok(())
+    }
+}
+
+impl SerializeTuple for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Buf::end(self);
+
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Buf::end(self);
+
+        Ok(())
+    }
+}
+
+impl SerializeTupleVariant for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Buf::end(self);
+
+        Ok(())
+    }
+}
+
+impl SerializeMap for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Buf::end(self);
+
+        Ok(())
+    }
+}
+
+impl SerializeStruct for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.interned(key);
+
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Buf::end(self);
+
+        Ok(())
+    }
+}
+
+impl SerializeStructVariant for &mut Buf {
+    type Ok = ();
+    type Error = JsError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.interned(key);
+
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Buf::end(self);
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen::prelude::*;
+    use wasm_bindgen_test::*;
+
+    use js_sys::Reflect;
+
+    use std::collections::BTreeMap;
+
+    use crate::test_util::get;
+
+    fn jsvalue(v: impl Serialize, always_use_map: bool) -> JsValue {
+        let mut buf = Buf::new(always_use_map);
+        buf.serde(v);
+
+        buf.decode()
+    }
+
+    map_tests!(serde, "serde_map_key");
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn interned_keys_across_bufs() {
+        #[derive(serde::Serialize)]
+        struct Data {
+            interned_a: i32,
+            interned_b: &'static str,
+        }
+
+        for i in 0..3 {
+            let value = jsvalue(
+                Data {
+                    interned_a: i,
+                    interned_b: "b",
+                },
+                false,
+            );
+
+            assert_eq!(
+                JsValue::from(i),
+                Reflect::get(&value, &JsValue::from("interned_a")).unwrap()
+            );
+            assert_eq!(
+                JsValue::from("b"),
+                Reflect::get(&value, &JsValue::from("interned_b")).unwrap()
+            );
+        }
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn interned_keys_nested_bufs() {
+        struct Outer;
+
+        impl serde::Serialize for Outer {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeStruct as _;
+
+                let mut s = serializer.serialize_struct("Outer", 1)?;
+                s.serialize_field("ser_nested_bufs_key", &Inner)?;
+                s.end()
+            }
+        }
+
+        struct Inner;
+
+        impl serde::Serialize for Inner {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                // Decode a buffer that refers to a key defined by the outer one
+                // before the outer one has been decoded
+                let inner = jsvalue(InnerValue, false);
+                assert_eq!(
+                    JsValue::from(2),
+                    Reflect::get(&inner, &JsValue::from("ser_nested_bufs_key")).unwrap()
+                );
+
+                serializer.serialize_i32(1)
+            }
+        }
+
+        struct InnerValue;
+
+        impl serde::Serialize for InnerValue {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeStruct as _;
+
+                let mut s = serializer.serialize_struct("InnerValue", 1)?;
+                s.serialize_field("ser_nested_bufs_key", &2)?;
+                s.end()
+            }
+        }
+
+        let outer = jsvalue(Outer, false);
+        assert_eq!(
+            JsValue::from(1),
+            Reflect::get(&outer, &JsValue::from("ser_nested_bufs_key")).unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn values() {
+        #[derive(serde::Serialize)]
+        enum Data {
+            Unit,
+            Newtype(i32),
+            Tuple(i32, i32),
+            Struct { a: i32 },
+        }
+
+        assert_eq!(JsValue::from("Unit"), jsvalue(Data::Unit, false));
+
+        let value = jsvalue(Data::Newtype(1), false);
+        assert_eq!(
+            JsValue::from(1),
+            Reflect::get(&value, &JsValue::from("Newtype")).unwrap()
+        );
+
+        let value = jsvalue(Data::Tuple(1, 2), false);
+        let tuple = Reflect::get(&value, &JsValue::from("Tuple")).unwrap();
+        assert_eq!(2, js_sys::Array::from(&tuple).length());
+
+        let value = jsvalue(Data::Struct { a: 1 }, false);
+        let fields = Reflect::get(&value, &JsValue::from("Struct")).unwrap();
+        assert_eq!(
+            JsValue::from(1),
+            Reflect::get(&fields, &JsValue::from("a")).unwrap()
+        );
+
+        assert!(jsvalue(u64::MAX, false).is_bigint());
+        assert!(jsvalue(i128::MIN, false).is_bigint());
+        assert_eq!(JsValue::from(42), jsvalue(42u64, false));
+        assert_eq!(JsValue::from("a ✓"), jsvalue("a ✓", false));
+        assert!(jsvalue(Option::<i32>::None, false).is_null());
+
+        let bytes = jsvalue(serde_bytes(&[1, 2, 3]), false);
+        assert_eq!(
+            vec![1, 2, 3],
+            bytes.dyn_into::<js_sys::Uint8Array>().unwrap().to_vec()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn error() {
+        struct Fail;
+
+        impl serde::Serialize for Fail {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("fail"))
+            }
+        }
+
+        assert_eq!(
+            JsValue::from("failed to serialize a value to JavaScript"),
+            jsvalue((1, Fail), false)
+        );
+    }
+
+    fn serde_bytes(bytes: &[u8]) -> impl serde::Serialize + '_ {
+        struct Bytes<'a>(&'a [u8]);
+
+        impl<'a> serde::Serialize for Bytes<'a> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        Bytes(bytes)
+    }
+}
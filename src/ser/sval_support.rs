@@ -0,0 +1,411 @@
+use sval::{tags, Index, Label, Tag};
+
+use super::Buf;
+
+impl Buf {
+    /**
+    Write a value to the buffer using `sval`.
+
+    If the value fails to stream then an error message is written instead.
+    */
+    pub fn sval(&mut self, v: impl sval::Value) {
+        let checkpoint = self.checkpoint();
+
+        let mut stream = Stream {
+            buf: self,
+            raw_at: None,
+            in_enum: false,
+        };
+
+        if sval::stream_computed(&mut stream, v).is_err() {
+            self.error(checkpoint);
+        }
+    }
+}
+
+struct Stream<'a> {
+    buf: &'a mut Buf,
+    // The position of the length of the text or binary value being streamed
+    raw_at: Option<usize>,
+    // Whether the next value is the variant of an enum
+    in_enum: bool,
+}
+
+impl Stream<'_> {
+    /**
+    Write the variant of an enum, if the value being streamed is one.
+
+    Variants with data are written as an object with the variant label as the key,
+    matching the way `serde` represents them.
+    */
+    fn variant(&mut self, label: Option<&Label>) {
+        if core::mem::take(&mut self.in_enum) {
+            if let Some(label) = label {
+                self.buf.variant(label.as_str());
+            }
+        }
+    }
+
+    fn raw_begin(&mut self) -> sval::Result {
+        if self.raw_at.is_some() {
+            return sval::error();
+        }
+
+        self.raw_at = Some(self.buf.raw_begin());
+
+        Ok(())
+    }
+
+    fn raw_fragment(&mut self, fragment: &[u8]) -> sval::Result {
+        if self.raw_at.is_none() {
+            return sval::error();
+        }
+
+        self.buf.raw_fragment(fragment);
+
+        Ok(())
+    }
+
+    fn raw_end(&mut self) -> sval::Result {
+        let len_at = self.raw_at.take().ok_or(sval::Error::new())?;
+
+        self.buf.raw_end(len_at);
+
+        Ok(())
+    }
+}
+
+impl<'sval> sval::Stream<'sval> for Stream<'_> {
+    fn null(&mut self) -> sval::Result {
+        self.buf.null();
+
+        Ok(())
+    }
+
+    fn bool(&mut self, value: bool) -> sval::Result {
+        self.buf.bool(value);
+
+        Ok(())
+    }
+
+    fn text_begin(&mut self, _: Option<usize>) -> sval::Result {
+        self.buf.tag(super::STR);
+        self.raw_begin()
+    }
+
+    fn text_fragment_computed(&mut self, fragment: &str) -> sval::Result {
+        self.raw_fragment(fragment.as_bytes())
+    }
+
+    fn text_end(&mut self) -> sval::Result {
+        self.raw_end()
+    }
+
+    fn binary_begin(&mut self, _: Option<usize>) -> sval::Result {
+        self.buf.tag(super::BYTES);
+        self.raw_begin()
+    }
+
+    fn binary_fragment_computed(&mut self, fragment: &[u8]) -> sval::Result {
+        self.raw_fragment(fragment)
+    }
+
+    fn binary_end(&mut self) -> sval::Result {
+        self.raw_end()
+    }
+
+    fn i64(&mut self, value: i64) -> sval::Result {
+        self.buf.int(value as i128);
+
+        Ok(())
+    }
+
+    fn i128(&mut self, value: i128) -> sval::Result {
+        self.buf.int(value);
+
+        Ok(())
+    }
+
+    fn u64(&mut self, value: u64) -> sval::Result {
+        self.buf.uint(value as u128);
+
+        Ok(())
+    }
+
+    fn u128(&mut self, value: u128) -> sval::Result {
+        self.buf.uint(value);
+
+        Ok(())
+    }
+
+    fn f64(&mut self, value: f64) -> sval::Result {
+        self.buf.number(value);
+
+        Ok(())
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> sval::Result {
+        self.buf.map_begin();
+
+        Ok(())
+    }
+
+    fn map_key_begin(&mut self) -> sval::Result {
+        Ok(())
+    }
+
+    fn map_key_end(&mut self) -> sval::Result {
+        Ok(())
+    }
+
+    fn map_value_begin(&mut self) -> sval::Result {
+        Ok(())
+    }
+
+    fn map_value_end(&mut self) -> sval::Result {
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> sval::Result {
+        self.buf.end();
+
+        Ok(())
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> sval::Result {
+        self.buf.array_begin();
+
+        Ok(())
+    }
+
+    fn seq_value_begin(&mut self) -> sval::Result {
+        Ok(())
+    }
+
+    fn seq_value_end(&mut self) -> sval::Result {
+        Ok(())
+    }
+
+    fn seq_end(&mut self) -> sval::Result {
+        self.buf.end();
+
+        Ok(())
+    }
+
+    fn enum_begin(
+        &mut self,
+        _: Option<&Tag>,
+        _: Option<&Label>,
+        _: Option<&Index>,
+    ) -> sval::Result {
+        self.in_enum = true;
+
+        Ok(())
+    }
+
+    fn enum_end(&mut self, _: Option<&Tag>, _: Option<&Label>, _: Option<&Index>) -> sval::Result {
+        Ok(())
+    }
+
+    fn tagged_begin(
+        &mut self,
+        _: Option<&Tag>,
+        label: Option<&Label>,
+        _: Option<&Index>,
+    ) -> sval::Result {
+        self.variant(label);
+
+        Ok(())
+    }
+
+    fn tagged_end(
+        &mut self,
+        _: Option<&Tag>,
+        _: Option<&Label>,
+        _: Option<&Index>,
+    ) -> sval::Result {
+        Ok(())
+    }
+
+    fn tag(&mut self, tag: Option<&Tag>, label: Option<&Label>, _: Option<&Index>) -> sval::Result {
+        self.in_enum = false;
+
+        match (tag, label) {
+            (Some(&tags::RUST_OPTION_NONE), _) | (Some(&tags::RUST_UNIT), _) | (_, None) => {
+                self.buf.null()
+            }
+            (_, Some(label)) => self.buf.interned(label.as_str()),
+        }
+
+        Ok(())
+    }
+
+    fn record_begin(
+        &mut self,
+        _: Option<&Tag>,
+        label: Option<&Label>,
+        _: Option<&Index>,
+        _: Option<usize>,
+    ) -> sval::Result {
+        self.variant(label);
+        self.buf.object_begin();
+
+        Ok(())
+    }
+
+    fn record_value_begin(&mut self, _: Option<&Tag>, label: &Label) -> sval::Result {
+        self.buf.interned(label.as_str());
+
+        Ok(())
+    }
+
+    fn record_value_end(&mut self, _: Option<&Tag>, _: &Label) -> sval::Result {
+        Ok(())
+    }
+
+    fn record_end(
+        &mut self,
+        _: Option<&Tag>,
+        _: Option<&Label>,
+        _: Option<&Index>,
+    ) -> sval::Result {
+        self.buf.end();
+
+        Ok(())
+    }
+
+    fn tuple_begin(
+        &mut self,
+        _: Option<&Tag>,
+        label: Option<&Label>,
+        _: Option<&Index>,
+        _: Option<usize>,
+    ) -> sval::Result {
+        self.variant(label);
+        self.buf.array_begin();
+
+        Ok(())
+    }
+
+    fn tuple_value_begin(&mut self, _: Option<&Tag>, _: &Index) -> sval::Result {
+        Ok(())
+    }
+
+    fn tuple_value_end(&mut self, _: Option<&Tag>, _: &Index) -> sval::Result {
+        Ok(())
+    }
+
+    fn tuple_end(&mut self, _: Option<&Tag>, _: Option<&Label>, _: Option<&Index>) -> sval::Result {
+        self.buf.end();
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen::prelude::*;
+    use wasm_bindgen_test::*;
+
+    use std::collections::BTreeMap;
+
+    use crate::test_util::get;
+
+    fn jsvalue(v: impl sval::Value, always_use_map: bool) -> JsValue {
+        let mut buf = Buf::new(always_use_map);
+        buf.sval(v);
+
+        buf.decode()
+    }
+
+    map_tests!(sval, "sval_map_key");
+
+    enum Data {
+        Unit,
+        Newtype(i32),
+        Struct { a: i32 },
+    }
+
+    impl sval::Value for Data {
+        fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+            &'sval self,
+            stream: &mut S,
+        ) -> sval::Result {
+            stream.enum_begin(None, Some(&Label::new("Data")), None)?;
+
+            match self {
+                Data::Unit => stream.tag(None, Some(&Label::new("Unit")), Some(&Index::new(0)))?,
+                Data::Newtype(v) => {
+                    let label = Label::new("Newtype");
+                    let index = Index::new(1);
+
+                    stream.tagged_begin(None, Some(&label), Some(&index))?;
+                    stream.i32(*v)?;
+                    stream.tagged_end(None, Some(&label), Some(&index))?;
+                }
+                Data::Struct { a } => {
+                    let label = Label::new("Struct");
+                    let index = Index::new(2);
+
+                    stream.record_begin(None, Some(&label), Some(&index), Some(1))?;
+                    stream.record_value_begin(None, &Label::new("a"))?;
+                    stream.i32(*a)?;
+                    stream.record_value_end(None, &Label::new("a"))?;
+                    stream.record_end(None, Some(&label), Some(&index))?;
+                }
+            }
+
+            stream.enum_end(None, Some(&Label::new("Data")), None)
+        }
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn values() {
+        assert_eq!(JsValue::from("Unit"), jsvalue(Data::Unit, false));
+
+        let value = jsvalue(Data::Newtype(1), false);
+        assert_eq!(JsValue::from(1), get(&value, "Newtype"));
+
+        let value = jsvalue(Data::Struct { a: 1 }, false);
+        assert_eq!(JsValue::from(1), get(&get(&value, "Struct"), "a"));
+
+        let value = jsvalue((1, "a", true), false);
+        assert_eq!(3, js_sys::Array::from(&value).length());
+
+        assert!(jsvalue(u64::MAX, false).is_bigint());
+        assert!(jsvalue(i128::MIN, false).is_bigint());
+        assert_eq!(JsValue::from(42), jsvalue(42u64, false));
+        assert_eq!(JsValue::from("a ✓"), jsvalue("a ✓", false));
+        assert_eq!(JsValue::from(1), jsvalue(Some(1), false));
+        assert!(jsvalue(Option::<i32>::None, false).is_null());
+        assert!(jsvalue((), false).is_null());
+
+        let bytes = jsvalue(sval::BinarySlice::new(&[1, 2, 3]), false);
+        assert_eq!(
+            vec![1, 2, 3],
+            bytes.dyn_into::<js_sys::Uint8Array>().unwrap().to_vec()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn error() {
+        struct Fail;
+
+        impl sval::Value for Fail {
+            fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+                &'sval self,
+                _: &mut S,
+            ) -> sval::Result {
+                sval::error()
+            }
+        }
+
+        assert_eq!(
+            JsValue::from("failed to serialize a value to JavaScript"),
+            jsvalue((1, Fail), false)
+        );
+    }
+}
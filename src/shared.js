@@ -0,0 +1,41 @@
+// Drains events from shared memory for the `SharedChannelPump` in `emit_web`.
+//
+// The pump runs once per animation frame, so draining never competes with
+// rendering. Animation frames are paused in hidden tabs and don't exist
+// outside of a page, so a timer is used instead when they're unavailable.
+
+export function emit_web_pump_start(drain, interval) {
+    const pump = { stopped: false };
+
+    const schedule = () => {
+        if (pump.stopped) {
+            return;
+        }
+
+        if (typeof requestAnimationFrame === "function" && !globalThis.document?.hidden) {
+            requestAnimationFrame(tick);
+        } else {
+            setTimeout(tick, interval);
+        }
+    };
+
+    const tick = () => {
+        if (pump.stopped) {
+            return;
+        }
+
+        try {
+            drain();
+        } finally {
+            schedule();
+        }
+    };
+
+    schedule();
+
+    return pump;
+}
+
+export function emit_web_pump_stop(pump) {
+    pump.stopped = true;
+}
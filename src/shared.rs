@@ -0,0 +1,370 @@
+/*!
+Collect events from threads that share memory with the main thread.
+*/
+
+use alloc::{boxed::Box, rc::Rc, sync::Arc};
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use wasm_bindgen::prelude::*;
+
+use emit::Emitter as _;
+
+use crate::duration_millis_f64;
+
+type OwnedEvent = emit::Event<'static, emit::props::OwnedProps>;
+
+/**
+A [`SharedChannelEmitter`] that can hold up to `capacity` events waiting to be pumped.
+*/
+pub fn shared_channel(capacity: usize) -> SharedChannelEmitter {
+    SharedChannelEmitter::new(capacity)
+}
+
+/**
+An emitter that collects events from any thread into a lock-free ring buffer in shared memory.
+
+With the `atomics` target feature, threads like the ones started by [`wasm-bindgen-rayon`](https://docs.rs/wasm-bindgen-rayon) share linear memory with the main thread, but can't write to its console.
+This emitter can be used from any of them, and a [`SharedChannelPump`] on the main thread emits their events through a local emitter.
+Events don't need a `postMessage` to reach the main thread.
+
+When the buffer is full, new events are dropped until the pump makes room for them.
+
+```rust
+# use wasm_bindgen::prelude::*;
+#[wasm_bindgen]
+pub fn setup() {
+    let channel = emit_web::shared_channel(1024);
+
+    // Emit events from all threads to the console on the main thread
+    let pump = channel.pump(emit_web::console());
+    core::mem::forget(pump);
+
+    let _ = emit::setup().emit_to(channel).try_init();
+}
+```
+*/
+#[derive(Clone)]
+pub struct SharedChannelEmitter {
+    ring: Arc<Ring<OwnedEvent>>,
+}
+
+impl SharedChannelEmitter {
+    /**
+    Create a new emitter that can hold up to `capacity` events waiting to be pumped.
+
+    The capacity is rounded up to the next power of two.
+    */
+    pub fn new(capacity: usize) -> Self {
+        SharedChannelEmitter {
+            ring: Arc::new(Ring::new(capacity)),
+        }
+    }
+
+    /**
+    Start emitting events from the buffer through `emitter`.
+
+    This must be called on the main thread.
+    See [`SharedChannelPump`] for details.
+    */
+    pub fn pump(&self, emitter: impl emit::Emitter + 'static) -> SharedChannelPump {
+        self.pump_with_interval(emitter, Duration::from_millis(100))
+    }
+
+    /**
+    Start emitting events from the buffer through `emitter`, using `interval` when animation frames aren't available.
+
+    See [`SharedChannelPump`] for details.
+    */
+    pub fn pump_with_interval(
+        &self,
+        emitter: impl emit::Emitter + 'static,
+        interval: Duration,
+    ) -> SharedChannelPump {
+        let inner = Rc::new(PumpInner {
+            ring: self.ring.clone(),
+            emitter: Box::new(emitter),
+        });
+
+        let drain = inner.clone();
+        let handle = shim::emit_web_pump_start(
+            &Closure::<dyn FnMut()>::new(move || drain.drain()).into_js_value(),
+            duration_millis_f64(interval),
+        );
+
+        SharedChannelPump { inner, handle }
+    }
+}
+
+impl emit::Emitter for SharedChannelEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let _ = self.ring.push(evt.to_event().to_owned());
+    }
+
+    // The main thread can't be blocked waiting for the pump,
+    // so this only reports whether the buffer is already empty
+    fn blocking_flush(&self, _: Duration) -> bool {
+        self.ring.is_empty()
+    }
+}
+
+/**
+Emit events from a [`SharedChannelEmitter`] through a local emitter on the main thread.
+
+The buffer is drained on each [animation frame](https://developer.mozilla.org/en-US/docs/Web/API/Window/requestAnimationFrame).
+When animation frames aren't available, like in hidden tabs or outside of a page, it's drained on an interval instead.
+
+The pump stops when it's dropped.
+*/
+pub struct SharedChannelPump {
+    inner: Rc<PumpInner>,
+    handle: JsValue,
+}
+
+struct PumpInner {
+    ring: Arc<Ring<OwnedEvent>>,
+    emitter: Box<dyn emit::emitter::ErasedEmitter>,
+}
+
+impl SharedChannelPump {
+    /**
+    Emit any events in the buffer now, without waiting for the next frame or interval.
+    */
+    pub fn drain(&self) {
+        self.inner.drain();
+    }
+}
+
+impl Drop for SharedChannelPump {
+    fn drop(&mut self) {
+        shim::emit_web_pump_stop(&self.handle);
+    }
+}
+
+impl PumpInner {
+    fn drain(&self) {
+        while let Some(evt) = self.ring.pop() {
+            self.emitter.emit(evt);
+        }
+    }
+}
+
+/**
+A bounded, lock-free, multi-producer, multi-consumer queue.
+
+Each slot has a sequence number that tracks whether it's ready to be written or read in the current lap around the ring.
+*/
+struct Ring<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+struct Slot<T> {
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: Values are only accessed by the thread that claimed their slot by advancing `head` or `tail`
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(2).next_power_of_two();
+
+        Ring {
+            slots: (0..capacity)
+                .map(|seq| Slot {
+                    seq: AtomicUsize::new(seq),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+            mask: capacity - 1,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /**
+    Push a value onto the ring, returning it if the ring is full.
+    */
+    fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+
+            match (seq as isize).wrapping_sub(pos as isize) {
+                // The slot is ready to be written
+                0 => match self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: The slot was claimed by advancing `tail`
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+
+                        return Ok(());
+                    }
+                    Err(actual) => pos = actual,
+                },
+                // The slot still holds a value from the previous lap
+                diff if diff < 0 => return Err(value),
+                // Another thread claimed the slot
+                _ => pos = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /**
+    Pop the oldest value from the ring, if there is one.
+    */
+    fn pop(&self) -> Option<T> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+
+            match (seq as isize).wrapping_sub(pos.wrapping_add(1) as isize) {
+                // The slot is ready to be read
+                0 => match self.head.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: The slot was claimed by advancing `head`, and was written before its `seq` was released
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.seq
+                            .store(pos.wrapping_add(self.mask + 1), Ordering::Release);
+
+                        return Some(value);
+                    }
+                    Err(actual) => pos = actual,
+                },
+                // The slot hasn't been written yet
+                diff if diff < 0 => return None,
+                // Another thread claimed the slot
+                _ => pos = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/shared.js")]
+    extern "C" {
+        pub fn emit_web_pump_start(drain: &JsValue, interval: f64) -> JsValue;
+        pub fn emit_web_pump_stop(pump: &JsValue);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use core::cell::RefCell;
+    use std::{string::String, vec::Vec};
+
+    use crate::test_util::sleep;
+
+    type Collected = Rc<RefCell<Vec<String>>>;
+
+    fn collect() -> (Collected, impl emit::Emitter + 'static) {
+        let collected = Rc::new(RefCell::new(Vec::new()));
+
+        let emitter = emit::emitter::from_fn({
+            let collected = collected.clone();
+
+            move |evt| collected.borrow_mut().push(evt.msg().to_string())
+        });
+
+        (collected, emitter)
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn ring_is_fifo() {
+        let ring = Ring::new(4);
+
+        for lap in 0..3 {
+            for i in 0..4 {
+                ring.push(lap * 4 + i).unwrap();
+            }
+
+            assert_eq!(Err(99), ring.push(99));
+
+            for i in 0..4 {
+                assert_eq!(Some(lap * 4 + i), ring.pop());
+            }
+
+            assert_eq!(None, ring.pop());
+            assert!(ring.is_empty());
+        }
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn drop_new_events_when_full() {
+        let channel = shared_channel(2);
+
+        let (collected, emitter) = collect();
+        let pump = channel.pump(emitter);
+
+        channel.emit(emit::evt!("event {n}", n: 1));
+        channel.emit(emit::evt!("event {n}", n: 2));
+        channel.emit(emit::evt!("event {n}", n: 3));
+
+        assert!(!channel.blocking_flush(Duration::from_secs(1)));
+
+        pump.drain();
+
+        assert_eq!(vec!["event 1", "event 2"], *collected.borrow());
+        assert!(channel.blocking_flush(Duration::from_secs(1)));
+    }
+
+    #[wasm_bindgen_test]
+    async fn pump_on_interval() {
+        let channel = shared_channel(16);
+
+        let (collected, emitter) = collect();
+        let pump = channel.pump_with_interval(emitter, Duration::from_millis(10));
+
+        channel.emit(emit::evt!("event {n}", n: 1));
+
+        sleep(50.0).await;
+        assert_eq!(vec!["event 1"], *collected.borrow());
+
+        drop(pump);
+
+        channel.emit(emit::evt!("event {n}", n: 2));
+
+        sleep(50.0).await;
+        assert_eq!(vec!["event 1"], *collected.borrow());
+    }
+}
@@ -0,0 +1,92 @@
+// Keeps events for the `StorageRingEmitter` in `emit_web` in Web Storage.
+//
+// Events are stored under a single key as a JSON array. Emitters keep the
+// array in memory, and only read it again when its generation changes. The
+// generation is bumped on each write, and by `storage` events when another
+// tab writes to the key, so tabs that share a key don't overwrite each
+// other's events.
+
+const SAVED = 0;
+const FULL = 1;
+const UNAVAILABLE = 2;
+
+function storage(session) {
+    // Accessing storage throws if it's been disabled
+    try {
+        return session ? globalThis.sessionStorage : globalThis.localStorage;
+    } catch {
+        return undefined;
+    }
+}
+
+// Generations by storage and key, along with a count of times all keys have
+// been cleared
+const generations = new Map();
+let cleared = 0;
+let listening = false;
+
+function changed(session, key) {
+    const id = `${session}:${key}`;
+
+    generations.set(id, (generations.get(id) ?? 0) + 1);
+}
+
+function listen() {
+    if (listening || typeof globalThis.addEventListener !== "function") {
+        return;
+    }
+
+    // `storage` events are only fired for writes from other tabs
+    globalThis.addEventListener("storage", (event) => {
+        if (event.key === null) {
+            cleared += 1;
+        } else {
+            changed(event.storageArea === storage(true), event.key);
+        }
+    });
+
+    listening = true;
+}
+
+function load(session, key) {
+    try {
+        const value = storage(session)?.getItem(key);
+        const records = value == null ? [] : JSON.parse(value);
+
+        // The key may have been written by something else
+        return Array.isArray(records) ? records : [];
+    } catch {
+        return [];
+    }
+}
+
+export function emit_web_storage_load(session, key) {
+    return load(session, key).map((record) => JSON.stringify(record));
+}
+
+export function emit_web_storage_save(session, key, value) {
+    const target = storage(session);
+
+    if (target == null) {
+        return UNAVAILABLE;
+    }
+
+    try {
+        target.setItem(key, value);
+        changed(session, key);
+
+        return SAVED;
+    } catch (e) {
+        return e?.name === "QuotaExceededError" ? FULL : UNAVAILABLE;
+    }
+}
+
+export function emit_web_storage_generation(session, key) {
+    listen();
+
+    return cleared + (generations.get(`${session}:${key}`) ?? 0);
+}
+
+export function emit_web_storage_dump(key) {
+    return [...load(false, key), ...load(true, key)];
+}
@@ -0,0 +1,454 @@
+/*!
+Keep the most recent events in Web Storage so they can be retrieved later.
+*/
+
+use alloc::{collections::VecDeque, string::String};
+use core::{cell::RefCell, time::Duration};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{ser, FetchFormat, Local};
+
+const DEFAULT_KEY: &str = "emit_web_logs";
+
+const SAVED: u8 = 0;
+const FULL: u8 = 1;
+
+/**
+An emitter that keeps the most recent events in [`localStorage`](https://developer.mozilla.org/en-US/docs/Web/API/Window/localStorage).
+*/
+pub const fn storage_ring() -> StorageRingEmitter {
+    StorageRingEmitter::new()
+}
+
+/**
+The kind of [Web Storage](https://developer.mozilla.org/en-US/docs/Web/API/Web_Storage_API) to keep events in.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebStorage {
+    /**
+    [`localStorage`](https://developer.mozilla.org/en-US/docs/Web/API/Window/localStorage), which is kept until it's cleared.
+    */
+    Local,
+    /**
+    [`sessionStorage`](https://developer.mozilla.org/en-US/docs/Web/API/Window/sessionStorage), which is kept until the tab is closed.
+    */
+    Session,
+}
+
+/**
+An emitter that keeps the most recent events in [Web Storage](https://developer.mozilla.org/en-US/docs/Web/API/Web_Storage_API).
+
+Events are stored as a JSON array under a single key, using the same schema as [`crate::FetchFormat`].
+Once there are more than a configured number of events, or their combined length is more than a configured maximum, the oldest events are dropped.
+If the browser's storage quota is reached then older events are dropped until the rest fit.
+If storage isn't available, like in some private browsing modes, then events aren't kept.
+
+Events are kept in memory and written to storage as each new one is emitted.
+They're only read back from storage when it's been written to by something else, like an emitter in another tab that shares the same key, so emitters add to the same events instead of overwriting them.
+
+Stored events survive page reloads, and can be retrieved from JavaScript with the exported `emitWebDumpLogs` function.
+See [`dump_logs`] for details.
+*/
+pub struct StorageRingEmitter {
+    storage: WebStorage,
+    key: &'static str,
+    max_events: usize,
+    max_len: usize,
+    state: Local<RefCell<Option<State>>>,
+}
+
+struct State {
+    // Changes whenever the key is written to, so stale events are read again
+    generation: u32,
+    records: VecDeque<(String, usize)>,
+    len: usize,
+}
+
+impl StorageRingEmitter {
+    /**
+    Create a new instance of the storage ring emitter.
+    */
+    pub const fn new() -> Self {
+        StorageRingEmitter {
+            storage: WebStorage::Local,
+            key: DEFAULT_KEY,
+            max_events: 500,
+            max_len: 256 * 1024,
+            state: Local(RefCell::new(None)),
+        }
+    }
+
+    /**
+    Set the kind of storage to keep events in.
+
+    The default is [`WebStorage::Local`].
+    */
+    pub const fn storage(mut self, storage: WebStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /**
+    Set the key to store events under.
+
+    The default key is `emit_web_logs`.
+    */
+    pub const fn key(mut self, key: &'static str) -> Self {
+        self.key = key;
+        self
+    }
+
+    /**
+    Set the maximum number of events to keep.
+
+    The default maximum is 500 events.
+    */
+    pub const fn max_events(mut self, max_events: usize) -> Self {
+        self.max_events = max_events;
+        self
+    }
+
+    /**
+    Set the maximum combined length of kept events, in UTF-16 code units.
+
+    This is the same unit browsers use for storage quotas.
+    The default maximum is 256Ki code units.
+    */
+    pub const fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    fn load(&self, generation: u32) -> State {
+        let mut state = State {
+            generation,
+            records: VecDeque::new(),
+            len: 0,
+        };
+
+        for record in shim::emit_web_storage_load(self.is_session(), self.key) {
+            state.push_back(record);
+        }
+
+        state
+    }
+
+    fn save(&self, state: &mut State) {
+        loop {
+            let value = FetchFormat::Json.body(state.records.iter().map(|(record, _)| record));
+
+            match shim::emit_web_storage_save(self.is_session(), self.key, &value) {
+                SAVED => break,
+                FULL if !state.records.is_empty() => {
+                    // Make room by dropping the oldest events until there's a
+                    // quarter less to write, instead of retrying after each one
+                    let target = state.body_len() * 3 / 4;
+
+                    while !state.records.is_empty() && state.body_len() > target {
+                        state.pop_front();
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        // Writing bumps the generation, so it's read again afterwards
+        state.generation = shim::emit_web_storage_generation(self.is_session(), self.key);
+    }
+
+    fn is_session(&self) -> bool {
+        matches!(self.storage, WebStorage::Session)
+    }
+}
+
+impl State {
+    fn push_back(&mut self, record: String) {
+        let len = record.encode_utf16().count();
+
+        self.len += len;
+        self.records.push_back((record, len));
+    }
+
+    fn pop_front(&mut self) {
+        if let Some((_, len)) = self.records.pop_front() {
+            self.len -= len;
+        }
+    }
+
+    fn body_len(&self) -> usize {
+        FetchFormat::Json.body_len(self.records.len(), self.len)
+    }
+}
+
+impl Default for StorageRingEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl emit::Emitter for StorageRingEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let mut state = self.state.borrow_mut();
+
+        // Events kept by other tabs since the last write are read back first
+        let generation = shim::emit_web_storage_generation(self.is_session(), self.key);
+        let state = match &mut *state {
+            Some(state) if state.generation == generation => state,
+            state => state.insert(self.load(generation)),
+        };
+
+        let mut buf = ser::Buf::new(false);
+        buf.record(&evt, None);
+
+        state.push_back(buf.json());
+
+        while state.records.len() > self.max_events || state.len > self.max_len {
+            state.pop_front();
+        }
+
+        self.save(state);
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        true
+    }
+}
+
+/**
+Get the events kept by [`StorageRingEmitter`]s under `key` as an array of objects.
+
+If no `key` is given then the default key of `emit_web_logs` is used.
+Events are read from both `localStorage` and `sessionStorage`.
+
+This function is exported from the generated JavaScript module as `emitWebDumpLogs`.
+It isn't added to the global scope, so to call it from the browser console it needs to be assigned to `window` first:
+
+```js
+import init, { emitWebDumpLogs } from "./pkg/my_app.js";
+
+await init();
+
+window.emitWebDumpLogs = emitWebDumpLogs;
+```
+
+It can then be called from the console or a support flow:
+
+```js
+const logs = emitWebDumpLogs();
+```
+*/
+#[wasm_bindgen(js_name = emitWebDumpLogs)]
+pub fn dump_logs(key: Option<String>) -> JsValue {
+    shim::emit_web_storage_dump(key.as_deref().unwrap_or(DEFAULT_KEY))
+}
+
+mod shim {
+    use alloc::{string::String, vec::Vec};
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/storage.js")]
+    extern "C" {
+        pub fn emit_web_storage_load(session: bool, key: &str) -> Vec<String>;
+        pub fn emit_web_storage_save(session: bool, key: &str, value: &str) -> u8;
+        pub fn emit_web_storage_generation(session: bool, key: &str) -> u32;
+        pub fn emit_web_storage_dump(key: &str) -> JsValue;
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::Emitter as _;
+    use js_sys::Array;
+
+    use crate::test_util::{get, Globals};
+
+    #[wasm_bindgen(inline_js = "
+    function storage(quota) {
+        const items = new Map();
+
+        return {
+            getItem: (key) => items.get(key) ?? null,
+            setItem: (key, value) => {
+                if (value.length > quota) {
+                    throw new DOMException('the quota has been exceeded', 'QuotaExceededError');
+                }
+
+                items.set(key, value);
+            },
+        };
+    }
+
+    export function stub_storage(quota) {
+        globalThis.localStorage = storage(quota);
+        globalThis.sessionStorage = storage(quota);
+    }
+
+    export function set_local(key, value) {
+        globalThis.localStorage.setItem(key, value);
+    }
+    ")]
+    extern "C" {
+        #[wasm_bindgen(js_name = stub_storage)]
+        fn stub_storage_js(quota: usize);
+        fn set_local(key: &str, value: &str);
+    }
+
+    fn stub_storage(quota: usize) -> Globals {
+        let globals = Globals::save(&["localStorage", "sessionStorage"]);
+        stub_storage_js(quota);
+
+        globals
+    }
+
+    fn msgs(key: &str) -> Vec<String> {
+        Array::from(&dump_logs(Some(key.into())))
+            .iter()
+            .map(|record| get(&record, "msg").as_string().unwrap())
+            .collect()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn keep_most_recent() {
+        let _globals = stub_storage(usize::MAX);
+
+        let emitter = storage_ring().key("keep_most_recent").max_events(2);
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+        emitter.emit(emit::evt!("event {n}", n: 3));
+
+        assert_eq!(vec!["event 2", "event 3"], msgs("keep_most_recent"));
+
+        let evt = Array::from(&dump_logs(Some("keep_most_recent".into()))).get(1);
+        assert_eq!(JsValue::from(3), get(&get(&evt, "props"), "n"));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn keep_max_len() {
+        let _globals = stub_storage(usize::MAX);
+
+        let emitter = storage_ring().key("keep_max_len").max_len(200);
+
+        let data = "a".repeat(80);
+
+        emitter.emit(emit::evt!("event {n}", n: 1, data));
+        emitter.emit(emit::evt!("event {n}", n: 2, data));
+        emitter.emit(emit::evt!("event {n}", n: 3, data));
+
+        assert_eq!(vec!["event 3"], msgs("keep_max_len"));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn drop_oldest_over_quota() {
+        let _globals = stub_storage(400);
+
+        let emitter = storage_ring().key("drop_oldest_over_quota");
+
+        let data = "a".repeat(80);
+
+        for n in 0..10 {
+            emitter.emit(emit::evt!("event {n}", n, data));
+        }
+
+        let msgs = msgs("drop_oldest_over_quota");
+
+        assert!(msgs.len() < 10);
+        assert_eq!("event 9", msgs.last().unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn load_after_reload() {
+        let _globals = stub_storage(usize::MAX);
+
+        let emitter = storage_ring()
+            .storage(WebStorage::Session)
+            .key("load_after_reload")
+            .max_events(2);
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+
+        let emitter = storage_ring()
+            .storage(WebStorage::Session)
+            .key("load_after_reload")
+            .max_events(2);
+
+        emitter.emit(emit::evt!("event {n}", n: 3));
+
+        assert_eq!(vec!["event 2", "event 3"], msgs("load_after_reload"));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn quota_too_small() {
+        let _globals = stub_storage(0);
+
+        let emitter = storage_ring().key("quota_too_small");
+
+        emitter.emit(emit::evt!("event"));
+
+        assert!(msgs("quota_too_small").is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn share_key_between_tabs() {
+        let _globals = stub_storage(usize::MAX);
+
+        // Emitters in different tabs don't share any state besides storage
+        let tab_a = storage_ring().key("share_key_between_tabs");
+        let tab_b = storage_ring().key("share_key_between_tabs");
+
+        tab_a.emit(emit::evt!("event {n}", n: 1));
+        tab_b.emit(emit::evt!("event {n}", n: 2));
+        tab_a.emit(emit::evt!("event {n}", n: 3));
+
+        assert_eq!(
+            vec!["event 1", "event 2", "event 3"],
+            msgs("share_key_between_tabs")
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn keep_events_in_memory() {
+        let _globals = stub_storage(usize::MAX);
+
+        let emitter = storage_ring().key("keep_events_in_memory");
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+
+        // Writes that aren't announced by a `storage` event aren't read back
+        set_local("keep_events_in_memory", "[]");
+
+        emitter.emit(emit::evt!("event {n}", n: 2));
+
+        assert_eq!(vec!["event 1", "event 2"], msgs("keep_events_in_memory"));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn replace_invalid_value() {
+        let _globals = stub_storage(usize::MAX);
+
+        set_local("replace_invalid_value", "{\"msg\":\"not an array\"}");
+
+        assert!(msgs("replace_invalid_value").is_empty());
+
+        let emitter = storage_ring().key("replace_invalid_value");
+
+        emitter.emit(emit::evt!("event"));
+
+        assert_eq!(vec!["event"], msgs("replace_invalid_value"));
+    }
+}
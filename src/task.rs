@@ -0,0 +1,437 @@
+/*!
+Spawn futures that carry `emit`'s ambient context.
+*/
+
+use alloc::boxed::Box;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{performance, root};
+
+type Rt = emit::runtime::AmbientRuntime<'static>;
+
+/**
+Spawn `fut` on the current thread, keeping the ambient context of the caller in `rt`.
+
+This is the same as `task(rt).spawn_local(fut)`. See [`TaskBuilder`] for details.
+*/
+pub fn spawn_local(rt: &'static Rt, fut: impl Future<Output = ()> + 'static) {
+    task(rt).spawn_local(fut)
+}
+
+/**
+A [`TaskBuilder`] for spawning futures that keep the ambient context of the caller in `rt`.
+*/
+pub const fn task(rt: &'static Rt) -> TaskBuilder {
+    TaskBuilder::new(rt)
+}
+
+/**
+Spawn futures on the current thread using [`wasm_bindgen_futures::spawn_local`](https://docs.rs/wasm-bindgen-futures/latest/wasm_bindgen_futures/fn.spawn_local.html), keeping the ambient context of the caller.
+
+A spawned future would otherwise run without the current span, because it's polled later by the JavaScript event loop rather than by its caller.
+The ambient context at the point the task is spawned is captured and made current around every poll of the future.
+If there's no current span when the task is spawned, the page's root span from [`crate::root_ctxt`] is made current instead.
+
+The task can also be wrapped in a span with [`TaskBuilder::span`].
+While the future is running, each synchronous poll blocks the browser's main thread, so the task records the following props:
+
+- `task_polls`: The number of times the future was polled.
+- `task_longest_poll_ms`: The time taken by the longest single poll of the future, in milliseconds.
+
+When the task has a span, these props are added to it when it completes.
+Otherwise they're only emitted if [`TaskBuilder::report_metrics`] is set.
+
+If the task is dropped before its future completes, it still completes its span and reports its metrics.
+When that's because the future panicked, the span also has an `err` prop.
+This needs panics to unwind, which they don't by default in WebAssembly.
+*/
+pub struct TaskBuilder {
+    rt: &'static Rt,
+    span: Option<(emit::Path<'static>, emit::Str<'static>)>,
+    report_metrics: bool,
+}
+
+impl TaskBuilder {
+    /**
+    Create a new builder for spawning futures that keep the ambient context of the caller in `rt`.
+
+    Events are also emitted through `rt`.
+    */
+    pub const fn new(rt: &'static Rt) -> Self {
+        TaskBuilder {
+            rt,
+            span: None,
+            report_metrics: false,
+        }
+    }
+
+    /**
+    Wrap the task in a span named `name`, emitted from the module `mdl`.
+
+    The span starts when the task is spawned, and completes when its future does.
+    It's a child of the span that was current when the task was spawned, or of the page's root span if there wasn't one.
+
+    ```rust,no_run
+    # async fn load_profile() {}
+    emit_web::task(emit::runtime::shared())
+        .span(emit::mdl!(), "load profile")
+        .spawn_local(load_profile());
+    ```
+    */
+    pub fn span(
+        mut self,
+        mdl: impl Into<emit::Path<'static>>,
+        name: impl Into<emit::Str<'static>>,
+    ) -> Self {
+        self.span = Some((mdl.into(), name.into()));
+        self
+    }
+
+    /**
+    Whether to emit the task's metrics as a `debug` event from the `emit_web::task` module when it completes.
+
+    This only applies to tasks without a span, because a span carries the metrics itself.
+    The default is `false`.
+    */
+    pub const fn report_metrics(mut self, report_metrics: bool) -> Self {
+        self.report_metrics = report_metrics;
+        self
+    }
+
+    /**
+    Spawn `fut` on the current thread.
+    */
+    pub fn spawn_local(self, fut: impl Future<Output = ()> + 'static) {
+        wasm_bindgen_futures::spawn_local(self.build(fut));
+    }
+
+    fn build<F: Future<Output = ()> + 'static>(
+        self,
+        fut: F,
+    ) -> Task<&'static emit::runtime::AmbientCtxt<'static>, F> {
+        let rt = self.rt;
+
+        match self.span {
+            Some((mdl, name)) => {
+                let (mut guard, frame) = root::frame(rt.ctxt()).call(|| {
+                    emit::span::SpanGuard::new(
+                        rt.filter(),
+                        rt.ctxt(),
+                        rt.clock(),
+                        rt.rng(),
+                        emit::span::completion::Default::<_, _, emit::Level>::new(
+                            rt.emitter(),
+                            rt.ctxt(),
+                        ),
+                        emit::Empty,
+                        mdl,
+                        name,
+                        Metrics::default().props(),
+                    )
+                });
+
+                guard.start();
+
+                Task::new(frame, fut, move |metrics| {
+                    if let Some(props) = guard.props_mut() {
+                        *props = metrics.props();
+                    }
+
+                    guard.complete();
+                })
+            }
+            None if self.report_metrics => Task::new(root::frame(rt.ctxt()), fut, move |metrics| {
+                emit::debug!(
+                    rt,
+                    mdl: emit::path!("emit_web::task"),
+                    "task completed after {task_polls} polls, the longest taking {task_longest_poll_ms}ms",
+                    task_polls: metrics.polls,
+                    task_longest_poll_ms: metrics.longest_poll_ms,
+                );
+            }),
+            None => Task::new(root::frame(rt.ctxt()), fut, |_| ()),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct Metrics {
+    polls: usize,
+    longest_poll_ms: f64,
+}
+
+impl Metrics {
+    fn props(&self) -> [(&'static str, emit::Value<'static>); 2] {
+        [
+            ("task_polls", emit::Value::from(self.polls)),
+            (
+                "task_longest_poll_ms",
+                emit::Value::from(self.longest_poll_ms),
+            ),
+        ]
+    }
+}
+
+struct Task<C: emit::Ctxt, F> {
+    frame: emit::Frame<C>,
+    fut: Pin<Box<F>>,
+    metrics: Metrics,
+    complete: Option<Box<dyn FnOnce(Metrics)>>,
+}
+
+// The future is boxed, so nothing in a `Task` is pinned
+impl<C: emit::Ctxt, F> Unpin for Task<C, F> {}
+
+impl<C: emit::Ctxt, F: Future<Output = ()>> Task<C, F> {
+    fn new(frame: emit::Frame<C>, fut: F, complete: impl FnOnce(Metrics) + 'static) -> Self {
+        Task {
+            frame,
+            fut: Box::pin(fut),
+            metrics: Metrics::default(),
+            complete: Some(Box::new(complete)),
+        }
+    }
+}
+
+impl<C: emit::Ctxt, F: Future<Output = ()>> Future for Task<C, F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let Task {
+            frame,
+            fut,
+            metrics,
+            complete,
+        } = self.get_mut();
+
+        let _guard = frame.enter();
+
+        let start = performance::now();
+        let poll = fut.as_mut().poll(cx);
+        let poll_ms = performance::now() - start;
+
+        metrics.polls += 1;
+        if poll_ms > metrics.longest_poll_ms {
+            metrics.longest_poll_ms = poll_ms;
+        }
+
+        if poll.is_ready() {
+            if let Some(complete) = complete.take() {
+                complete(*metrics);
+            }
+        }
+
+        poll
+    }
+}
+
+impl<C: emit::Ctxt, F> Drop for Task<C, F> {
+    fn drop(&mut self) {
+        // The task was dropped before its future completed, like when it panics
+        if let Some(complete) = self.complete.take() {
+            let _guard = self.frame.enter();
+
+            complete(self.metrics);
+        }
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use alloc::rc::Rc;
+    use core::{cell::RefCell, task::Waker};
+    use std::sync::Mutex;
+
+    use emit::Props as _;
+
+    use crate::test_util::sleep;
+
+    #[derive(Debug, PartialEq)]
+    struct Emitted {
+        mdl: String,
+        name: Option<String>,
+        lvl: Option<emit::Level>,
+        err: bool,
+        task_polls: Option<usize>,
+        ctxt: emit::SpanCtxt,
+    }
+
+    static EMITTED: Mutex<Vec<Emitted>> = Mutex::new(Vec::new());
+
+    fn rt() -> &'static Rt {
+        static SLOT: emit::runtime::AmbientSlot = emit::runtime::AmbientSlot::new();
+
+        let _ = SLOT.init(
+            emit::runtime::Runtime::new()
+                .with_emitter(emit::emitter::from_fn(|evt| {
+                    let props = evt.props();
+
+                    EMITTED.lock().unwrap().push(Emitted {
+                        mdl: evt.mdl().to_string(),
+                        name: props.pull("span_name"),
+                        lvl: props.pull("lvl"),
+                        err: props.get("err").is_some(),
+                        task_polls: props.pull("task_polls"),
+                        ctxt: emit::SpanCtxt::new(
+                            props.pull("trace_id"),
+                            props.pull("span_parent"),
+                            props.pull("span_id"),
+                        ),
+                    })
+                }))
+                .with_ctxt(emit::platform::thread_local_ctxt::ThreadLocalCtxt::shared())
+                .with_rng(crate::crypto_rng()),
+        );
+
+        SLOT.get()
+    }
+
+    fn take() -> Vec<Emitted> {
+        core::mem::take(&mut *EMITTED.lock().unwrap())
+    }
+
+    fn current() -> emit::SpanCtxt {
+        emit::SpanCtxt::current(rt().ctxt())
+    }
+
+    /**
+    A future that records the current span before and after yielding to the event loop.
+    */
+    fn observe_across_await() -> (Rc<RefCell<Vec<emit::SpanCtxt>>>, impl Future<Output = ()>) {
+        let observed = Rc::new(RefCell::new(Vec::new()));
+
+        let fut = {
+            let observed = observed.clone();
+
+            async move {
+                observed.borrow_mut().push(current());
+                sleep(0.0).await;
+                observed.borrow_mut().push(current());
+            }
+        };
+
+        (observed, fut)
+    }
+
+    #[wasm_bindgen_test]
+    async fn wrap_in_span() {
+        let rt = rt();
+        let parent = emit::SpanCtxt::new_root(rt.rng());
+
+        let (observed, fut) = observe_across_await();
+
+        emit::Frame::push(rt.ctxt(), parent).call(|| {
+            task(rt)
+                .span(emit::path!("emit_web::task::tests"), "load")
+                .spawn_local(fut)
+        });
+
+        sleep(10.0).await;
+
+        let emitted = take();
+        assert_eq!(1, emitted.len());
+
+        let span = &emitted[0];
+        assert_eq!("emit_web::task::tests", span.mdl);
+        assert_eq!(Some("load".into()), span.name);
+        assert_eq!(Some(2), span.task_polls);
+        assert!(!span.err);
+
+        assert_eq!(parent.trace_id(), span.ctxt.trace_id());
+        assert_eq!(parent.span_id(), span.ctxt.span_parent());
+
+        // The span is current on both sides of the `.await`
+        assert_eq!(vec![span.ctxt, span.ctxt], *observed.borrow());
+
+        assert_eq!(emit::SpanCtxt::empty(), current());
+    }
+
+    #[wasm_bindgen_test]
+    async fn keep_ctxt_without_span() {
+        let rt = rt();
+        let parent = emit::SpanCtxt::new_root(rt.rng());
+
+        let (observed, fut) = observe_across_await();
+
+        emit::Frame::push(rt.ctxt(), parent).call(|| spawn_local(rt, fut));
+
+        sleep(10.0).await;
+
+        assert_eq!(vec![parent, parent], *observed.borrow());
+
+        // Metrics aren't emitted unless they're asked for
+        assert!(take().is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    async fn report_metrics() {
+        let rt = rt();
+
+        task(rt)
+            .report_metrics(true)
+            .spawn_local(async { sleep(0.0).await });
+
+        sleep(10.0).await;
+
+        let emitted = take();
+        assert_eq!(1, emitted.len());
+
+        assert_eq!("emit_web::task", emitted[0].mdl);
+        assert_eq!(None, emitted[0].name);
+        assert_eq!(Some(emit::Level::Debug), emitted[0].lvl);
+        assert_eq!(Some(2), emitted[0].task_polls);
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn complete_span_when_dropped() {
+        let rt = rt();
+
+        let mut task = task(rt)
+            .span(emit::path!("emit_web::task::tests"), "pending")
+            .build(core::future::pending());
+
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(Pin::new(&mut task).poll(&mut cx).is_pending());
+        assert!(take().is_empty());
+
+        drop(task);
+
+        let emitted = take();
+        assert_eq!(1, emitted.len());
+        assert_eq!(Some("pending".into()), emitted[0].name);
+        assert_eq!(Some(1), emitted[0].task_polls);
+        assert!(!emitted[0].err);
+    }
+
+    // Panics can only be observed when they unwind
+    #[cfg(panic = "unwind")]
+    #[wasm_bindgen_test]
+    #[test]
+    fn report_panics() {
+        let rt = rt();
+
+        let task = task(rt)
+            .span(emit::path!("emit_web::task::tests"), "panic")
+            .build(async { panic!("explicit panic") });
+
+        // The task is dropped while the panic unwinds
+        let _ = std::panic::catch_unwind(core::panic::AssertUnwindSafe(move || {
+            let mut task = task;
+            let _ = Pin::new(&mut task).poll(&mut Context::from_waker(Waker::noop()));
+        }));
+
+        let emitted = take();
+        assert_eq!(1, emitted.len());
+        assert_eq!(Some("panic".into()), emitted[0].name);
+        assert_eq!(Some(emit::Level::Error), emitted[0].lvl);
+        assert!(emitted[0].err);
+    }
+}
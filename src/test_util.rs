@@ -0,0 +1,98 @@
+/*!
+Helpers shared by tests that run in JavaScript.
+*/
+
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(inline_js = "
+export function emit_web_test_save_globals(names) {
+    return Array.from(names, (name) => [name, Object.getOwnPropertyDescriptor(globalThis, name)]);
+}
+
+export function emit_web_test_restore_globals(saved) {
+    for (const [name, descriptor] of saved) {
+        if (descriptor) {
+            Object.defineProperty(globalThis, name, descriptor);
+        } else {
+            delete globalThis[name];
+        }
+    }
+}
+")]
+extern "C" {
+    fn emit_web_test_save_globals(names: Vec<String>) -> JsValue;
+    fn emit_web_test_restore_globals(saved: &JsValue);
+}
+
+/**
+Globals that are restored to their original values when dropped.
+
+Stubs that replace globals like `fetch` or `document` return one of these, so they don't leak into other tests.
+*/
+#[must_use = "globals are restored as soon as this value is dropped"]
+pub struct Globals(JsValue);
+
+impl Globals {
+    /**
+    Save the current values of the globals in `names`.
+    */
+    pub fn save(names: &[&str]) -> Self {
+        Globals(emit_web_test_save_globals(
+            names.iter().map(|name| String::from(*name)).collect(),
+        ))
+    }
+}
+
+impl Drop for Globals {
+    fn drop(&mut self) {
+        emit_web_test_restore_globals(&self.0);
+    }
+}
+
+/**
+The root span of the page, that's restored when dropped.
+
+Tests that start a page-load span or trace routes hold one of these, so their root span doesn't become the parent of spans in other tests.
+*/
+#[must_use = "the root span is restored as soon as this value is dropped"]
+pub struct Root(emit::SpanCtxt);
+
+impl Root {
+    /**
+    Save the current root span.
+    */
+    pub fn save() -> Self {
+        Root(crate::root_ctxt())
+    }
+}
+
+impl Drop for Root {
+    fn drop(&mut self) {
+        crate::root::replace(self.0);
+    }
+}
+
+/**
+Get the property `key` from a JavaScript object.
+*/
+pub fn get(value: &JsValue, key: &str) -> JsValue {
+    Reflect::get(value, &JsValue::from(key)).unwrap()
+}
+
+/**
+Wait for at least `millis` on the JavaScript event loop.
+*/
+pub async fn sleep(millis: f64) {
+    let promise =
+        js_sys::Promise::new(&mut |resolve, _| crate::timers::set_timeout(&resolve, millis));
+
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
+
+/**
+Wait for timers that were just scheduled, and the microtasks they queue, to run.
+*/
+pub async fn tick() {
+    sleep(10.0).await;
+}
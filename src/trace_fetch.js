@@ -0,0 +1,118 @@
+// Wraps the global `fetch` for the `FetchTracer` in `emit_web`.
+//
+// Each request calls back into Rust with its method, scrubbed URL, and
+// whether its origin is allowed to receive trace context. Rust starts a span
+// and returns the headers to add, along with a function to call with the
+// status of the response, or `0` and an error if the request fails.
+//
+// The `fetch` that was wrapped by the first tracer is kept on a well-known
+// symbol, so requests made by `emit_web` itself aren't traced. The number of
+// installed tracers is kept on another, so the symbol is only removed once
+// they've all been uninstalled.
+
+const UNTRACED = Symbol.for("emit_web.fetch");
+const TRACERS = Symbol.for("emit_web.fetch.tracers");
+
+function origin(url) {
+    try {
+        return new URL(url, globalThis.location?.href).origin;
+    } catch {
+        return null;
+    }
+}
+
+// Fields of `init` that aren't kept by the `Request` are passed through to
+// `fetch`, but its headers and body have already been taken by the request
+function passthrough(init, request) {
+    if (init == null) {
+        return undefined;
+    }
+
+    const { body, ...rest } = init;
+
+    return { ...rest, headers: request.headers };
+}
+
+export function emit_web_trace_fetch_install(origins, begin) {
+    if (typeof fetch !== "function" || typeof Request !== "function") {
+        return null;
+    }
+
+    const previous = fetch;
+    const allowed = new Set(origins.map(origin).filter((origin) => origin !== null));
+
+    const installed = { previous, wrapper: null, active: true };
+
+    if (!(UNTRACED in globalThis)) {
+        globalThis[UNTRACED] = previous;
+    }
+
+    globalThis[TRACERS] = (globalThis[TRACERS] ?? 0) + 1;
+
+    installed.wrapper = function fetch(input, init) {
+        if (!installed.active) {
+            return previous.call(globalThis, input, init);
+        }
+
+        let request;
+        try {
+            request = new Request(input, init);
+        } catch {
+            // Let `fetch` report invalid requests itself
+            return previous.call(globalThis, input, init);
+        }
+
+        // Only the origin and path are recorded, since query strings
+        // and fragments often carry tokens or personal data
+        const url = new URL(request.url);
+        const scrubbed = `${url.origin}${url.pathname}`;
+
+        const inject = url.origin === globalThis.location?.origin || allowed.has(url.origin);
+
+        const [headers, end] = begin(request.method, scrubbed, inject);
+
+        for (const [name, value] of headers) {
+            try {
+                request.headers.set(name, value);
+            } catch {
+                // Requests with `mode: "no-cors"` can't carry custom headers
+            }
+        }
+
+        return previous.call(globalThis, request, passthrough(init, request)).then(
+            (res) => {
+                end(res.status, undefined);
+                return res;
+            },
+            (err) => {
+                end(0, String(err));
+                throw err;
+            },
+        );
+    };
+
+    globalThis.fetch = installed.wrapper;
+
+    return installed;
+}
+
+export function emit_web_trace_fetch_uninstall(installed) {
+    if (!installed) {
+        return;
+    }
+
+    // The wrapper may have been captured before it's uninstalled,
+    // so it passes requests through from now on
+    installed.active = false;
+
+    if (globalThis.fetch === installed.wrapper) {
+        globalThis.fetch = installed.previous;
+    }
+
+    globalThis[TRACERS] -= 1;
+
+    if (globalThis[TRACERS] <= 0) {
+        delete globalThis[UNTRACED];
+        delete globalThis[TRACERS];
+    }
+}
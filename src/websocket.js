@@ -0,0 +1,135 @@
+// Streams events over a WebSocket for the `WebSocketEmitter` in `emit_web`.
+//
+// Events are buffered while the socket is disconnected, and the socket is
+// reconnected with exponential backoff. The backoff is only reset once a
+// connection is stable, so a server that accepts and then immediately drops
+// connections isn't reconnected to in a tight loop. Text messages from the
+// server are handed back to Rust to update the emitter's filter.
+
+const OPEN = 1;
+
+// Connections that stay open this long, or receive a message, are stable
+const STABLE = 5000;
+
+export function emit_web_ws_open(url, maxBuffer, backoff, maxBackoff, control) {
+    const ws = {
+        url,
+        maxBuffer,
+        backoff,
+        maxBackoff,
+        control,
+        socket: null,
+        buffer: [],
+        attempts: 0,
+        timer: null,
+        stable: null,
+        closed: false,
+    };
+
+    connect(ws);
+
+    return ws;
+}
+
+function connect(ws) {
+    // Without WebSockets, events are only buffered
+    if (ws.closed || typeof WebSocket === "undefined") {
+        return;
+    }
+
+    let socket;
+    try {
+        socket = new WebSocket(ws.url);
+    } catch {
+        reconnect(ws);
+        return;
+    }
+
+    ws.socket = socket;
+
+    socket.onopen = () => {
+        ws.stable = setTimeout(() => stable(ws), STABLE);
+        flush(ws);
+    };
+
+    socket.onmessage = (e) => {
+        stable(ws);
+
+        if (typeof e.data === "string") {
+            ws.control(e.data);
+        }
+    };
+
+    // Errors are always followed by a close
+    socket.onclose = () => {
+        if (ws.socket === socket) {
+            clearTimeout(ws.stable);
+            ws.stable = null;
+            ws.socket = null;
+            reconnect(ws);
+        }
+    };
+}
+
+function stable(ws) {
+    clearTimeout(ws.stable);
+    ws.stable = null;
+    ws.attempts = 0;
+}
+
+function reconnect(ws) {
+    if (ws.closed || ws.timer !== null) {
+        return;
+    }
+
+    const delay = Math.min(ws.backoff * 2 ** ws.attempts, ws.maxBackoff);
+    ws.attempts++;
+
+    ws.timer = setTimeout(() => {
+        ws.timer = null;
+        connect(ws);
+    }, delay);
+}
+
+function isOpen(ws) {
+    return ws.socket !== null && ws.socket.readyState === OPEN;
+}
+
+function flush(ws) {
+    while (ws.buffer.length > 0 && isOpen(ws)) {
+        ws.socket.send(ws.buffer.shift());
+    }
+}
+
+export function emit_web_ws_send(ws, record) {
+    if (isOpen(ws)) {
+        ws.socket.send(record);
+        return;
+    }
+
+    ws.buffer.push(record);
+
+    if (ws.buffer.length > ws.maxBuffer) {
+        ws.buffer.shift();
+    }
+}
+
+export function emit_web_ws_is_flushed(ws) {
+    return ws.buffer.length === 0 && (ws.socket?.bufferedAmount ?? 0) === 0;
+}
+
+export function emit_web_ws_close(ws) {
+    ws.closed = true;
+
+    clearTimeout(ws.timer);
+    ws.timer = null;
+
+    clearTimeout(ws.stable);
+    ws.stable = null;
+
+    if (ws.socket !== null) {
+        ws.socket.onclose = null;
+        ws.socket.close();
+        ws.socket = null;
+    }
+}
@@ -0,0 +1,451 @@
+/*!
+Stream events to a remote endpoint over a WebSocket.
+*/
+
+use alloc::{
+    rc::{Rc, Weak},
+    string::String,
+};
+use core::{cell::RefCell, cmp, time::Duration};
+
+use js_sys::{Reflect, JSON};
+use wasm_bindgen::prelude::*;
+
+use crate::{duration_millis_f64, ser, Local};
+
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/**
+A [`WebSocketEmitter`] that streams events to `url` over a [WebSocket](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket).
+
+See [`WebSocketEmitterBuilder`] for configuration.
+*/
+pub fn websocket(url: impl Into<String>) -> WebSocketEmitterBuilder {
+    WebSocketEmitterBuilder::new(url)
+}
+
+/**
+A builder for a [`WebSocketEmitter`].
+*/
+pub struct WebSocketEmitterBuilder {
+    url: String,
+    max_buffer_len: usize,
+    reconnect_backoff: Duration,
+}
+
+impl WebSocketEmitterBuilder {
+    /**
+    Create a new builder for an emitter that streams events to `url`.
+    */
+    pub fn new(url: impl Into<String>) -> Self {
+        WebSocketEmitterBuilder {
+            url: url.into(),
+            max_buffer_len: 1000,
+            reconnect_backoff: Duration::from_millis(500),
+        }
+    }
+
+    /**
+    Set the maximum number of events to keep while disconnected.
+
+    When the buffer is full, the oldest events are dropped to make room for new ones.
+    The default maximum is 1000 events.
+    */
+    pub fn max_buffer_len(mut self, max_buffer_len: usize) -> Self {
+        self.max_buffer_len = cmp::max(1, max_buffer_len);
+        self
+    }
+
+    /**
+    Set the time to wait before the first attempt to reconnect.
+
+    The time doubles after each failed attempt, up to a maximum of 30 seconds.
+    The default backoff is 500 milliseconds.
+    */
+    pub fn reconnect_backoff(mut self, reconnect_backoff: Duration) -> Self {
+        self.reconnect_backoff = reconnect_backoff;
+        self
+    }
+
+    /**
+    Build a [`WebSocketEmitter`] from this configuration.
+
+    The socket starts connecting immediately.
+    */
+    pub fn build(self) -> WebSocketEmitter {
+        let inner = Rc::new_cyclic(|inner: &Weak<Inner>| {
+            let control = inner.clone();
+
+            Inner {
+                socket: shim::emit_web_ws_open(
+                    &self.url,
+                    self.max_buffer_len,
+                    duration_millis_f64(self.reconnect_backoff),
+                    duration_millis_f64(cmp::max(self.reconnect_backoff, MAX_RECONNECT_BACKOFF)),
+                    &Closure::<dyn FnMut(String)>::new(move |msg: String| {
+                        if let (Some(inner), Some(filter)) =
+                            (control.upgrade(), Filter::parse(&msg))
+                        {
+                            *inner.filter.borrow_mut() = filter;
+                        }
+                    })
+                    .into_js_value(),
+                ),
+                filter: RefCell::new(Filter::default()),
+            }
+        });
+
+        WebSocketEmitter {
+            inner: Local(inner),
+        }
+    }
+}
+
+/**
+An emitter that streams events to a remote endpoint over a [WebSocket](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket).
+
+This is useful for live-tailing events from devices without accessible developer tools, like kiosks and smart TVs.
+Each event is sent as a text message containing a JSON object, in the schema described by [`crate::FetchFormat`].
+
+While the socket is disconnected, events are kept in a bounded buffer and sent once it reconnects.
+The socket is reconnected with an exponential backoff, which is reset once a connection has stayed open for a few seconds, or received a message from the server.
+
+The server can control which events are sent by sending a text message containing a JSON object with the following fields:
+
+- `min_lvl`: The minimum level of events to send, like `"warn"`. Events without a level are treated as `"info"`.
+- `mdl`: The module events must be emitted from, or a child of, like `"my_app::net"`.
+
+Each message replaces the current filter, so an empty object `{}` sends all events again.
+Messages that aren't valid filters are ignored.
+
+The socket is closed when the emitter is dropped.
+
+Use [`websocket`] to configure a new emitter.
+*/
+pub struct WebSocketEmitter {
+    inner: Local<Rc<Inner>>,
+}
+
+struct Inner {
+    socket: JsValue,
+    filter: RefCell<Filter>,
+}
+
+#[derive(Default)]
+struct Filter {
+    min_lvl: Option<emit::Level>,
+    mdl: Option<String>,
+}
+
+impl Filter {
+    fn parse(msg: &str) -> Option<Self> {
+        let msg = JSON::parse(msg).ok()?;
+
+        if !msg.is_object() {
+            return None;
+        }
+
+        let field = |key| {
+            Reflect::get(&msg, &JsValue::from(key))
+                .ok()
+                .and_then(|value| value.as_string())
+        };
+
+        let min_lvl = match field("min_lvl") {
+            Some(lvl) => Some(lvl.parse().ok()?),
+            None => None,
+        };
+
+        let mdl = match field("mdl") {
+            Some(mdl) if emit::path::is_valid_path(&mdl) => Some(mdl),
+            Some(_) => return None,
+            None => None,
+        };
+
+        Some(Filter { min_lvl, mdl })
+    }
+
+    fn matches(&self, evt: &emit::Event<impl emit::Props>) -> bool {
+        if let Some(min_lvl) = self.min_lvl {
+            let lvl = evt
+                .props()
+                .pull::<emit::Level, _>(emit::well_known::KEY_LVL)
+                .unwrap_or_default();
+
+            if lvl < min_lvl {
+                return false;
+            }
+        }
+
+        if let Some(ref mdl) = self.mdl {
+            if !evt.mdl().is_child_of(&emit::Path::new_ref_raw(mdl)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl emit::Emitter for WebSocketEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let Ok(filter) = self.inner.filter.try_borrow() else {
+            return;
+        };
+
+        if !filter.matches(&evt) {
+            return;
+        }
+
+        let mut buf = ser::Buf::new(false);
+        buf.record(&evt, None);
+
+        shim::emit_web_ws_send(&self.inner.socket, &buf.json());
+    }
+
+    // Sockets can't be waited on synchronously,
+    // so this only reports whether everything has already been sent
+    fn blocking_flush(&self, _: Duration) -> bool {
+        shim::emit_web_ws_is_flushed(&self.inner.socket)
+    }
+}
+
+impl Drop for WebSocketEmitter {
+    fn drop(&mut self) {
+        shim::emit_web_ws_close(&self.inner.socket);
+    }
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/websocket.js")]
+    extern "C" {
+        pub fn emit_web_ws_open(
+            url: &str,
+            max_buffer: usize,
+            backoff: f64,
+            max_backoff: f64,
+            control: &JsValue,
+        ) -> JsValue;
+        pub fn emit_web_ws_send(ws: &JsValue, record: &str);
+        pub fn emit_web_ws_is_flushed(ws: &JsValue) -> bool;
+        pub fn emit_web_ws_close(ws: &JsValue);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use emit::Emitter as _;
+    use js_sys::Array;
+
+    use crate::test_util::{get, sleep, Globals};
+
+    #[wasm_bindgen(inline_js = "
+    export function stub_server() {
+        const server = { sockets: [], sent: [] };
+
+        globalThis.WebSocket = class {
+            constructor(url) {
+                this.url = url;
+                this.readyState = 0;
+                this.bufferedAmount = 0;
+
+                server.sockets.push(this);
+            }
+
+            send(data) {
+                server.sent.push(data);
+            }
+
+            close() {
+                this.readyState = 3;
+            }
+        };
+
+        const latest = () => server.sockets[server.sockets.length - 1];
+
+        server.accept = () => {
+            latest().readyState = 1;
+            latest().onopen?.();
+        };
+        server.push = (data) => latest().onmessage?.({ data });
+        server.disconnect = () => {
+            latest().readyState = 3;
+            latest().onclose?.();
+        };
+
+        return server;
+    }
+    ")]
+    extern "C" {
+        #[wasm_bindgen(js_name = stub_server)]
+        fn stub_server_js() -> JsValue;
+    }
+
+    fn stub_server() -> (JsValue, Globals) {
+        let globals = Globals::save(&["WebSocket"]);
+
+        (stub_server_js(), globals)
+    }
+
+    fn call(server: &JsValue, method: &str, args: &Array) {
+        get(server, method)
+            .dyn_into::<js_sys::Function>()
+            .unwrap()
+            .apply(server, args)
+            .unwrap();
+    }
+
+    fn sent(server: &JsValue) -> Vec<String> {
+        Array::from(&get(server, "sent"))
+            .iter()
+            .map(|record| {
+                get(&JSON::parse(&record.as_string().unwrap()).unwrap(), "msg")
+                    .as_string()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn sockets(server: &JsValue) -> u32 {
+        Array::from(&get(server, "sockets")).length()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn buffer_until_connected() {
+        let (server, _globals) = stub_server();
+        let emitter = websocket("ws://localhost/tail").build();
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+
+        assert!(sent(&server).is_empty());
+        assert!(!emitter.blocking_flush(Duration::from_secs(1)));
+
+        call(&server, "accept", &Array::new());
+
+        assert_eq!(vec!["event 1", "event 2"], sent(&server));
+        assert!(emitter.blocking_flush(Duration::from_secs(1)));
+
+        emitter.emit(emit::evt!("event {n}", n: 3));
+
+        assert_eq!(vec!["event 1", "event 2", "event 3"], sent(&server));
+    }
+
+    #[wasm_bindgen_test]
+    async fn reconnect_with_backoff() {
+        let (server, _globals) = stub_server();
+        let emitter = websocket("ws://localhost/tail")
+            .max_buffer_len(2)
+            .reconnect_backoff(Duration::from_millis(10))
+            .build();
+
+        call(&server, "accept", &Array::new());
+        call(&server, "disconnect", &Array::new());
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::evt!("event {n}", n: 2));
+        emitter.emit(emit::evt!("event {n}", n: 3));
+
+        sleep(50.0).await;
+
+        assert_eq!(2, sockets(&server));
+
+        call(&server, "accept", &Array::new());
+
+        assert_eq!(vec!["event 2", "event 3"], sent(&server));
+
+        // The socket isn't reconnected once the emitter is dropped
+        drop(emitter);
+        call(&server, "disconnect", &Array::new());
+
+        sleep(50.0).await;
+
+        assert_eq!(2, sockets(&server));
+    }
+
+    #[wasm_bindgen_test]
+    async fn keep_backoff_until_stable() {
+        let (server, _globals) = stub_server();
+        let _emitter = websocket("ws://localhost/tail")
+            .reconnect_backoff(Duration::from_millis(20))
+            .build();
+
+        call(&server, "accept", &Array::new());
+        call(&server, "disconnect", &Array::new());
+
+        sleep(30.0).await;
+
+        assert_eq!(2, sockets(&server));
+
+        // Dropped straight after opening, so the backoff doubles
+        call(&server, "accept", &Array::new());
+        call(&server, "disconnect", &Array::new());
+
+        sleep(30.0).await;
+
+        assert_eq!(2, sockets(&server));
+
+        sleep(30.0).await;
+
+        assert_eq!(3, sockets(&server));
+
+        // A message from the server means the connection is stable
+        call(&server, "accept", &Array::new());
+        call(&server, "push", &Array::of1(&"{}".into()));
+        call(&server, "disconnect", &Array::new());
+
+        sleep(30.0).await;
+
+        assert_eq!(4, sockets(&server));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn apply_server_filter() {
+        let (server, _globals) = stub_server();
+        let emitter = websocket("ws://localhost/tail").build();
+
+        call(&server, "accept", &Array::new());
+
+        call(
+            &server,
+            "push",
+            &Array::of1(&r#"{"min_lvl":"warn"}"#.into()),
+        );
+
+        emitter.emit(emit::evt!("event {n}", n: 1));
+        emitter.emit(emit::warn_evt!("event {n}", n: 2));
+
+        call(
+            &server,
+            "push",
+            &Array::of1(&r#"{"mdl":"emit_web::websocket::other"}"#.into()),
+        );
+
+        emitter.emit(emit::warn_evt!("event {n}", n: 3));
+
+        // Invalid filters are ignored
+        call(
+            &server,
+            "push",
+            &Array::of1(&r#"{"min_lvl":"loud"}"#.into()),
+        );
+        call(&server, "push", &Array::of1(&"not json".into()));
+
+        emitter.emit(emit::warn_evt!("event {n}", n: 4));
+
+        call(&server, "push", &Array::of1(&"{}".into()));
+
+        emitter.emit(emit::evt!("event {n}", n: 5));
+
+        assert_eq!(vec!["event 2", "event 5"], sent(&server));
+    }
+}
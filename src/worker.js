@@ -0,0 +1,71 @@
+// Forwards events from a worker to the main thread for the
+// `WorkerBridgeEmitter` in `emit_web`.
+//
+// Batches of records are encoded as newline-delimited JSON into an
+// `ArrayBuffer` that's transferred, rather than copied, to the main thread.
+// Messages carrying events have an `emitWebEvents` field so they can be
+// told apart from the application's own messages.
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+export function emit_web_bridge_new(target, batchSize, flushInterval) {
+    return {
+        target: target ?? globalThis,
+        batchSize,
+        flushInterval,
+        records: [],
+        timer: null,
+    };
+}
+
+export function emit_web_bridge_push(bridge, record) {
+    bridge.records.push(record);
+
+    if (bridge.records.length >= bridge.batchSize) {
+        emit_web_bridge_flush(bridge);
+    } else if (bridge.timer === null) {
+        bridge.timer = setTimeout(() => emit_web_bridge_flush(bridge), bridge.flushInterval);
+    }
+}
+
+export function emit_web_bridge_flush(bridge) {
+    if (bridge.timer !== null) {
+        clearTimeout(bridge.timer);
+        bridge.timer = null;
+    }
+
+    if (bridge.records.length === 0) {
+        return;
+    }
+
+    // Records are JSON, so they never contain a newline
+    const buf = encoder.encode(bridge.records.join("\n")).buffer;
+    bridge.records = [];
+
+    try {
+        bridge.target.postMessage({ emitWebEvents: buf }, [buf]);
+    } catch {
+        // The target can't receive messages
+    }
+}
+
+export function emit_web_bridge_install(worker, f) {
+    worker.addEventListener("message", (e) => {
+        const buf = e.data?.emitWebEvents;
+
+        if (!(buf instanceof ArrayBuffer)) {
+            return;
+        }
+
+        // Hide events from the application's own listeners
+        e.stopImmediatePropagation();
+
+        for (const record of decoder.decode(buf).split("\n")) {
+            f(record);
+        }
+    });
+
+    // Ports only deliver messages once they're started
+    worker.start?.();
+}
@@ -0,0 +1,317 @@
+/*!
+Forward events from a Web Worker to the main thread.
+*/
+
+use alloc::string::String;
+use core::{cmp, time::Duration};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{duration_millis_f64, record::Received, ser, Local};
+
+/**
+A [`WorkerBridgeEmitter`] that forwards events from a worker to the main thread.
+
+See [`WorkerBridgeEmitterBuilder`] for configuration.
+*/
+pub fn worker_bridge() -> WorkerBridgeEmitterBuilder {
+    WorkerBridgeEmitterBuilder::new()
+}
+
+/**
+A builder for a [`WorkerBridgeEmitter`].
+*/
+pub struct WorkerBridgeEmitterBuilder {
+    target: Option<JsValue>,
+    batch_size: usize,
+    flush_interval: Duration,
+}
+
+impl WorkerBridgeEmitterBuilder {
+    /**
+    Create a new builder for an emitter that forwards events to the main thread.
+    */
+    pub fn new() -> Self {
+        WorkerBridgeEmitterBuilder {
+            target: None,
+            batch_size: 100,
+            flush_interval: Duration::from_millis(100),
+        }
+    }
+
+    /**
+    Set the object to post events to, like a [`MessagePort`](https://developer.mozilla.org/en-US/docs/Web/API/MessagePort).
+
+    The default target is the worker's global scope, which posts events to the `Worker` object on the main thread.
+    */
+    pub fn target(mut self, target: JsValue) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /**
+    Set the maximum number of events to post in a single message.
+
+    A message is posted as soon as this many events are queued, without waiting for the flush interval.
+    The default batch size is 100 events.
+    */
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = cmp::max(1, batch_size);
+        self
+    }
+
+    /**
+    Set the time to wait before posting events that don't fill a batch.
+
+    The default interval is 100 milliseconds.
+    */
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /**
+    Build a [`WorkerBridgeEmitter`] from this configuration.
+    */
+    pub fn build(self) -> WorkerBridgeEmitter {
+        WorkerBridgeEmitter {
+            bridge: Local(shim::emit_web_bridge_new(
+                self.target.as_ref().unwrap_or(&JsValue::UNDEFINED),
+                self.batch_size,
+                duration_millis_f64(self.flush_interval),
+            )),
+        }
+    }
+}
+
+impl Default for WorkerBridgeEmitterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+An emitter that forwards events from a [Web Worker](https://developer.mozilla.org/en-US/docs/Web/API/Web_Workers_API) to the main thread using [`postMessage`](https://developer.mozilla.org/en-US/docs/Web/API/DedicatedWorkerGlobalScope/postMessage).
+
+Events are batched into newline-delimited JSON, in the schema described by [`crate::FetchFormat`], and posted as an `ArrayBuffer` that's transferred rather than copied.
+Messages carrying events have an `emitWebEvents` field.
+On the main thread, [`install_worker_bridge`] receives them from the `Worker` and emits them locally.
+
+Any queued events are posted when the emitter is flushed or dropped.
+
+Use [`worker_bridge`] to configure a new emitter.
+*/
+pub struct WorkerBridgeEmitter {
+    bridge: Local<JsValue>,
+}
+
+impl emit::Emitter for WorkerBridgeEmitter {
+    fn emit<E: emit::event::ToEvent>(&self, evt: E) {
+        let evt = evt.to_event();
+
+        let mut buf = ser::Buf::new(false);
+        buf.record(&evt, None);
+
+        shim::emit_web_bridge_push(&self.bridge, &buf.json());
+    }
+
+    fn blocking_flush(&self, _: Duration) -> bool {
+        shim::emit_web_bridge_flush(&self.bridge);
+
+        true
+    }
+}
+
+impl Drop for WorkerBridgeEmitter {
+    fn drop(&mut self) {
+        shim::emit_web_bridge_flush(&self.bridge);
+    }
+}
+
+/**
+Receive events posted by a [`WorkerBridgeEmitter`] in `worker`, and emit them through `emitter`.
+
+The `worker` is a [`Worker`](https://developer.mozilla.org/en-US/docs/Web/API/Worker), or any other object that receives messages from the [`WorkerBridgeEmitterBuilder::target`], like a [`MessagePort`](https://developer.mozilla.org/en-US/docs/Web/API/MessagePort).
+Received events keep their original module, template, extent, and props, including their trace and span ids.
+
+Messages carrying events aren't seen by the application's own `message` listeners as long as the bridge is installed before them.
+Events are received for as long as the `worker` is alive.
+*/
+pub fn install_worker_bridge(worker: &JsValue, emitter: impl emit::Emitter + 'static) {
+    shim::emit_web_bridge_install(
+        worker,
+        &Closure::<dyn FnMut(String)>::new(move |record: String| {
+            if let Some(received) = Received::from_json(&record) {
+                received.emit(&emitter, emit::Empty);
+            }
+        })
+        .into_js_value(),
+    );
+}
+
+mod shim {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/worker.js")]
+    extern "C" {
+        pub fn emit_web_bridge_new(
+            target: &JsValue,
+            batch_size: usize,
+            flush_interval: f64,
+        ) -> JsValue;
+        pub fn emit_web_bridge_push(bridge: &JsValue, record: &str);
+        pub fn emit_web_bridge_flush(bridge: &JsValue);
+        pub fn emit_web_bridge_install(worker: &JsValue, f: &JsValue);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    use core::cell::RefCell;
+    use std::rc::Rc;
+
+    use emit::{Emitter as _, Props as _};
+
+    use crate::test_util::{get, sleep};
+
+    #[wasm_bindgen(inline_js = "
+    export function message_channel() {
+        const channel = new MessageChannel();
+
+        return { worker: channel.port1, main: channel.port2, app: [] };
+    }
+
+    export function listen_app(channel) {
+        channel.main.addEventListener('message', (e) => channel.app.push(e.data));
+    }
+
+    export function close_channel(channel) {
+        channel.worker.close();
+    }
+    ")]
+    extern "C" {
+        fn message_channel() -> JsValue;
+        fn listen_app(channel: &JsValue);
+        fn close_channel(channel: &JsValue);
+    }
+
+    type Collected = Rc<RefCell<Vec<(String, Option<emit::Extent>, Option<emit::TraceId>)>>>;
+
+    fn install(channel: &JsValue) -> Collected {
+        let received: Collected = Rc::new(RefCell::new(Vec::new()));
+
+        install_worker_bridge(
+            &get(channel, "main"),
+            emit::emitter::from_fn({
+                let received = received.clone();
+
+                move |evt| {
+                    received.borrow_mut().push((
+                        evt.msg().to_string(),
+                        evt.extent().cloned(),
+                        evt.props().pull("trace_id"),
+                    ))
+                }
+            }),
+        );
+
+        received
+    }
+
+    #[wasm_bindgen_test]
+    async fn forward_events() {
+        let channel = message_channel();
+        let received = install(&channel);
+
+        let emitter = worker_bridge().target(get(&channel, "worker")).build();
+
+        let extent = emit::Timestamp::from_unix(Duration::from_secs(1)).unwrap()
+            ..emit::Timestamp::from_unix(Duration::from_secs(2)).unwrap();
+
+        emitter.emit(emit::evt!(
+            extent: extent.clone(),
+            "work {n}",
+            n: 1,
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736",
+        ));
+        emitter.emit(emit::evt!("event"));
+
+        sleep(200.0).await;
+
+        {
+            let received = received.borrow();
+
+            assert_eq!(2, received.len());
+
+            let (msg, ts, trace_id) = &received[0];
+
+            assert_eq!("work 1", msg);
+            assert_eq!(Some(&extent), ts.as_ref().and_then(|ts| ts.as_range()));
+            assert_eq!(
+                emit::TraceId::from_u128(0x4bf92f3577b34da6a3ce929d0e0e4736),
+                *trace_id
+            );
+
+            assert_eq!("event", received[1].0);
+        }
+
+        close_channel(&channel);
+    }
+
+    #[wasm_bindgen_test]
+    async fn flush_immediately() {
+        let channel = message_channel();
+        let received = install(&channel);
+
+        let emitter = worker_bridge()
+            .target(get(&channel, "worker"))
+            .flush_interval(Duration::from_secs(60))
+            .build();
+
+        emitter.emit(emit::evt!("event"));
+
+        sleep(10.0).await;
+        assert!(received.borrow().is_empty());
+
+        assert!(emitter.blocking_flush(Duration::from_secs(1)));
+
+        sleep(10.0).await;
+        assert_eq!(1, received.borrow().len());
+
+        close_channel(&channel);
+    }
+
+    #[wasm_bindgen_test]
+    async fn hide_events_from_app() {
+        let channel = message_channel();
+        let received = install(&channel);
+        listen_app(&channel);
+
+        let emitter = worker_bridge()
+            .target(get(&channel, "worker"))
+            .batch_size(1)
+            .build();
+
+        emitter.emit(emit::evt!("event"));
+
+        let worker = get(&channel, "worker");
+        get(&worker, "postMessage")
+            .dyn_into::<js_sys::Function>()
+            .unwrap()
+            .call1(&worker, &"app message".into())
+            .unwrap();
+
+        sleep(10.0).await;
+
+        assert_eq!(1, received.borrow().len());
+
+        let app = js_sys::Array::from(&get(&channel, "app"));
+        assert_eq!(1, app.length());
+        assert_eq!(JsValue::from("app message"), app.get(0));
+
+        close_channel(&channel);
+    }
+}